@@ -0,0 +1,132 @@
+//! This module provides parsing of the command line arguments.
+
+/// The usage text printed by `--help`.
+pub const USAGE: &str = concat!(
+    "Usage:\n",
+    "  wsl-usb-manager [--minimized] [--portable] [--verbose]\n",
+    "  wsl-usb-manager list [--json]\n",
+    "  wsl-usb-manager attach --busid <BUSID> [--distribution <NAME>]\n",
+    "  wsl-usb-manager detach --busid <BUSID>\n",
+    "  wsl-usb-manager bind --busid <BUSID> [--force]\n",
+    "  wsl-usb-manager unbind --busid <BUSID>\n",
+    "\n",
+    "Without a command, the GUI is started.\n",
+    "\n",
+    "Options:\n",
+    "  --minimized  Start the GUI minimized to the tray\n",
+    "  --portable   Store the settings next to the executable, as does a portable.txt file there\n",
+    "  --json       Print the device list as JSON, for scripts\n",
+    "  --verbose    Log more details, and print the log to stderr (debug builds only)\n",
+    "  --help       Print this help message\n",
+    "  --version    Print the version",
+);
+
+/// A command that runs without starting the GUI.
+pub enum CliCommand {
+    Help,
+    Version,
+    List {
+        json: bool,
+    },
+    Attach {
+        bus_id: String,
+        distribution: Option<String>,
+    },
+    Detach {
+        bus_id: String,
+    },
+    Bind {
+        bus_id: String,
+        force: bool,
+    },
+    Unbind {
+        bus_id: String,
+    },
+}
+
+/// The command line arguments the app was started with.
+#[derive(Default)]
+pub struct Args {
+    /// Start minimized to the tray (`--minimized`).
+    pub minimized: bool,
+
+    /// Store the settings next to the executable (`--portable`).
+    pub portable: bool,
+
+    /// Write debug entries to the log, and mirror it to stderr in debug builds (`--verbose`).
+    pub verbose: bool,
+
+    /// The command to run instead of starting the GUI, if any.
+    pub command: Option<CliCommand>,
+}
+
+impl Args {
+    /// Parses the command line arguments of the current process.
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses the given command line arguments, excluding the executable name.
+    fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        let mut command_name = None;
+        let mut bus_id = None;
+        let mut distribution = None;
+        let mut force = false;
+        let mut json = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--minimized" => parsed.minimized = true,
+                "--portable" => parsed.portable = true,
+                "--verbose" | "-v" => parsed.verbose = true,
+                "--help" | "-h" => parsed.command = Some(CliCommand::Help),
+                "--version" | "-V" => parsed.command = Some(CliCommand::Version),
+                "--busid" | "-b" => {
+                    bus_id = Some(args.next().ok_or("--busid requires a value.")?);
+                }
+                "--distribution" | "-d" => {
+                    distribution = Some(args.next().ok_or("--distribution requires a value.")?);
+                }
+                "--force" | "-f" => force = true,
+                "--json" => json = true,
+                "list" | "--list" | "attach" | "--attach" | "detach" | "--detach" | "bind"
+                | "--bind" | "unbind" | "--unbind"
+                    if command_name.is_none() =>
+                {
+                    command_name = Some(arg.trim_start_matches("--").to_owned());
+                }
+                _ => return Err(format!("Unexpected argument '{arg}'.")),
+            }
+        }
+
+        // Help and version take precedence over any other command
+        if parsed.command.is_some() {
+            return Ok(parsed);
+        }
+
+        let mut require_bus_id = || bus_id.take().ok_or("This command requires --busid.");
+        parsed.command = match command_name.as_deref() {
+            None => None,
+            Some("list") => Some(CliCommand::List { json }),
+            Some("attach") => Some(CliCommand::Attach {
+                bus_id: require_bus_id()?,
+                distribution,
+            }),
+            Some("detach") => Some(CliCommand::Detach {
+                bus_id: require_bus_id()?,
+            }),
+            Some("bind") => Some(CliCommand::Bind {
+                bus_id: require_bus_id()?,
+                force,
+            }),
+            Some(_) => Some(CliCommand::Unbind {
+                bus_id: require_bus_id()?,
+            }),
+        };
+
+        Ok(parsed)
+    }
+}
@@ -1,17 +1,120 @@
 use std::{
     collections::{HashMap, HashSet},
+    fmt::Display,
     hash::{Hash, Hasher},
+    io::Read,
+    path::PathBuf,
+    process::Child,
+    sync::atomic::{AtomicBool, Ordering},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::usbipd::UsbDevice;
+use crate::logging;
+use crate::settings;
+use crate::usbipd::{self, UsbDevice};
+
+/// The name of the file auto-attach profiles are saved to, inside the settings directory.
+const PROFILES_FILE_NAME: &str = "auto_attach.json";
+
+/// The name of the copy kept of a profiles file that could not be fully loaded,
+/// inside the settings directory.
+const PROFILES_BACKUP_FILE_NAME: &str = "auto_attach.backup.json";
+
+/// The name of the file auto-bind rules are saved to, inside the settings directory.
+const BIND_RULES_FILE_NAME: &str = "auto_bind.json";
+
+/// How long a new auto-attach process is watched for failing right away.
+const STARTUP_CHECK_DURATION: Duration = Duration::from_secs(1);
+
+/// How many times the auto-attach process of a profile is restarted within
+/// [`RESTART_WINDOW`] before giving up, so that a persistent failure is not retried forever.
+const MAX_RESTARTS: usize = 3;
+
+/// The period over which restarts are counted against [`MAX_RESTARTS`].
+const RESTART_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// How a profile recognizes the device to auto-attach.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum AutoAttachMode {
+    /// Match the device identity, on any port
+    #[default]
+    Device,
+    /// Match any device connected to the port
+    Port,
+    /// Match the device identity, only on the port
+    Both,
+}
+
+impl AutoAttachMode {
+    pub const ALL: [AutoAttachMode; 3] = [Self::Device, Self::Port, Self::Both];
+}
+
+impl Display for AutoAttachMode {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Device => write!(fmt, "Device"),
+            Self::Port => write!(fmt, "Port"),
+            Self::Both => write!(fmt, "Device and port"),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Eq)]
 pub struct AutoAttachProfile {
     /// Unique identifier of the profile (persisted_guid)
     pub id: String,
     pub description: Option<String>,
+    /// Instance ID of the device, used to find it again if its binding is lost
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    /// VID:PID of the device, used to match the device if it is bound again with a new GUID
+    #[serde(default)]
+    pub vid_pid: Option<String>,
+    /// Serial number of the device, if available. When set, it must also match.
+    #[serde(default)]
+    pub serial: Option<String>,
+    /// The WSL distribution to attach to, or `None` for the default distribution
+    #[serde(default)]
+    pub distribution: Option<String>,
+    /// How the device to auto-attach is recognized
+    #[serde(default)]
+    pub mode: AutoAttachMode,
+    /// Bus ID of the port watched by the auto-attach process
+    #[serde(default)]
+    pub bus_id: Option<String>,
+}
+
+impl AutoAttachProfile {
+    /// Returns the key identifying the profile's device, see [`usbipd::stable_key`].
+    pub fn stable_key(&self) -> Option<String> {
+        usbipd::stable_key(
+            self.vid_pid.as_deref()?,
+            self.serial.as_deref(),
+            self.bus_id.as_deref(),
+        )
+    }
+
+    /// Returns whether `device` is the device of this profile, according to its mode.
+    ///
+    /// The device identity is its stable key, see [`usbipd::stable_key`]. Devices without
+    /// a serial number are therefore only recognized on the port they were added from.
+    /// Profiles without a VID:PID fall back to matching the persisted GUID.
+    pub fn matches(&self, device: &UsbDevice) -> bool {
+        let same_device = match self.stable_key() {
+            Some(key) => device.stable_key() == Some(key),
+            None => device.persisted_guid.as_ref() == Some(&self.id),
+        };
+        let same_port = self.bus_id.is_some() && device.bus_id == self.bus_id;
+
+        match self.mode {
+            AutoAttachMode::Device => same_device,
+            AutoAttachMode::Port => same_port,
+            AutoAttachMode::Both => same_device && same_port,
+        }
+    }
 }
 
 impl PartialEq for AutoAttachProfile {
@@ -26,63 +129,650 @@ impl Hash for AutoAttachProfile {
     }
 }
 
+/// A rule binding a device whenever it is connected, without attaching it.
+///
+/// Bound devices are shared, so that they can be attached manually or by any usbip client.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AutoBindRule {
+    /// The stable key of the device, see [`usbipd::stable_key`]
+    pub key: String,
+    pub description: Option<String>,
+}
+
 #[derive(Default)]
 pub struct AutoAttacher {
     profiles: HashSet<AutoAttachProfile>,
-    process_map: HashMap<String, std::process::Child>,
+    process_map: HashMap<String, AutoAttachProcess>,
+    /// The errors of auto-attach processes that exited, by profile ID
+    last_errors: HashMap<String, String>,
+    /// When the auto-attach processes exited unexpectedly, by profile ID
+    exit_times: HashMap<String, Vec<Instant>>,
+    /// The profiles whose process exited too often to be restarted, until the device reconnects
+    given_up: HashSet<String>,
+    /// The rules binding devices when they are connected
+    bind_rules: Vec<AutoBindRule>,
+    /// The instance IDs of the devices connected when last checked, to recognize new ones
+    connected_ids: HashSet<String>,
 }
 
 impl AutoAttacher {
+    /// Creates a new auto attacher with the profiles saved from a previous session.
+    ///
+    /// Auto-attach processes are started for all saved profiles whose device is bound.
     pub fn new() -> Self {
-        Default::default()
+        let mut auto_attacher = Self {
+            profiles: Self::load_profiles(),
+            bind_rules: Self::load_bind_rules(),
+            ..Default::default()
+        };
+
+        if let Ok(devices) = usbipd::list_devices() {
+            // Devices connected at startup are not new, they are left as they are
+            let _ = auto_attacher.devices_to_bind(&devices);
+            let _ = auto_attacher.attach_matching_devices_in(&devices);
+        }
+
+        auto_attacher
+    }
+
+    /// Returns the path of the file auto-attach profiles are saved to.
+    fn profiles_path() -> Result<PathBuf, String> {
+        settings::ensure_settings_dir().map(|dir| dir.join(PROFILES_FILE_NAME))
+    }
+
+    /// Loads the saved profiles. A missing file results in an empty set.
+    ///
+    /// Profiles are loaded one at a time, so that a profile saved by another version of the app,
+    /// e.g. with a mode this version does not know, does not discard the others.
+    /// If any profile cannot be loaded, a copy of the file is kept, as the next save
+    /// overwrites it with the loaded profiles only.
+    fn load_profiles() -> HashSet<AutoAttachProfile> {
+        let path = match Self::profiles_path() {
+            Ok(path) => path,
+            Err(_) => return HashSet::new(),
+        };
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(_) => return HashSet::new(),
+        };
+
+        let (entries, mut complete) = match serde_json::from_str::<Vec<serde_json::Value>>(&json) {
+            Ok(entries) => (entries, true),
+            Err(err) => {
+                logging::error(format_args!("Failed to load auto-attach profiles: {err}"));
+                (Vec::new(), false)
+            }
+        };
+
+        let mut profiles = HashSet::new();
+        for entry in entries {
+            match serde_json::from_value(entry) {
+                Ok(profile) => {
+                    profiles.insert(profile);
+                }
+                Err(err) => {
+                    logging::error(format_args!(
+                        "Skipped an auto-attach profile that cannot be loaded: {err}"
+                    ));
+                    complete = false;
+                }
+            }
+        }
+
+        if !complete {
+            let backup = path.with_file_name(PROFILES_BACKUP_FILE_NAME);
+            match std::fs::copy(&path, &backup) {
+                Ok(_) => logging::info(format_args!(
+                    "Kept a copy of the auto-attach profiles at {}",
+                    backup.display()
+                )),
+                Err(err) => logging::error(format_args!(
+                    "Failed to keep a copy of the auto-attach profiles: {err}"
+                )),
+            }
+        }
+
+        profiles
     }
 
-    pub fn add_device(&mut self, device: &UsbDevice) -> Result<(), String> {
+    /// Saves the current profiles to disk.
+    fn save_profiles(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.profiles).map_err(|err| err.to_string())?;
+        std::fs::write(Self::profiles_path()?, json)
+            .map_err(|err| format!("Failed to save auto-attach profiles: {err}"))
+    }
+
+    /// Returns the path of the file auto-bind rules are saved to.
+    fn bind_rules_path() -> Result<PathBuf, String> {
+        settings::ensure_settings_dir().map(|dir| dir.join(BIND_RULES_FILE_NAME))
+    }
+
+    /// Loads the saved auto-bind rules. A missing or corrupt file results in no rules.
+    fn load_bind_rules() -> Vec<AutoBindRule> {
+        Self::bind_rules_path()
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the current auto-bind rules to disk.
+    fn save_bind_rules(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.bind_rules).map_err(|err| err.to_string())?;
+        std::fs::write(Self::bind_rules_path()?, json)
+            .map_err(|err| format!("Failed to save auto-bind rules: {err}"))
+    }
+
+    /// Creates a new profile for `device`, attaching it to the given WSL distribution,
+    /// or to the default one if `None`.
+    ///
+    /// Its process is started by [`PendingProfile::start`], and the profile is only added
+    /// by [`AutoAttacher::add_started`], once the process is running.
+    pub fn new_profile(
+        &self,
+        device: &UsbDevice,
+        distribution: Option<&str>,
+    ) -> Result<AutoAttachProfile, String> {
         let id = device
             .persisted_guid
             .clone()
             .ok_or("The device does not have a persisted GUID, are you sure it's bound?")?;
 
-        // Auto attaching spawns a process that might fail immediately and exit silently
-        // We cannot detect this failure as that would require waiting for the process to exit
-        // As a workaround, attach the device manually first to catch any errors
-        if !device.is_attached() {
-            device.attach()?;
-            device.wait(|d| d.is_some_and(|d| d.is_attached()))?;
-        }
-
-        if !self.profiles.insert(AutoAttachProfile {
+        let profile = AutoAttachProfile {
             id: id.clone(),
             description: device.description.clone(),
-        }) {
+            instance_id: device.instance_id.clone(),
+            vid_pid: device.vid_pid(),
+            serial: device.serial(),
+            distribution: distribution.map(str::to_owned),
+            mode: AutoAttachMode::default(),
+            bus_id: device.bus_id.clone(),
+        };
+        if self.profiles.contains(&profile) {
             return Err("The device is already in the auto attach list.".to_string());
         }
 
-        let process = device.auto_attach()?;
-        self.process_map.insert(id, process);
+        Ok(profile)
+    }
 
-        Ok(())
+    /// Adds a profile whose process was started by [`PendingProfile::start`].
+    pub fn add_started(&mut self, pending: PendingProfile) -> Result<(), String> {
+        self.process_map
+            .insert(pending.profile.id.clone(), pending.process);
+        self.profiles.insert(pending.profile);
+
+        self.save_profiles()
     }
 
     pub fn remove(&mut self, profile: &AutoAttachProfile) -> Result<(), String> {
         self.profiles.remove(profile);
 
         if let Some(mut process) = self.process_map.remove(&profile.id) {
-            let _ = process.kill();
+            process.kill();
         }
+        self.exit_times.remove(&profile.id);
+        self.given_up.remove(&profile.id);
+
+        self.save_profiles()
+    }
+
+    /// Changes the mode of the profile with the given ID.
+    ///
+    /// The auto-attach process is restarted if it no longer watches a matching device.
+    pub fn set_mode(&mut self, id: &str, mode: AutoAttachMode) -> Result<(), String> {
+        let mut profile = match self.profiles.iter().find(|p| p.id == id) {
+            Some(profile) => profile.clone(),
+            None => return Err("The auto attach profile no longer exists.".to_owned()),
+        };
+
+        profile.mode = mode;
+        self.profiles.replace(profile);
+        self.save_profiles()?;
+
+        self.attach_matching_devices()
+    }
 
-        Ok(())
+    /// Starts auto-attach processes for profiles whose device is bound, but not watched
+    /// by the auto-attach process of the profile, e.g. because it was connected to
+    /// a different port or bound again with a new GUID.
+    ///
+    /// This should be called whenever a device is connected.
+    pub fn attach_matching_devices(&mut self) -> Result<(), String> {
+        let devices = usbipd::list_devices()?;
+        self.attach_matching_devices_in(&devices)
+    }
+
+    /// Like [`AutoAttacher::attach_matching_devices`], using an already retrieved device list.
+    pub fn attach_matching_devices_in(&mut self, devices: &[UsbDevice]) -> Result<(), String> {
+        let profiles: Vec<AutoAttachProfile> = self.profiles.iter().cloned().collect();
+        let mut changed = false;
+        let mut result = Ok(());
+
+        for mut profile in profiles {
+            if self.given_up.contains(&profile.id) {
+                // Try again once the device is reconnected
+                if !devices
+                    .iter()
+                    .any(|d| d.is_connected() && profile.matches(d))
+                {
+                    self.given_up.remove(&profile.id);
+                    self.exit_times.remove(&profile.id);
+                }
+                continue;
+            }
+
+            // The process attaches any bound device connected to the port it watches
+            let is_watched = self.process_map.contains_key(&profile.id)
+                && devices
+                    .iter()
+                    .any(|d| d.is_connected() && d.bus_id == profile.bus_id && profile.matches(d));
+            if is_watched {
+                continue;
+            }
+
+            // Skip devices already handled by the process of another profile
+            let device = devices.iter().find(|d| {
+                d.is_connected()
+                    && d.is_bound()
+                    && profile.matches(d)
+                    && d.persisted_guid.as_ref().is_some_and(|guid| {
+                        *guid == profile.id || !self.process_map.contains_key(guid)
+                    })
+            });
+            let device = match device {
+                Some(device) => device,
+                None => continue,
+            };
+
+            if let Some(mut process) = self.process_map.remove(&profile.id) {
+                process.kill();
+            }
+            self.profiles.remove(&profile);
+
+            self.last_errors.remove(&profile.id);
+            profile.id = device.persisted_guid.clone().unwrap();
+            profile.bus_id = device.bus_id.clone();
+            match AutoAttachProcess::spawn(device, profile.distribution.as_deref()) {
+                Ok(process) => {
+                    self.process_map.insert(profile.id.clone(), process);
+                }
+                Err(err) => result = Err(err),
+            }
+
+            self.profiles.insert(profile);
+            changed = true;
+        }
+
+        if changed {
+            result = result.and(self.save_profiles());
+        }
+
+        result
+    }
+
+    /// Updates the profiles whose device is connected and bound after [`bind_profiled_devices`],
+    /// as binding assigns new persisted GUIDs.
+    ///
+    /// If `reattach` is `true`, the auto-attach processes of these profiles are restarted,
+    /// re-attaching their devices. Otherwise, running processes are left as they are.
+    pub fn update_rebound_profiles(&mut self, reattach: bool) -> Result<(), String> {
+        let devices = usbipd::list_devices()?;
+        let old_profiles: Vec<AutoAttachProfile> = self.profiles.drain().collect();
+        let mut result = Ok(());
+
+        for mut profile in old_profiles {
+            let device = find_profile_device(&devices, &profile).filter(|d| d.is_bound());
+            if let Some(device) = device {
+                let old_id = profile.id.clone();
+                if let Some(guid) = device.persisted_guid.clone() {
+                    profile.id = guid;
+                }
+                profile.bus_id = device.bus_id.clone();
+
+                if reattach {
+                    if let Some(mut process) = self.process_map.remove(&old_id) {
+                        process.kill();
+                    }
+
+                    // Restarting explicitly gives up on earlier failures
+                    self.last_errors.remove(&old_id);
+                    self.exit_times.remove(&old_id);
+                    self.given_up.remove(&old_id);
+
+                    match AutoAttachProcess::spawn(device, profile.distribution.as_deref()) {
+                        Ok(process) => {
+                            self.process_map.insert(profile.id.clone(), process);
+                        }
+                        Err(err) => result = Err(err),
+                    }
+                } else if profile.id != old_id {
+                    self.rename_profile_state(&old_id, &profile.id);
+                }
+            }
+
+            self.profiles.insert(profile);
+        }
+
+        result.and(self.save_profiles())
+    }
+
+    /// Moves the process and the failure history of a profile whose ID changed.
+    fn rename_profile_state(&mut self, old_id: &str, new_id: &str) {
+        if let Some(process) = self.process_map.remove(old_id) {
+            self.process_map.insert(new_id.to_owned(), process);
+        }
+        if let Some(err) = self.last_errors.remove(old_id) {
+            self.last_errors.insert(new_id.to_owned(), err);
+        }
+        if let Some(times) = self.exit_times.remove(old_id) {
+            self.exit_times.insert(new_id.to_owned(), times);
+        }
+        if self.given_up.remove(old_id) {
+            self.given_up.insert(new_id.to_owned());
+        }
+    }
+
+    /// Adds the given profiles, e.g. from an imported configuration.
+    ///
+    /// If `replace` is `true`, the existing profiles are removed first. Otherwise, profiles
+    /// with the same ID as an existing one are skipped.
+    pub fn import_profiles(
+        &mut self,
+        profiles: Vec<AutoAttachProfile>,
+        replace: bool,
+    ) -> Result<(), String> {
+        if replace {
+            for (_, mut process) in self.process_map.drain() {
+                process.kill();
+            }
+            self.profiles.clear();
+            self.exit_times.clear();
+            self.given_up.clear();
+        }
+
+        for profile in profiles {
+            self.profiles.insert(profile);
+        }
+
+        self.save_profiles()?;
+        self.attach_matching_devices()
+    }
+
+    /// Stops all auto-attach processes. The profiles are kept.
+    pub fn stop_all(&mut self) {
+        for (_, mut process) in self.process_map.drain() {
+            process.kill();
+        }
+    }
+
+    /// Returns whether the auto-attach process of the profile with the given ID is running.
+    pub fn is_running(&self, id: &str) -> bool {
+        self.process_map.contains_key(id)
+    }
+
+    /// Detects auto-attach processes that exited, e.g. because `usbipd` was restarted,
+    /// and tries to restart them.
+    ///
+    /// Returns `true` if any process had exited. Profiles whose process could not be
+    /// restarted, e.g. because the device is not connected, are left inactive.
+    /// Processes that exited more than [`MAX_RESTARTS`] times within [`RESTART_WINDOW`]
+    /// are not restarted until the device is reconnected, keeping their error.
+    pub fn restart_exited(&mut self) -> bool {
+        let exited: Vec<String> = self
+            .process_map
+            .iter_mut()
+            .filter(|(_, process)| process.has_exited())
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        if exited.is_empty() {
+            return false;
+        }
+
+        let now = Instant::now();
+        for id in exited {
+            if let Some(mut process) = self.process_map.remove(&id) {
+                let mut err = process.exit_error();
+                logging::error(format_args!("Auto-attach process for {id} exited: {err}"));
+
+                let exit_times = self.exit_times.entry(id.clone()).or_default();
+                exit_times.retain(|time| now.duration_since(*time) < RESTART_WINDOW);
+                exit_times.push(now);
+                if exit_times.len() > MAX_RESTARTS {
+                    logging::error(format_args!(
+                        "Auto-attach process for {id} keeps exiting, no longer restarting it"
+                    ));
+                    err = format!(
+                        concat!(
+                            "Auto-attach stopped after the process exited {} times in {} minutes, ",
+                            "reconnect the device to try again.\n{}"
+                        ),
+                        exit_times.len(),
+                        RESTART_WINDOW.as_secs() / 60,
+                        err
+                    );
+                    self.given_up.insert(id.clone());
+                }
+                self.last_errors.insert(id, err);
+            }
+        }
+
+        if let Err(err) = self.attach_matching_devices() {
+            logging::error(format_args!(
+                "Failed to restart auto-attach processes: {err}"
+            ));
+        }
+
+        true
+    }
+
+    /// Returns the error of the last auto-attach process of the profile with the given ID,
+    /// if it exited and was not restarted since.
+    pub fn last_error(&self, id: &str) -> Option<&str> {
+        self.last_errors.get(id).map(String::as_str)
+    }
+
+    /// Returns whether there are profiles or auto-bind rules, which need to be matched
+    /// against the connected devices whenever they change.
+    pub fn watches_devices(&self) -> bool {
+        !self.profiles.is_empty() || !self.bind_rules.is_empty()
     }
 
     pub fn profiles(&self) -> Vec<AutoAttachProfile> {
         self.profiles.iter().cloned().collect()
     }
+
+    /// Returns the auto-bind rules.
+    pub fn bind_rules(&self) -> &[AutoBindRule] {
+        &self.bind_rules
+    }
+
+    /// Returns whether `device` is bound whenever it is connected.
+    pub fn has_bind_rule(&self, device: &UsbDevice) -> bool {
+        device
+            .stable_key()
+            .is_some_and(|key| self.bind_rules.iter().any(|r| r.key == key))
+    }
+
+    /// Adds a rule binding `device` whenever it is connected.
+    pub fn add_bind_rule(&mut self, device: &UsbDevice) -> Result<(), String> {
+        let key = device
+            .stable_key()
+            .ok_or("This device cannot be told apart from other devices.")?;
+        if self.bind_rules.iter().any(|r| r.key == key) {
+            return Err("The device is already bound on connect.".to_owned());
+        }
+
+        self.bind_rules.push(AutoBindRule {
+            key,
+            description: device.description.clone(),
+        });
+        self.save_bind_rules()
+    }
+
+    /// Removes the auto-bind rule with the given key.
+    pub fn remove_bind_rule(&mut self, key: &str) -> Result<(), String> {
+        self.bind_rules.retain(|r| r.key != key);
+        self.save_bind_rules()
+    }
+
+    /// Returns the devices in `devices` that were connected since the last call,
+    /// match an auto-bind rule and are not bound yet.
+    ///
+    /// This should be called whenever a device is connected.
+    pub fn devices_to_bind(&mut self, devices: &[UsbDevice]) -> Vec<UsbDevice> {
+        let connected_ids: HashSet<String> = devices
+            .iter()
+            .filter(|d| d.is_connected())
+            .filter_map(|d| d.instance_id.clone())
+            .collect();
+
+        let to_bind = devices
+            .iter()
+            .filter(|d| {
+                d.instance_id.as_ref().is_some_and(|id| {
+                    connected_ids.contains(id) && !self.connected_ids.contains(id)
+                })
+            })
+            .filter(|d| !d.is_bound() && self.has_bind_rule(d))
+            .cloned()
+            .collect();
+
+        self.connected_ids = connected_ids;
+        to_bind
+    }
 }
 
 impl Drop for AutoAttacher {
     fn drop(&mut self) {
-        for (_, mut process) in self.process_map.drain() {
-            let _ = process.kill();
+        self.stop_all();
+    }
+}
+
+/// Binds the connected devices of `profiles` that are not bound, e.g. after `usbipd` lost its
+/// bindings. Unbound devices are bound in a single elevated operation.
+///
+/// All devices are attempted even if some fail, errors are collected into a single message.
+/// This blocks until the devices are bound, so it should run on a background thread.
+pub fn bind_profiled_devices(profiles: &[AutoAttachProfile]) -> Result<(), String> {
+    let devices = usbipd::list_devices()?;
+    let unbound: Vec<&UsbDevice> = profiles
+        .iter()
+        .filter_map(|profile| find_profile_device(&devices, profile))
+        .filter(|d| !d.is_bound())
+        .collect();
+
+    usbipd::bind_all_and_wait(&unbound)
+}
+
+/// Returns the connected device of `profile`, recognized by its instance ID,
+/// or as in [`AutoAttachProfile::matches`] for profiles saved without one.
+fn find_profile_device<'a>(
+    devices: &'a [UsbDevice],
+    profile: &AutoAttachProfile,
+) -> Option<&'a UsbDevice> {
+    let connected = || devices.iter().filter(|d| d.is_connected());
+    connected()
+        .find(|d| profile.instance_id.is_some() && d.instance_id == profile.instance_id)
+        .or_else(|| connected().find(|d| profile.matches(d)))
+}
+
+/// A new auto-attach profile whose process is starting, see [`AutoAttacher::new_profile`].
+pub struct PendingProfile {
+    profile: AutoAttachProfile,
+    process: AutoAttachProcess,
+}
+
+impl PendingProfile {
+    /// Starts the auto-attach process of `profile` for `device`.
+    pub fn start(profile: AutoAttachProfile, device: &UsbDevice) -> Result<Self, String> {
+        let process = AutoAttachProcess::spawn(device, profile.distribution.as_deref())?;
+        Ok(Self { profile, process })
+    }
+
+    /// Waits until the process has been running for a moment, blocking the calling thread.
+    ///
+    /// The process might fail immediately, e.g. if the distribution is not running,
+    /// its error is returned instead of keeping a profile that does nothing.
+    /// Returns `None` after killing the process if `cancelled` is set in the meantime.
+    pub fn wait_started(mut self, cancelled: &AtomicBool) -> Option<Result<Self, String>> {
+        let err = self.process.startup_error(cancelled);
+        if cancelled.load(Ordering::Relaxed) {
+            self.process.kill();
+            return None;
+        }
+
+        match err {
+            Some(err) => Some(Err(err)),
+            None => Some(Ok(self)),
+        }
+    }
+}
+
+/// A running auto-attach process.
+struct AutoAttachProcess {
+    child: Child,
+    started: Instant,
+    /// Collects the error output of the process until it exits
+    stderr_reader: Option<JoinHandle<String>>,
+}
+
+impl AutoAttachProcess {
+    /// Spawns the auto-attach process for `device`, see [`UsbDevice::auto_attach`].
+    fn spawn(device: &UsbDevice, distribution: Option<&str>) -> Result<Self, String> {
+        let mut child = device.auto_attach(distribution)?;
+
+        // Read on a separate thread, so that the process never blocks on a full pipe
+        let stderr_reader = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut output = String::new();
+                let _ = stderr.read_to_string(&mut output);
+                output
+            })
+        });
+
+        Ok(Self {
+            child,
+            started: Instant::now(),
+            stderr_reader,
+        })
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+
+    fn has_exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Waits until the process has been running for [`STARTUP_CHECK_DURATION`],
+    /// or until `cancelled` is set. Returns its error if it failed in the meantime.
+    fn startup_error(&mut self, cancelled: &AtomicBool) -> Option<String> {
+        while self.started.elapsed() < STARTUP_CHECK_DURATION && !cancelled.load(Ordering::Relaxed)
+        {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return (!status.success()).then(|| self.exit_error());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        None
+    }
+
+    /// Returns the error of the exited process, from its error output or its exit status.
+    fn exit_error(&mut self) -> String {
+        let status = self.child.try_wait().ok().flatten();
+        let stderr = self
+            .stderr_reader
+            .take()
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+
+        match (stderr.trim(), status) {
+            ("", Some(status)) => format!("The auto-attach process exited with {status}."),
+            ("", None) => "The auto-attach process exited.".to_owned(),
+            (stderr, _) => stderr.to_owned(),
         }
     }
 }
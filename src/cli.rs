@@ -0,0 +1,133 @@
+//! This module provides the headless command line interface,
+//! which runs `usbipd` operations without starting the GUI.
+
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+use crate::args::{CliCommand, USAGE};
+use crate::usbipd::{self, UsbDevice};
+
+/// Runs a command line command, printing its results to the console.
+pub fn run(command: CliCommand) -> ExitCode {
+    let result = match command {
+        CliCommand::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        CliCommand::Version => {
+            println!("wsl-usb-manager {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        CliCommand::List { json: false } => list(),
+        CliCommand::List { json: true } => list_json(),
+        CliCommand::Attach {
+            bus_id,
+            distribution,
+        } => find_device(&bus_id).and_then(|device| {
+            device.attach(distribution.as_deref())?;
+            device.wait(|d| d.is_some_and(|d| d.is_attached()))?;
+            println!("Attached device {bus_id}.");
+            Ok(())
+        }),
+        CliCommand::Detach { bus_id } => find_device(&bus_id).and_then(|device| {
+            device.detach()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_attached()))?;
+            println!("Detached device {bus_id}.");
+            Ok(())
+        }),
+        CliCommand::Bind { bus_id, force } => find_device(&bus_id).and_then(|device| {
+            device.bind(force)?;
+            device.wait(|d| d.is_some_and(|d| d.is_bound()))?;
+            println!("Bound device {bus_id}.");
+            Ok(())
+        }),
+        CliCommand::Unbind { bus_id } => find_device(&bus_id).and_then(|device| {
+            device.unbind()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_bound()))?;
+            println!("Unbound device {bus_id}.");
+            Ok(())
+        }),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err.trim());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the connected and persisted devices.
+fn list() -> Result<(), String> {
+    let devices = usbipd::list_devices()?;
+
+    println!("Connected:");
+    println!("{:<8} {:<10} {:<18} DEVICE", "BUSID", "VID:PID", "STATE");
+    for device in devices.iter().filter(|d| d.is_connected()) {
+        println!(
+            "{:<8} {:<10} {:<18} {}",
+            device.bus_id.as_deref().unwrap_or("-"),
+            device.vid_pid().as_deref().unwrap_or("-"),
+            device.state().to_string(),
+            device.description.as_deref().unwrap_or("Unknown device"),
+        );
+    }
+
+    println!();
+    println!("Persisted:");
+    println!("{:<36} DEVICE", "GUID");
+    for device in devices.iter().filter(|d| !d.is_connected()) {
+        println!(
+            "{:<36} {}",
+            device.persisted_guid.as_deref().unwrap_or("-"),
+            device.description.as_deref().unwrap_or("Unknown device"),
+        );
+    }
+
+    Ok(())
+}
+
+/// A device as printed by `list --json`: the fields reported by usbipd,
+/// along with the ones derived from them.
+#[derive(Serialize)]
+struct ListedDevice<'a> {
+    #[serde(flatten)]
+    device: &'a UsbDevice,
+
+    #[serde(rename = "State")]
+    state: String,
+
+    #[serde(rename = "VidPid")]
+    vid_pid: Option<String>,
+
+    #[serde(rename = "Serial")]
+    serial: Option<String>,
+}
+
+/// Prints the connected and persisted devices as a JSON array.
+fn list_json() -> Result<(), String> {
+    let devices = usbipd::list_devices()?;
+    let listed: Vec<ListedDevice> = devices
+        .iter()
+        .map(|device| ListedDevice {
+            device,
+            state: device.state().to_string(),
+            vid_pid: device.vid_pid(),
+            serial: device.serial(),
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&listed).map_err(|err| err.to_string())?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Finds the connected device with the given bus ID.
+fn find_device(bus_id: &str) -> Result<UsbDevice, String> {
+    usbipd::list_devices()?
+        .into_iter()
+        .find(|d| d.bus_id.as_deref() == Some(bus_id))
+        .ok_or(format!("No device is connected with bus ID {bus_id}."))
+}
@@ -0,0 +1,61 @@
+//! This module provides exporting and importing the whole app configuration as a single file,
+//! to move a setup between machines.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::auto_attach::AutoAttachProfile;
+use crate::settings::Settings;
+
+/// The version of the configuration file format, increased on incompatible changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// The exported app configuration.
+#[derive(Serialize, Deserialize)]
+pub struct Configuration {
+    pub format_version: u32,
+    pub settings: Settings,
+    pub auto_attach_profiles: Vec<AutoAttachProfile>,
+}
+
+impl Configuration {
+    pub fn new(settings: Settings, auto_attach_profiles: Vec<AutoAttachProfile>) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            settings,
+            auto_attach_profiles,
+        }
+    }
+
+    /// Writes the configuration to the file at `path`.
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, json)
+            .map_err(|err| format!("Failed to export the configuration: {err}"))
+    }
+
+    /// Reads a configuration from the file at `path`.
+    ///
+    /// Fails if the file is not a configuration exported by this app,
+    /// or if it was exported by a newer, incompatible version.
+    pub fn import(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read the configuration: {err}"))?;
+        let value: serde_json::Value = serde_json::from_str(&json)
+            .map_err(|err| format!("The file is not valid JSON: {err}"))?;
+
+        match value.get("format_version").and_then(|v| v.as_u64()) {
+            Some(version) if version == FORMAT_VERSION as u64 => {}
+            Some(version) if version > FORMAT_VERSION as u64 => {
+                return Err(format!(
+                    "The configuration was exported by a newer version of the app (format version {version})."
+                ));
+            }
+            _ => return Err("The file is not a WSL USB Manager configuration.".to_owned()),
+        }
+
+        serde_json::from_value(value)
+            .map_err(|err| format!("The configuration file is invalid: {err}"))
+    }
+}
@@ -0,0 +1,120 @@
+//! This module collects diagnostics to attach to bug reports.
+
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use windows_sys::Win32::Foundation::SYSTEMTIME;
+use windows_sys::Win32::System::SystemInformation::GetLocalTime;
+use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+
+use crate::{logging, settings, usbipd};
+
+/// The description of the collected files, written next to them.
+const README: &str = "\
+WSL USB Manager diagnostics
+
+This archive contains:
+- version.txt: the versions of WSL USB Manager and usbipd
+- usbipd-state.json: the raw output of `usbipd state`, describing all the USB devices
+- the log files of WSL USB Manager
+- the settings, auto-attach profiles and auto-bind rules
+
+Nothing is redacted. The files might contain device serial numbers, device nicknames
+and paths on this computer, review them before sharing.
+";
+
+/// Collects diagnostics into a timestamped zip archive in the settings directory
+/// and returns its path.
+///
+/// The files are gathered into a folder, which is compressed with PowerShell.
+/// If compression fails, the path of the folder is returned instead.
+pub fn collect() -> Result<PathBuf, String> {
+    let settings_dir = settings::ensure_settings_dir()?;
+    let dir = settings_dir.join(format!("diagnostics-{}", file_timestamp()));
+    std::fs::create_dir(&dir)
+        .map_err(|err| format!("Failed to create the diagnostics folder: {err}"))?;
+
+    gather(&settings_dir, &dir).map_err(|err| format!("Failed to collect diagnostics: {err}"))?;
+
+    let zip_path = dir.with_extension("zip");
+    match compress(&dir, &zip_path) {
+        Ok(()) => {
+            let _ = std::fs::remove_dir_all(&dir);
+            logging::info(format_args!("Diagnostics saved to {}", zip_path.display()));
+            Ok(zip_path)
+        }
+        Err(err) => {
+            logging::error(format_args!("Failed to compress diagnostics: {err}"));
+            Ok(dir)
+        }
+    }
+}
+
+/// Writes the diagnostics into `dir`, copying the logs and settings from `settings_dir`.
+fn gather(settings_dir: &Path, dir: &Path) -> std::io::Result<()> {
+    std::fs::write(dir.join("README.txt"), README)?;
+
+    let usbipd_version = usbipd::version()
+        .map(|version| version.to_string())
+        .unwrap_or_else(|err| err.to_string());
+    std::fs::write(
+        dir.join("version.txt"),
+        format!(
+            "WSL USB Manager: {}\nusbipd: {usbipd_version}\n",
+            env!("CARGO_PKG_VERSION")
+        ),
+    )?;
+
+    let state = usbipd::state_json().unwrap_or_else(|err| err);
+    std::fs::write(dir.join("usbipd-state.json"), state)?;
+
+    // The logs, settings, profiles and rules are the only files in the settings directory
+    for entry in std::fs::read_dir(settings_dir)? {
+        let path = entry?.path();
+        let is_collected = path
+            .extension()
+            .is_some_and(|extension| extension == "json" || extension == "log");
+        if path.is_file() && is_collected {
+            if let Some(file_name) = path.file_name() {
+                std::fs::copy(&path, dir.join(file_name))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compresses the contents of `dir` into a zip archive at `zip_path` with PowerShell.
+fn compress(dir: &Path, zip_path: &Path) -> Result<(), String> {
+    // Single quotes are escaped by doubling them in PowerShell string literals
+    let quote = |path: &Path| format!("'{}'", path.to_string_lossy().replace('\'', "''"));
+    let script = format!(
+        "Compress-Archive -Path {} -DestinationPath {} -Force",
+        quote(&dir.join("*")),
+        quote(zip_path)
+    );
+
+    let output = Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|err| format!("Failed to run PowerShell: {err}"))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_owned())
+    }
+}
+
+/// Returns the current local time formatted as `YYYYMMDD-HHMMSS`, for use in file names.
+fn file_timestamp() -> String {
+    let mut time: SYSTEMTIME = unsafe { std::mem::zeroed() };
+    unsafe { GetLocalTime(&mut time as *mut _) };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        time.wYear, time.wMonth, time.wDay, time.wHour, time.wMinute, time.wSecond
+    )
+}
@@ -0,0 +1,80 @@
+use native_windows_gui as nwg;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Controls::{
+    TaskDialogIndirect, TASKDIALOGCONFIG, TASKDIALOG_BUTTON, TDCBF_CLOSE_BUTTON,
+    TDF_ALLOW_DIALOG_CANCELLATION, TDF_POSITION_RELATIVE_TO_WINDOW, TDF_USE_COMMAND_LINKS,
+    TD_INFORMATION_ICON,
+};
+
+use crate::usbipd;
+use crate::win_utils::{open_url, to_wide};
+
+/// The project page, where issues can be reported.
+const PROJECT_URL: &str = "https://github.com/nickbeth/wsl-usb-manager";
+
+const BUTTON_PROJECT_PAGE: i32 = 100;
+const BUTTON_COPY_VERSIONS: i32 = 101;
+
+/// Shows the version of the app and of the detected `usbipd`, with a link to the project page.
+///
+/// The versions can be copied to the clipboard, so that they can be pasted into bug reports.
+pub fn show_about(window: nwg::ControlHandle) {
+    let usbipd_version = match usbipd::version() {
+        Ok(version) => version.to_string(),
+        Err(_) => "unknown".to_owned(),
+    };
+    let versions = format!(
+        "WSL USB Manager {}\nusbipd-win {usbipd_version}",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let title = to_wide("WSL USB Manager: About");
+    let instruction = to_wide("WSL USB Manager");
+    let content = to_wide(&versions);
+    let project_page_text =
+        to_wide("Open the project page\nReport issues and get the latest version");
+    let copy_versions_text = to_wide("Copy version information\nTo paste it into a bug report");
+
+    let buttons = [
+        TASKDIALOG_BUTTON {
+            nButtonID: BUTTON_PROJECT_PAGE,
+            pszButtonText: project_page_text.as_ptr(),
+        },
+        TASKDIALOG_BUTTON {
+            nButtonID: BUTTON_COPY_VERSIONS,
+            pszButtonText: copy_versions_text.as_ptr(),
+        },
+    ];
+
+    let mut config: TASKDIALOGCONFIG = unsafe { std::mem::zeroed() };
+    config.cbSize = std::mem::size_of::<TASKDIALOGCONFIG>() as u32;
+    config.hwndParent = window.hwnd().map_or(0, |hwnd| hwnd as HWND);
+    config.dwFlags =
+        TDF_ALLOW_DIALOG_CANCELLATION | TDF_POSITION_RELATIVE_TO_WINDOW | TDF_USE_COMMAND_LINKS;
+    config.dwCommonButtons = TDCBF_CLOSE_BUTTON;
+    config.pszWindowTitle = title.as_ptr();
+    config.Anonymous1.pszMainIcon = TD_INFORMATION_ICON;
+    config.pszMainInstruction = instruction.as_ptr();
+    config.pszContent = content.as_ptr();
+    config.cButtons = buttons.len() as u32;
+    config.pButtons = buttons.as_ptr();
+
+    let mut button = 0;
+    let result = unsafe {
+        TaskDialogIndirect(
+            &config as *const _,
+            &mut button as *mut _,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if result < 0 {
+        return;
+    }
+
+    match button {
+        BUTTON_PROJECT_PAGE => open_url(PROJECT_URL),
+        BUTTON_COPY_VERSIONS => nwg::Clipboard::set_data_text(window, &versions),
+        _ => {}
+    }
+}
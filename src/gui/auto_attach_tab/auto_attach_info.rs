@@ -40,16 +40,48 @@ pub struct AutoAttachInfo {
     )]
     separator: nwg::Frame,
 
-    #[nwg_control(text: "Persisted ID:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_control(text: "Status:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
     #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0)},
         margin: Rect { start: Pt(0.0), end: Pt(0.0), top: Pt(6.0), bottom: Pt(0.0)}
     )]
+    status: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    status_content: nwg::RichLabel,
+
+    #[nwg_control(text: "Persisted ID:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     persisted_id: nwg::Label,
 
     #[nwg_control]
     #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     persisted_id_content: nwg::RichLabel,
 
+    #[nwg_control(text: "VID:PID:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    vid_pid: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    vid_pid_content: nwg::RichLabel,
+
+    #[nwg_control(text: "Match by:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    mode: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    mode_content: nwg::RichLabel,
+
+    #[nwg_control(text: "Distribution:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    distribution: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    distribution_content: nwg::RichLabel,
+
     #[nwg_control(text: "Description:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
     #[nwg_layout_item(layout: info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     description: nwg::Label,
@@ -60,9 +92,35 @@ pub struct AutoAttachInfo {
 }
 
 impl AutoAttachInfo {
-    pub fn update(&self, profile: Option<&AutoAttachProfile>) {
+    /// Updates the displayed profile. `is_running` tells whether its auto-attach process is running,
+    /// `last_error` is the error of its last process, if it exited.
+    pub fn update(
+        &self,
+        profile: Option<&AutoAttachProfile>,
+        is_running: bool,
+        last_error: Option<&str>,
+    ) {
         if let Some(profile) = profile {
+            match last_error {
+                _ if is_running => self.status_content.set_text("Active"),
+                Some(err) => self.status_content.set_text(&format!("Failed: {err}")),
+                None => self
+                    .status_content
+                    .set_text("Inactive, waiting for the device"),
+            }
             self.persisted_id_content.set_text(&profile.id);
+            self.vid_pid_content
+                .set_text(profile.vid_pid.as_deref().unwrap_or("-"));
+            self.mode_content.set_text(&match &profile.bus_id {
+                Some(bus_id) => format!("{} (port {bus_id})", profile.mode),
+                None => profile.mode.to_string(),
+            });
+            self.distribution_content.set_text(
+                profile
+                    .distribution
+                    .as_deref()
+                    .unwrap_or("Default distribution"),
+            );
             self.description_content.set_text(
                 profile
                     .description
@@ -70,7 +128,11 @@ impl AutoAttachInfo {
                     .unwrap_or("No description available"),
             );
         } else {
+            self.status_content.set_text("-");
             self.persisted_id_content.set_text("-");
+            self.vid_pid_content.set_text("-");
+            self.mode_content.set_text("-");
+            self.distribution_content.set_text("-");
             self.description_content.set_text("No profile selected");
         }
     }
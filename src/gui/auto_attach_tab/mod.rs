@@ -3,6 +3,7 @@ mod auto_attach_info;
 use std::{
     cell::{Cell, RefCell},
     rc::Rc,
+    time::Duration,
 };
 
 use native_windows_derive::NwgPartial;
@@ -11,11 +12,20 @@ use nwg::stretch::{
     geometry::{Rect, Size},
     style::{Dimension as D, FlexDirection},
 };
-use windows_sys::Win32::UI::Controls::LVSCW_AUTOSIZE_USEHEADER;
+use windows_sys::Win32::UI::{Controls::LVSCW_AUTOSIZE_USEHEADER, Shell::SIID_SHIELD};
 
 use self::auto_attach_info::AutoAttachInfo;
-use crate::auto_attach::{self, AutoAttachProfile, AutoAttacher};
-use crate::gui::usbipd_gui::GuiTab;
+use crate::auto_attach::{self, AutoAttachMode, AutoAttachProfile, AutoAttacher, AutoBindRule};
+use crate::gui::{
+    confirm::confirm,
+    helpers::{bind_rule_name, profile_name},
+    nwg_ext::{set_tab_order, BitmapEx, ListViewEx},
+    rename_dialog::ask_device_name,
+    usbipd_gui::GuiTab,
+    worker::BackgroundCommand,
+};
+use crate::settings::Settings;
+use crate::usbipd::UsbDevice;
 
 const PADDING_LEFT: Rect<D> = Rect {
     start: D::Points(8.0),
@@ -24,34 +34,95 @@ const PADDING_LEFT: Rect<D> = Rect {
     bottom: D::Points(0.0),
 };
 
+const PADDING_TOP: Rect<D> = Rect {
+    start: D::Points(0.0),
+    end: D::Points(0.0),
+    top: D::Points(4.0),
+    bottom: D::Points(0.0),
+};
+
+/// How long to wait for further refresh requests before refreshing, so that bursts of
+/// changes, e.g. from a composite device, cause a single refresh.
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(100);
+
 const DETAILS_PANEL_WIDTH: f32 = 285.0;
 const DETAILS_PANEL_PADDING: u32 = 4;
 
 #[derive(Default, NwgPartial)]
 pub struct AutoAttachTab {
     auto_attacher: Rc<RefCell<AutoAttacher>>,
+    settings: Rc<RefCell<Settings>>,
 
     window: Cell<nwg::ControlHandle>,
+    shield_bitmap: Cell<nwg::Bitmap>,
 
     auto_attach_profiles: RefCell<Vec<auto_attach::AutoAttachProfile>>,
+    /// The devices as of the last refresh, to determine the status of the profiles
+    devices: RefCell<Vec<UsbDevice>>,
+    /// The auto-bind rules, in the same order as the bind rules list rows
+    bind_rules: RefCell<Vec<AutoBindRule>>,
+
+    /// The device command currently running in the background, shared with the main window
+    background_command: BackgroundCommand<Result<(), String>>,
+    /// Whether the devices re-bound by the running command are re-attached once it completes
+    reattach_after_rebind: Cell<bool>,
 
     #[nwg_control]
-    #[nwg_events(OnNotice: [AutoAttachTab::refresh])]
+    #[nwg_events(OnNotice: [AutoAttachTab::rebind_done])]
+    rebind_notice: nwg::Notice,
+
+    /// Requests a refresh, coalesced with other requests arriving shortly after
+    #[nwg_control]
+    #[nwg_events(OnNotice: [AutoAttachTab::schedule_refresh])]
     pub refresh_notice: nwg::Notice,
+    /// A notice sender to notify the main window that a device command started or completed
+    pub busy_notice: Cell<Option<nwg::NoticeSender>>,
+    /// A notice sender to request a refresh of all tabs from the main window
+    pub refresh_all_notice: Cell<Option<nwg::NoticeSender>>,
+
+    #[nwg_control(interval: REFRESH_DEBOUNCE, active: false)]
+    #[nwg_events(OnTimerTick: [AutoAttachTab::refresh])]
+    refresh_debounce_timer: nwg::AnimationTimer,
 
     #[nwg_layout(flex_direction: FlexDirection::Row)]
     tab_layout: nwg::FlexboxLayout,
 
-    #[nwg_control(list_style: nwg::ListViewStyle::Detailed, focus: true,
+    #[nwg_control(flags: "VISIBLE")]
+    #[nwg_layout_item(layout: tab_layout, flex_grow: 1.0)]
+    list_frame: nwg::Frame,
+
+    #[nwg_layout(parent: list_frame, flex_direction: FlexDirection::Column, auto_spacing: None)]
+    list_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: list_frame, list_style: nwg::ListViewStyle::Detailed, focus: true,
         flags: "VISIBLE|SINGLE_SELECTION|TAB_STOP",
         ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT,
     )]
     #[nwg_events(OnListViewRightClick: [AutoAttachTab::show_menu],
         OnListViewItemChanged: [AutoAttachTab::update_auto_attach_details]
     )]
-    #[nwg_layout_item(layout: tab_layout, flex_grow: 1.0)]
+    #[nwg_layout_item(layout: list_layout, flex_grow: 1.0)]
     list_view: nwg::ListView,
 
+    // Auto-bind rules
+    #[nwg_control(parent: list_frame, text: "Bound on connect, without attaching:",
+        v_align: nwg::VTextAlign::Bottom,
+    )]
+    #[nwg_layout_item(layout: list_layout, margin: PADDING_TOP,
+        size: Size { width: D::Auto, height: D::Points(20.0) },
+    )]
+    bind_rules_label: nwg::Label,
+
+    #[nwg_control(parent: list_frame, list_style: nwg::ListViewStyle::Detailed,
+        flags: "VISIBLE|SINGLE_SELECTION|TAB_STOP",
+        ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT,
+    )]
+    #[nwg_events(OnListViewRightClick: [AutoAttachTab::show_bind_rules_menu])]
+    #[nwg_layout_item(layout: list_layout, margin: PADDING_TOP,
+        size: Size { width: D::Auto, height: D::Points(100.0) },
+    )]
+    bind_rules_list: nwg::ListView,
+
     // Profile info
     #[nwg_control]
     #[nwg_layout_item(layout: tab_layout, margin: PADDING_LEFT,
@@ -72,6 +143,12 @@ pub struct AutoAttachTab {
     #[nwg_partial(parent: details_info_frame)]
     auto_attach_info: AutoAttachInfo,
 
+    // Match mode
+    #[nwg_control(parent: details_frame)]
+    #[nwg_layout_item(layout: details_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
+    #[nwg_events(OnComboxBoxSelection: [AutoAttachTab::change_mode])]
+    mode_combo: nwg::ComboBox<AutoAttachMode>,
+
     // Buttons
     #[nwg_control(parent: details_frame, flags: "VISIBLE")]
     #[nwg_layout_item(layout: details_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
@@ -85,19 +162,42 @@ pub struct AutoAttachTab {
     #[nwg_events(OnButtonClick: [AutoAttachTab::delete])]
     button_delete: nwg::Button,
 
+    #[nwg_control(parent: buttons_frame, text: "Rebind All")]
+    #[nwg_layout_item(layout: buttons_layout, flex_grow: 0.33)]
+    #[nwg_events(OnButtonClick: [AutoAttachTab::rebind_all])]
+    button_rebind_all: nwg::Button,
+
     // Device context menu
     #[nwg_control(text: "Device", popup: true)]
     menu: nwg::Menu,
 
+    #[nwg_control(parent: menu, text: "Rename...")]
+    #[nwg_events(OnMenuItemSelected: [AutoAttachTab::rename])]
+    menu_rename: nwg::MenuItem,
+
     #[nwg_control(parent: menu, text: "Delete")]
     #[nwg_events(OnMenuItemSelected: [AutoAttachTab::delete])]
     menu_delete: nwg::MenuItem,
+
+    // Auto-bind rule context menu
+    #[nwg_control(text: "Bind rule", popup: true)]
+    bind_rules_menu: nwg::Menu,
+
+    #[nwg_control(parent: bind_rules_menu, text: "Remove")]
+    #[nwg_events(OnMenuItemSelected: [AutoAttachTab::remove_bind_rule])]
+    bind_rules_menu_remove: nwg::MenuItem,
 }
 
 impl AutoAttachTab {
-    pub fn new(auto_attacher: &Rc<RefCell<AutoAttacher>>) -> Self {
+    pub fn new(
+        auto_attacher: &Rc<RefCell<AutoAttacher>>,
+        settings: &Rc<RefCell<Settings>>,
+        background_command: &BackgroundCommand<Result<(), String>>,
+    ) -> Self {
         Self {
             auto_attacher: auto_attacher.clone(),
+            settings: settings.clone(),
+            background_command: background_command.clone(),
             ..Default::default()
         }
     }
@@ -106,22 +206,85 @@ impl AutoAttachTab {
         let dv = &self.list_view;
         dv.clear();
         dv.insert_column("Device");
+        dv.insert_column("Status");
         dv.set_headers_enabled(true);
 
-        dv.set_column_width(0, LVSCW_AUTOSIZE_USEHEADER as isize);
+        dv.set_column_width(0, 330);
+        dv.set_column_width(1, LVSCW_AUTOSIZE_USEHEADER as isize);
+
+        let rules = &self.bind_rules_list;
+        rules.clear();
+        rules.insert_column("Device");
+        rules.set_headers_enabled(true);
+        rules.set_column_width(0, LVSCW_AUTOSIZE_USEHEADER as isize);
+    }
+
+    /// Schedules a refresh once refresh requests stop arriving.
+    fn schedule_refresh(&self) {
+        self.refresh_debounce_timer.stop();
+        self.refresh_debounce_timer.start();
+    }
+
+    /// Clears the auto-bind rule list and reloads it.
+    fn refresh_bind_rules_list(&self) {
+        *self.bind_rules.borrow_mut() = self.auto_attacher.borrow().bind_rules().to_vec();
+
+        let settings = self.settings.borrow();
+        self.bind_rules_list.clear();
+        for rule in self.bind_rules.borrow().iter() {
+            self.bind_rules_list
+                .insert_items_row(None, &[&bind_rule_name(&settings, rule)]);
+        }
     }
 
-    /// Clears the auto attach profile list and reloads it.
-    fn refresh_list(&self) {
+    /// Clears the auto attach profile list and reloads it,
+    /// using `devices` to determine the status of the profiles.
+    fn refresh_list_with_devices(&self, devices: &[UsbDevice]) {
+        // Keep track of the selected profile, as its row might change
+        let selected_id = self
+            .list_view
+            .selected_item()
+            .and_then(|i| Some(self.auto_attach_profiles.borrow().get(i)?.id.clone()));
+        let top_index = self.list_view.top_index();
+
         self.update_profiles();
 
+        let auto_attacher = self.auto_attacher.borrow();
+        let settings = self.settings.borrow();
+
         self.list_view.clear();
         for profile in self.auto_attach_profiles.borrow().iter() {
-            self.list_view.insert_items_row(
-                None,
-                &[profile.description.as_deref().unwrap_or("Unknown device")],
-            );
+            let device = devices
+                .iter()
+                .find(|d| d.is_connected() && profile.matches(d));
+
+            let status = if !auto_attacher.is_running(&profile.id) {
+                if auto_attacher.last_error(&profile.id).is_some() {
+                    "Failed"
+                } else {
+                    "Inactive"
+                }
+            } else {
+                match device {
+                    Some(device) if device.is_attached() => "Attached",
+                    Some(_) => "Connected",
+                    None => "Waiting for device",
+                }
+            };
+
+            self.list_view
+                .insert_items_row(None, &[&profile_name(&settings, profile), status]);
+        }
+
+        let selected_index = self
+            .auto_attach_profiles
+            .borrow()
+            .iter()
+            .position(|p| selected_id.as_ref() == Some(&p.id));
+        if let Some(index) = selected_index {
+            self.list_view.select_item(index, true);
         }
+        self.list_view.set_top_index(top_index);
     }
 
     /// Updates the auto attach details panel info.
@@ -129,10 +292,36 @@ impl AutoAttachTab {
         let profiles = self.auto_attach_profiles.borrow();
         let profile = self.list_view.selected_item().and_then(|i| profiles.get(i));
 
-        self.auto_attach_info.update(profile);
+        let auto_attacher = self.auto_attacher.borrow();
+        let is_running = profile.is_some_and(|p| auto_attacher.is_running(&p.id));
+        let last_error = profile.and_then(|p| auto_attacher.last_error(&p.id));
+        self.auto_attach_info
+            .update(profile, is_running, last_error);
+
+        let mode_index =
+            profile.and_then(|p| AutoAttachMode::ALL.iter().position(|m| *m == p.mode));
+        self.mode_combo.set_selection(mode_index);
+        self.mode_combo.set_enabled(profile.is_some());
 
         // Update buttons
         self.button_delete.set_enabled(profile.is_some());
+        self.button_rebind_all
+            .set_enabled(!self.background_command.is_running());
+    }
+
+    /// Applies the mode selected in the combo box to the selected profile.
+    fn change_mode(&self) {
+        let mode = match self.mode_combo.selection() {
+            Some(index) => AutoAttachMode::ALL[index],
+            None => return,
+        };
+
+        self.run_command(|profile| {
+            if profile.mode == mode {
+                return Ok(());
+            }
+            self.auto_attacher.borrow_mut().set_mode(&profile.id, mode)
+        });
     }
 
     fn show_menu(&self) {
@@ -146,10 +335,176 @@ impl AutoAttachTab {
             .popup_with_flags(x, y, nwg::PopupMenuFlags::ANIMATE_NONE);
     }
 
+    /// Deletes the selected profile, stopping its auto-attach process.
+    ///
+    /// Stopping the process may detach the device, so confirmation is asked first
+    /// if the device was attached as of the last refresh.
     fn delete(&self) {
+        let (is_attached, name) = {
+            let profiles = self.auto_attach_profiles.borrow();
+            let profile = match self.list_view.selected_item().and_then(|i| profiles.get(i)) {
+                Some(profile) => profile,
+                None => return,
+            };
+            let is_attached = self
+                .devices
+                .borrow()
+                .iter()
+                .any(|d| d.is_attached() && profile.matches(d));
+            (is_attached, profile_name(&self.settings.borrow(), profile))
+        };
+
+        if is_attached
+            && !confirm(
+                self.window.get(),
+                &self.settings,
+                "This will stop auto-attaching and may detach the device. Continue?",
+                &[name],
+            )
+        {
+            return;
+        }
+
         self.run_command(|profile| self.auto_attacher.borrow_mut().remove(profile));
     }
 
+    fn show_bind_rules_menu(&self) {
+        if self.bind_rules_list.selected_item().is_none() {
+            return;
+        }
+
+        let (x, y) = nwg::GlobalCursor::position();
+        self.bind_rules_menu.popup(x, y);
+    }
+
+    /// Removes the selected auto-bind rule. The device is left bound.
+    fn remove_bind_rule(&self) {
+        let key = match self
+            .bind_rules_list
+            .selected_item()
+            .and_then(|i| self.bind_rules.borrow().get(i).map(|r| r.key.clone()))
+        {
+            Some(key) => key,
+            None => return,
+        };
+
+        if let Err(err) = self.auto_attacher.borrow_mut().remove_bind_rule(&key) {
+            nwg::modal_error_message(self.window.get(), "WSL USB Manager: Auto Bind Error", &err);
+        }
+
+        self.refresh_bind_rules_list();
+    }
+
+    /// Asks for a new name for the device of the selected profile and stores it as its nickname.
+    /// The nickname is shared with the device in the connected devices list.
+    fn rename(&self) {
+        let (key, current_name) = {
+            let profiles = self.auto_attach_profiles.borrow();
+            let profile = match self.list_view.selected_item().and_then(|i| profiles.get(i)) {
+                Some(profile) => profile,
+                None => return,
+            };
+            let current_name = profile_name(&self.settings.borrow(), profile);
+            (profile.stable_key(), current_name)
+        };
+        let key = match key {
+            Some(key) => key,
+            None => {
+                nwg::modal_error_message(
+                    self.window.get(),
+                    "WSL USB Manager: Rename Error",
+                    "This device cannot be told apart from other devices, so it cannot be renamed.",
+                );
+                return;
+            }
+        };
+
+        let name = match ask_device_name(self.window.get(), &current_name) {
+            Some(name) => name,
+            None => return,
+        };
+
+        let result = {
+            let mut settings = self.settings.borrow_mut();
+            settings.set_nickname(key, &name);
+            settings.save()
+        };
+        if let Err(err) = result {
+            nwg::modal_error_message(self.window.get(), "WSL USB Manager: Settings Error", &err);
+        }
+
+        self.refresh();
+    }
+
+    /// Re-binds all profiled devices on a background thread, then updates their profiles in
+    /// [`AutoAttachTab::rebind_done`]. The user chooses whether the devices are re-attached too.
+    fn rebind_all(&self) {
+        let choice = nwg::modal_message(
+            self.window.get(),
+            &nwg::MessageParams {
+                title: "WSL USB Manager: Rebind All",
+                content: concat!(
+                    "Re-attach the devices to WSL once they are bound again?\n\n",
+                    "Choose No to only bind them."
+                ),
+                buttons: nwg::MessageButtons::YesNoCancel,
+                icons: nwg::MessageIcons::Question,
+            },
+        );
+        let reattach = match choice {
+            nwg::MessageChoice::Yes => true,
+            nwg::MessageChoice::No => false,
+            _ => return,
+        };
+
+        let profiles = self.auto_attacher.borrow().profiles();
+        let started = self.background_command.start(
+            &self.window.get(),
+            self.rebind_notice.sender(),
+            move || auto_attach::bind_profiled_devices(&profiles),
+        );
+        if started {
+            self.reattach_after_rebind.set(reattach);
+            self.update_auto_attach_details();
+            self.notify_busy_state();
+        }
+    }
+
+    /// Completes [`AutoAttachTab::rebind_all`]. Profiles whose device could be bound are
+    /// updated, and re-attached if chosen, even if others failed.
+    /// If an error occurs, an error dialog is shown.
+    fn rebind_done(&self) {
+        let bind_result = self
+            .background_command
+            .finish()
+            .unwrap_or_else(|| Err("Re-binding the devices stopped unexpectedly.".to_owned()));
+        let restart_result = self
+            .auto_attacher
+            .borrow_mut()
+            .update_rebound_profiles(self.reattach_after_rebind.get());
+        self.notify_busy_state();
+
+        let errors: Vec<String> = [bind_result, restart_result]
+            .into_iter()
+            .filter_map(Result::err)
+            .collect();
+        if !errors.is_empty() {
+            nwg::modal_error_message(
+                self.window.get(),
+                "WSL USB Manager: Command Error",
+                &errors.join("\n"),
+            );
+        }
+
+        self.refresh();
+    }
+
+    fn notify_busy_state(&self) {
+        if let Some(busy_notice) = self.busy_notice.get() {
+            busy_notice.notice();
+        }
+    }
+
     /// Runs a `command` function on the currently selected profile.
     /// No-op if no profile is selected.
     ///
@@ -208,12 +563,39 @@ impl GuiTab for AutoAttachTab {
     fn init(&self, window: &nwg::Window) {
         self.window.replace(window.handle);
 
+        // Re-binding devices requires admin privileges
+        let shield_bitmap = nwg::Bitmap::from_system_icon(SIID_SHIELD);
+        self.button_rebind_all.set_bitmap(Some(&shield_bitmap));
+        self.shield_bitmap.set(shield_bitmap);
+
+        self.mode_combo.set_collection(AutoAttachMode::ALL.to_vec());
+
+        // The buttons are laid out in reverse, tab through them in the order they are shown
+        set_tab_order(&[&self.button_rebind_all.handle, &self.button_delete.handle]);
+
         self.init_list();
         self.refresh();
     }
 
     fn refresh(&self) {
-        self.refresh_list();
+        // A scheduled refresh is no longer needed
+        self.refresh_debounce_timer.stop();
+
+        // Show profile changes right away, their status is updated once the devices are retrieved
+        self.refresh_list_with_devices(&self.devices.borrow());
+        self.refresh_bind_rules_list();
+        self.update_auto_attach_details();
+
+        if let Some(refresh_notice) = self.refresh_all_notice.get() {
+            refresh_notice.notice();
+        }
+    }
+
+    fn refresh_with_devices(&self, devices: &[UsbDevice]) {
+        *self.devices.borrow_mut() = devices.to_vec();
+        self.refresh_debounce_timer.stop();
+        self.refresh_list_with_devices(devices);
+        self.refresh_bind_rules_list();
         self.update_auto_attach_details();
     }
 }
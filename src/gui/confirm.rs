@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+
+use native_windows_gui as nwg;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Controls::{
+    TaskDialogIndirect, TASKDIALOGCONFIG, TDCBF_NO_BUTTON, TDCBF_YES_BUTTON,
+    TDF_ALLOW_DIALOG_CANCELLATION, TDF_POSITION_RELATIVE_TO_WINDOW, TD_WARNING_ICON,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::IDYES;
+
+use crate::settings::Settings;
+use crate::win_utils::to_wide;
+
+/// Asks the user to confirm that `action` should be performed on the devices with the given
+/// `names`. Returns `true` if the action should proceed.
+///
+/// No dialog is shown if confirmations are disabled in the settings. Checking
+/// "Don't ask me again" in the dialog disables them and saves the settings.
+pub fn confirm_action(
+    window: nwg::ControlHandle,
+    settings: &RefCell<Settings>,
+    action: &str,
+    names: &[String],
+) -> bool {
+    let instruction = match names.len() {
+        0 | 1 => format!("Are you sure you want to {action} this device?"),
+        count => format!("Are you sure you want to {action} these {count} devices?"),
+    };
+    confirm(window, settings, &instruction, names)
+}
+
+/// Asks the user to confirm the operation described by `instruction`, listing the devices
+/// with the given `names` it affects. Returns `true` if the operation should proceed.
+///
+/// Like [`confirm_action`], no dialog is shown if confirmations are disabled in the settings.
+pub fn confirm(
+    window: nwg::ControlHandle,
+    settings: &RefCell<Settings>,
+    instruction: &str,
+    names: &[String],
+) -> bool {
+    if !settings.borrow().confirm_before_detach {
+        return true;
+    }
+
+    let title = to_wide("WSL USB Manager: Confirm Action");
+    let instruction = to_wide(instruction);
+    let content = match names {
+        [] => to_wide("Unknown device"),
+        names => to_wide(&names.join("\n")),
+    };
+    let verification = to_wide("Don't ask me again");
+
+    let mut config: TASKDIALOGCONFIG = unsafe { std::mem::zeroed() };
+    config.cbSize = std::mem::size_of::<TASKDIALOGCONFIG>() as u32;
+    config.hwndParent = window.hwnd().map_or(0, |hwnd| hwnd as HWND);
+    config.dwFlags = TDF_ALLOW_DIALOG_CANCELLATION | TDF_POSITION_RELATIVE_TO_WINDOW;
+    config.dwCommonButtons = TDCBF_YES_BUTTON | TDCBF_NO_BUTTON;
+    config.pszWindowTitle = title.as_ptr();
+    config.Anonymous1.pszMainIcon = TD_WARNING_ICON;
+    config.pszMainInstruction = instruction.as_ptr();
+    config.pszContent = content.as_ptr();
+    config.nDefaultButton = IDYES;
+    config.pszVerificationText = verification.as_ptr();
+
+    let mut button = 0;
+    let mut dont_ask_again = 0;
+    let result = unsafe {
+        TaskDialogIndirect(
+            &config as *const _,
+            &mut button as *mut _,
+            std::ptr::null_mut(),
+            &mut dont_ask_again as *mut _,
+        )
+    };
+
+    // Do not block the action if the dialog could not be shown
+    if result < 0 {
+        return true;
+    }
+
+    if button != IDYES {
+        return false;
+    }
+
+    if dont_ask_again != 0 {
+        let mut settings = settings.borrow_mut();
+        settings.confirm_before_detach = false;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    true
+}
@@ -0,0 +1,87 @@
+use windows_sys::Win32::UI::Controls::LVSCW_AUTOSIZE_USEHEADER;
+
+use crate::gui::helpers::{device_name, state_label};
+use crate::settings::Settings;
+use crate::usbipd::UsbDevice;
+
+/// A column of the connected devices list.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListColumn {
+    BusId,
+    Device,
+    State,
+    VidPid,
+    Serial,
+    ClientIp,
+    Speed,
+}
+
+impl ListColumn {
+    /// The columns that are always shown, first and in this order.
+    const FIXED: [ListColumn; 3] = [ListColumn::BusId, ListColumn::Device, ListColumn::State];
+
+    /// The columns that can be shown or hidden, after the fixed ones and in this order.
+    const OPTIONAL: [ListColumn; 4] = [
+        ListColumn::VidPid,
+        ListColumn::Serial,
+        ListColumn::ClientIp,
+        ListColumn::Speed,
+    ];
+
+    /// Returns the columns shown with the current settings.
+    pub fn visible(settings: &Settings) -> Vec<ListColumn> {
+        let optional = Self::OPTIONAL
+            .into_iter()
+            .filter(|column| settings.connected_columns.iter().any(|k| k == column.key()));
+
+        Self::FIXED.into_iter().chain(optional).collect()
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            ListColumn::BusId => "Bus ID",
+            ListColumn::Device => "Device",
+            ListColumn::State => "State",
+            ListColumn::VidPid => "VID:PID",
+            ListColumn::Serial => "Serial",
+            ListColumn::ClientIp => "Client IP",
+            ListColumn::Speed => "Speed",
+        }
+    }
+
+    /// Returns the key the column is persisted with in the settings.
+    pub fn key(self) -> &'static str {
+        match self {
+            ListColumn::BusId => "bus_id",
+            ListColumn::Device => "device",
+            ListColumn::State => "state",
+            ListColumn::VidPid => "vid_pid",
+            ListColumn::Serial => "serial",
+            ListColumn::ClientIp => "client_ip",
+            ListColumn::Speed => "speed",
+        }
+    }
+
+    /// Returns the width of the column until the user resizes it.
+    pub fn default_width(self) -> isize {
+        match self {
+            ListColumn::Device => 415,
+            _ => LVSCW_AUTOSIZE_USEHEADER as isize,
+        }
+    }
+
+    /// Returns the text shown in the column for `device`.
+    pub fn value(self, settings: &Settings, device: &UsbDevice) -> String {
+        let value = match self {
+            ListColumn::BusId => device.bus_id.clone(),
+            ListColumn::Device => Some(device_name(settings, device)),
+            ListColumn::State => Some(state_label(&device.state())),
+            ListColumn::VidPid => device.vid_pid(),
+            ListColumn::Serial => device.serial(),
+            ListColumn::ClientIp => device.client_ip_address.clone(),
+            ListColumn::Speed => device.speed.map(|speed| speed.to_string()),
+        };
+
+        value.unwrap_or_else(|| "-".to_owned())
+    }
+}
@@ -6,12 +6,23 @@ use nwg::stretch::{
     style::{Dimension as D, Dimension::Points as Pt, FlexDirection},
 };
 
+use crate::gui::{helpers::time_ago, nwg_ext::RichLabelEx};
 use crate::usbipd::{UsbDevice, UsbipState};
 
+/// The text color of values that need attention, e.g. the state of forced-bound devices.
+/// A dark orange.
+const WARNING_TEXT_COLOR: [u8; 3] = [196, 89, 17];
+
+/// The default text color.
+const DEFAULT_TEXT_COLOR: [u8; 3] = [0, 0, 0];
+
 /// The connected device info tab.
 /// It displays detailed information about a connected device.
 ///
-/// Call the `update` method to update the information displayed.
+/// Call the `init` method once built, and the `update` method to update the information displayed.
+///
+/// Each content control is created right after its caption label, which screen readers
+/// use as its name, so the caption and the value are read together (e.g. "Bus ID: 1-4").
 ///
 /// # Remarks
 ///
@@ -66,6 +77,18 @@ pub struct DeviceInfo {
     #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     serial_content: nwg::RichLabel,
 
+    // Explains the consequences of devices not having a serial number
+    #[nwg_control]
+    serial_tooltip: nwg::Tooltip,
+
+    #[nwg_control(text: "Speed:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    speed: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    speed_content: nwg::RichLabel,
+
     #[nwg_control(text: "State:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
     #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     state: nwg::Label,
@@ -74,6 +97,30 @@ pub struct DeviceInfo {
     #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     state_content: nwg::RichLabel,
 
+    #[nwg_control(text: "Availability:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    availability: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    availability_content: nwg::RichLabel,
+
+    #[nwg_control(text: "Last attached:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    last_attached: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    last_attached_content: nwg::RichLabel,
+
+    #[nwg_control(text: "Remote attach:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    remote_attach: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
+    remote_attach_content: nwg::RichLabel,
+
     #[nwg_control(text: "Description:", font: Some(&data.font_bold), v_align: nwg::VTextAlign::Bottom)]
     #[nwg_layout_item(layout: device_info_layout, size: Size { width: D::Auto, height: Pt(20.0) })]
     description: nwg::Label,
@@ -84,15 +131,60 @@ pub struct DeviceInfo {
 }
 
 impl DeviceInfo {
-    pub fn update(&self, device: Option<&UsbDevice>) {
+    /// Makes the content controls reachable with the keyboard, for screen reader users.
+    pub fn init(&self) {
+        for content in [
+            &self.bus_id_content,
+            &self.vid_pid_content,
+            &self.serial_content,
+            &self.speed_content,
+            &self.state_content,
+            &self.availability_content,
+            &self.last_attached_content,
+            &self.remote_attach_content,
+            &self.description_content,
+        ] {
+            content.set_tab_stop(true);
+        }
+
+        // The text is updated along with the device
+        self.serial_tooltip.register(&self.serial_content, "");
+    }
+
+    /// Shows the information of `device`, or clears it if `None`.
+    ///
+    /// `distributions` are the WSL distributions the device is attached to, if known,
+    /// and `last_attached` is when it was last attached, in seconds since the Unix epoch.
+    /// `needs_rebind` flags the state as inconsistent, see [`UsbDevice::needs_rebind`].
+    pub fn update(
+        &self,
+        device: Option<&UsbDevice>,
+        distributions: &[String],
+        last_attached: Option<u64>,
+        needs_rebind: bool,
+    ) {
         if let Some(device) = device {
             self.bus_id_content
                 .set_text(device.bus_id.as_deref().unwrap_or("-"));
             self.vid_pid_content
                 .set_text(device.vid_pid().as_deref().unwrap_or("-"));
-            self.serial_content
-                .set_text(device.serial().as_deref().unwrap_or("-"));
-            self.state_content.set_text(&device.state().to_string());
+            self.set_serial(device);
+            self.speed_content.set_text(
+                &device
+                    .speed
+                    .map_or_else(|| "-".to_owned(), |speed| speed.to_string()),
+            );
+            self.set_state(&device.state(), needs_rebind);
+            self.availability_content
+                .set_text(&Self::availability(device, distributions));
+            self.last_attached_content
+                .set_text(&last_attached.map_or_else(|| "Never".to_owned(), time_ago));
+            self.remote_attach_content.set_text(
+                device
+                    .remote_attach_command()
+                    .as_deref()
+                    .unwrap_or("Bind the device to share it"),
+            );
             self.description_content.set_text(
                 device
                     .description
@@ -103,8 +195,91 @@ impl DeviceInfo {
             self.bus_id_content.set_text("-");
             self.vid_pid_content.set_text("-");
             self.serial_content.set_text("-");
-            self.state_content.set_text(&UsbipState::None.to_string());
+            self.serial_tooltip
+                .set_text(&self.serial_content.handle, "");
+            self.speed_content.set_text("-");
+            self.set_state(&UsbipState::None, false);
+            self.availability_content.set_text("-");
+            self.last_attached_content.set_text("-");
+            self.remote_attach_content.set_text("-");
             self.description_content.set_text("No device selected");
         }
     }
+
+    /// Shows the serial number of `device`.
+    ///
+    /// Devices without a hardware serial number are flagged, since they can only be told
+    /// apart by the port they are plugged into, see [`UsbDevice::stable_key`].
+    fn set_serial(&self, device: &UsbDevice) {
+        let (text, tooltip, warning) = match device.serial() {
+            Some(serial) => (serial, "", false),
+            None if device.instance_id.is_some() => (
+                "No hardware serial".to_owned(),
+                concat!(
+                    "The device does not provide a serial number, so it is recognized ",
+                    "by the USB port it is plugged into.\n",
+                    "Auto-attach may not survive reconnecting it to another port."
+                ),
+                true,
+            ),
+            None => ("-".to_owned(), "", false),
+        };
+
+        Self::set_text_highlighted(&self.serial_content, &text, warning);
+        self.serial_tooltip
+            .set_text(&self.serial_content.handle, tooltip);
+    }
+
+    /// Shows `state`, highlighting forced bindings and inconsistent states so that they
+    /// stand out.
+    fn set_state(&self, state: &UsbipState, needs_rebind: bool) {
+        let text = if needs_rebind {
+            format!("{state}, needs rebind")
+        } else {
+            state.to_string()
+        };
+        Self::set_text_highlighted(
+            &self.state_content,
+            &text,
+            state.is_forced() || needs_rebind,
+        );
+    }
+
+    /// Sets the text of `label`, in the warning color if `warning` is `true`.
+    fn set_text_highlighted(label: &nwg::RichLabel, text: &str, warning: bool) {
+        label.set_text(text);
+
+        let text_color = if warning {
+            WARNING_TEXT_COLOR
+        } else {
+            DEFAULT_TEXT_COLOR
+        };
+        label.set_char_format(
+            0..text.encode_utf16().count() as u32,
+            &nwg::CharFormat {
+                text_color: Some(text_color),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Describes who can use the device.
+    ///
+    /// Shared devices are not attached to WSL, but can be attached by any usbip client
+    /// that can reach this machine, e.g. a remote Linux host.
+    /// Attached devices show the WSL distributions they are attached to when known,
+    /// falling back to the IP address of the client.
+    fn availability(device: &UsbDevice, distributions: &[String]) -> String {
+        match device.state() {
+            UsbipState::None | UsbipState::Persisted => "Local only".to_owned(),
+            UsbipState::Shared(_) => "Shared, reachable by IP".to_owned(),
+            UsbipState::Attached(_) => match (&device.client_ip_address, distributions) {
+                (Some(ip), []) => format!("In use by {ip}"),
+                (Some(ip), distributions) => {
+                    format!("In use by {} ({ip})", distributions.join(", "))
+                }
+                (None, _) => "In use".to_owned(),
+            },
+        }
+    }
 }
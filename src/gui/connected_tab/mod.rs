@@ -1,26 +1,44 @@
+mod columns;
 mod device_info;
 
 use std::{
     cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    net::IpAddr,
     rc::Rc,
+    sync::{atomic::AtomicBool, mpsc::Receiver, Arc, Mutex},
 };
 
 use native_windows_derive::NwgPartial;
 use native_windows_gui as nwg;
 use nwg::stretch::{
     geometry::{Rect, Size},
-    style::{Dimension as D, FlexDirection},
+    style::{Dimension as D, FlexDirection, Style},
 };
-use windows_sys::Win32::UI::Controls::LVSCW_AUTOSIZE_USEHEADER;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::VK_RETURN;
 use windows_sys::Win32::UI::Shell::SIID_SHIELD;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    DLGC_WANTALLKEYS, WM_CONTEXTMENU, WM_GETDLGCODE, WM_KEYDOWN, WM_KILLFOCUS,
+};
 
+use self::columns::ListColumn;
 use self::device_info::DeviceInfo;
-use crate::auto_attach::AutoAttacher;
+use crate::auto_attach::{AutoAttachProfile, AutoAttacher, PendingProfile};
 use crate::gui::{
-    nwg_ext::{BitmapEx, MenuItemEx},
+    confirm::{confirm, confirm_action},
+    distribution_dialog::target_distribution,
+    helpers::{device_name, DEFAULT_DISTRIBUTION},
+    nwg_ext::{set_tab_order, BitmapEx, ListViewEx, MenuItemEx},
+    progress_dialog::run_with_progress,
+    rename_dialog::ask_device_name,
     usbipd_gui::GuiTab,
+    worker::{self, BackgroundCommand},
 };
-use crate::usbipd::{self, UsbDevice};
+use crate::logging;
+use crate::settings::{AttachTarget, Settings};
+use crate::usbipd::{self, UsbDevice, UsbipError};
+use crate::win_utils::open_url;
+use crate::wsl::{self, DistributionsByIp};
 
 const PADDING_LEFT: Rect<D> = Rect {
     start: D::Points(8.0),
@@ -29,34 +47,170 @@ const PADDING_LEFT: Rect<D> = Rect {
     bottom: D::Points(0.0),
 };
 
+const PADDING_TOP: Rect<D> = Rect {
+    start: D::Points(0.0),
+    end: D::Points(0.0),
+    top: D::Points(4.0),
+    bottom: D::Points(0.0),
+};
+
 const DETAILS_PANEL_WIDTH: f32 = 285.0;
 const DETAILS_PANEL_PADDING: u32 = 4;
 
+/// The setup instructions for attaching USB devices to WSL, linked when usbip support is missing.
+const WSL_USB_DOCS_URL: &str = "https://learn.microsoft.com/windows/wsl/connect-usb";
+
+/// The entry of the distribution selector that asks for a distribution each time.
+const ASK_DISTRIBUTION: &str = "Ask every time";
+
+/// The index of the device name column of the device list.
+const DEVICE_COLUMN: usize = 1;
+
+/// The ID of the raw event handler watching the focus of the inline rename input.
+/// Must be greater than 0xFFFF.
+const INLINE_RENAME_HANDLER_ID: usize = 0x10001;
+
+/// The ID of the raw event handler receiving the Enter key in the device list.
+/// Must be greater than 0xFFFF.
+const LIST_ENTER_HANDLER_ID: usize = 0x10002;
+
+/// The ID of the raw event handler receiving right clicks on the device list header.
+/// Must be greater than 0xFFFF.
+const HEADER_MENU_HANDLER_ID: usize = 0x10003;
+
 #[derive(Default, NwgPartial)]
 pub struct ConnectedTab {
     auto_attacher: Rc<RefCell<AutoAttacher>>,
+    settings: Rc<RefCell<Settings>>,
 
     window: Cell<nwg::ControlHandle>,
     shield_bitmap: Cell<nwg::Bitmap>,
 
     /// A notice sender to notify the auto attach tab to refresh
     pub auto_attach_notice: Cell<Option<nwg::NoticeSender>>,
-
+    /// A notice sender to notify the main window that a device command completed
+    pub busy_notice: Cell<Option<nwg::NoticeSender>>,
+    /// A notice sender to request a refresh of all tabs from the main window
+    pub refresh_all_notice: Cell<Option<nwg::NoticeSender>>,
+
+    /// All the connected devices, regardless of the filter
+    all_devices: RefCell<Vec<usbipd::UsbDevice>>,
+    /// The connected devices matching the filter, in the same order as the list view rows
     connected_devices: RefCell<Vec<usbipd::UsbDevice>>,
+    /// The client IP addresses of the attached devices, as of the last refresh
+    client_addresses: RefCell<HashSet<String>>,
+    /// The running WSL distributions by IP address, once retrieved for the client addresses
+    client_distributions: RefCell<Option<DistributionsByIp>>,
+    /// The running WSL distributions being retrieved in the background
+    client_distributions_receiver: RefCell<Option<Receiver<Option<DistributionsByIp>>>>,
+    /// Whether the client addresses changed while the distributions were being retrieved
+    client_distributions_outdated: Cell<bool>,
+    /// The stable keys of the attached devices, or `None` before the first refresh
+    attached_keys: RefCell<Option<HashSet<String>>>,
+    /// The columns shown in the list, in order
+    columns: RefCell<Vec<ListColumn>>,
+    /// The column the list is sorted by, and whether the order is descending
+    sort_column: Cell<Option<(usize, bool)>>,
+    /// The stable key and the current name of the device being renamed in place, if any
+    inline_rename: RefCell<Option<(String, String)>>,
+    inline_rename_handler: RefCell<Option<nwg::RawEventHandler>>,
+    list_enter_handler: RefCell<Option<nwg::RawEventHandler>>,
+    header_menu_handler: RefCell<Option<nwg::RawEventHandler>>,
+
+    /// Whether the WSL kernel supports usbip, once known. Checked once per session
+    usbip_support: Cell<Option<bool>>,
+    /// The check of the usbip support of the WSL kernel running in the background
+    usbip_support_receiver: RefCell<Option<Receiver<Option<bool>>>>,
+
+    /// Instance IDs of the devices whose sharing state is inconsistent in two consecutive lists
+    inconsistent_devices: RefCell<HashSet<String>>,
+    /// The device list retrieved again in the background to confirm inconsistent states
+    rebind_check_receiver: RefCell<Option<Receiver<Result<Vec<UsbDevice>, String>>>>,
+
+    /// The device command currently running in the background. Shared with the main window,
+    /// so that commands started from the menus and the tray never overlap
+    background_command: BackgroundCommand<Result<(), String>>,
+    /// Instance IDs of the devices the running command operates on, shown as working
+    working_devices: RefCell<HashSet<String>>,
+    /// The devices the running command failed to bind because they are in use,
+    /// offered a forced bind once it completes
+    in_use_devices: Arc<Mutex<Vec<UsbDevice>>>,
+    /// The devices the running command did not attach because another usbip client attached
+    /// them, offered to be taken over once it completes
+    attached_elsewhere: Arc<Mutex<AttachedElsewhere>>,
+
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::command_done])]
+    command_notice: nwg::Notice,
 
     #[nwg_layout(flex_direction: FlexDirection::Row)]
     connected_tab_layout: nwg::FlexboxLayout,
 
-    #[nwg_control(list_style: nwg::ListViewStyle::Detailed, focus: true,
-        flags: "VISIBLE|SINGLE_SELECTION|TAB_STOP",
+    #[nwg_control(flags: "VISIBLE")]
+    #[nwg_layout_item(layout: connected_tab_layout, flex_grow: 1.0)]
+    list_frame: nwg::Frame,
+
+    #[nwg_layout(parent: list_frame, flex_direction: FlexDirection::Column, auto_spacing: None)]
+    list_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: list_frame, placeholder_text: Some("Filter devices"))]
+    #[nwg_layout_item(layout: list_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
+    #[nwg_events(OnTextInput: [ConnectedTab::refresh_filter])]
+    filter_input: nwg::TextInput,
+
+    #[nwg_control(parent: list_frame, list_style: nwg::ListViewStyle::Detailed, focus: true,
+        flags: "VISIBLE|TAB_STOP",
         ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT,
     )]
     #[nwg_events(OnListViewRightClick: [ConnectedTab::show_menu],
-        OnListViewItemChanged: [ConnectedTab::update_device_details]
+        OnListViewItemChanged: [ConnectedTab::update_device_details],
+        OnListViewColumnClick: [ConnectedTab::sort_by_column(SELF, EVT_DATA)],
+        OnListViewDoubleClick: [ConnectedTab::list_double_click(SELF, EVT_DATA)]
     )]
-    #[nwg_layout_item(layout: connected_tab_layout, flex_grow: 1.0)]
+    #[nwg_layout_item(layout: list_layout, margin: PADDING_TOP, flex_grow: 1.0)]
     list_view: nwg::ListView,
 
+    // Added below the list if the WSL kernel lacks usbip support
+    #[nwg_control(parent: list_frame, flags: "NONE",
+        text: "\u{26a0} The WSL kernel does not seem to support usbip, attached devices will not show up in Linux. Click here for setup instructions.",
+    )]
+    #[nwg_events(OnLabelClick: [ConnectedTab::open_wsl_usb_docs])]
+    usbip_warning: nwg::Label,
+
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::usbip_support_checked])]
+    usbip_support_notice: nwg::Notice,
+
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::client_distributions_retrieved])]
+    client_distributions_notice: nwg::Notice,
+
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::rebind_check_done])]
+    rebind_check_notice: nwg::Notice,
+
+    // Edits the name of a device in place, placed over its cell when renaming
+    #[nwg_control(parent: list_view, flags: "AUTO_SCROLL")]
+    #[nwg_events(OnKeyEnter: [ConnectedTab::finish_inline_rename],
+        OnKeyEsc: [ConnectedTab::cancel_inline_rename]
+    )]
+    inline_rename_input: nwg::TextInput,
+
+    /// Notified when the inline rename input loses focus
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::finish_inline_rename])]
+    inline_rename_notice: nwg::Notice,
+
+    /// Notified when Enter is pressed in the device list
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::attach_detach_device])]
+    list_enter_notice: nwg::Notice,
+
+    /// Notified when the device list header is right-clicked
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ConnectedTab::show_header_menu])]
+    header_menu_notice: nwg::Notice,
+
     // Device info
     #[nwg_control]
     #[nwg_layout_item(layout: connected_tab_layout, margin: PADDING_LEFT,
@@ -77,6 +231,17 @@ pub struct ConnectedTab {
     #[nwg_partial(parent: device_info_frame)]
     device_info: DeviceInfo,
 
+    #[nwg_control(parent: details_frame, text: "Copy remote attach command")]
+    #[nwg_layout_item(layout: details_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
+    #[nwg_events(OnButtonClick: [ConnectedTab::copy_remote_attach_command])]
+    copy_remote_attach_button: nwg::Button,
+
+    // Target distribution
+    #[nwg_control(parent: details_frame)]
+    #[nwg_layout_item(layout: details_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
+    #[nwg_events(OnComboxBoxSelection: [ConnectedTab::save_attach_target])]
+    distribution_combo: nwg::ComboBox<String>,
+
     // Buttons
     #[nwg_control(parent: details_frame, flags: "VISIBLE")]
     #[nwg_layout_item(layout: details_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
@@ -100,6 +265,10 @@ pub struct ConnectedTab {
     #[nwg_events(OnButtonClick: [ConnectedTab::auto_attach_device])]
     auto_attach_button: nwg::Button,
 
+    // Explains why some buttons show the UAC shield icon
+    #[nwg_control]
+    buttons_tooltip: nwg::Tooltip,
+
     // Device context menu
     #[nwg_control(text: "Device", popup: true)]
     menu: nwg::Menu,
@@ -112,6 +281,10 @@ pub struct ConnectedTab {
     #[nwg_events(OnMenuItemSelected: [ConnectedTab::detach_device])]
     menu_detach: nwg::MenuItem,
 
+    #[nwg_control(parent: menu, text: "Reattach")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::reattach_device])]
+    menu_reattach: nwg::MenuItem,
+
     #[nwg_control(parent: menu)]
     menu_sep: nwg::MenuSeparator,
 
@@ -126,79 +299,460 @@ pub struct ConnectedTab {
     #[nwg_control(parent: menu, text: "Unbind")]
     #[nwg_events(OnMenuItemSelected: [ConnectedTab::unbind_device])]
     menu_unbind: nwg::MenuItem,
+
+    #[nwg_control(parent: menu, text: "Bind on connect")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::toggle_bind_on_connect])]
+    menu_bind_on_connect: nwg::MenuItem,
+
+    #[nwg_control(parent: menu)]
+    menu_sep2: nwg::MenuSeparator,
+
+    #[nwg_control(parent: menu, text: "Copy bus ID")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::copy_bus_id])]
+    menu_copy_bus_id: nwg::MenuItem,
+
+    #[nwg_control(parent: menu, text: "Copy instance ID")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::copy_instance_id])]
+    menu_copy_instance_id: nwg::MenuItem,
+
+    #[nwg_control(parent: menu, text: "Rename...")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::rename_device])]
+    menu_rename: nwg::MenuItem,
+
+    // Column chooser, shown when right-clicking the list header
+    #[nwg_control(text: "Columns", popup: true)]
+    header_menu: nwg::Menu,
+
+    #[nwg_control(parent: header_menu, text: "VID:PID")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::toggle_column(SELF, HANDLE)])]
+    header_menu_vid_pid: nwg::MenuItem,
+
+    #[nwg_control(parent: header_menu, text: "Serial")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::toggle_column(SELF, HANDLE)])]
+    header_menu_serial: nwg::MenuItem,
+
+    #[nwg_control(parent: header_menu, text: "Client IP")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::toggle_column(SELF, HANDLE)])]
+    header_menu_client_ip: nwg::MenuItem,
+
+    #[nwg_control(parent: header_menu, text: "Speed")]
+    #[nwg_events(OnMenuItemSelected: [ConnectedTab::toggle_column(SELF, HANDLE)])]
+    header_menu_speed: nwg::MenuItem,
 }
 
 impl ConnectedTab {
-    pub fn new(auto_attacher: &Rc<RefCell<AutoAttacher>>) -> Self {
+    pub fn new(
+        auto_attacher: &Rc<RefCell<AutoAttacher>>,
+        settings: &Rc<RefCell<Settings>>,
+        background_command: &BackgroundCommand<Result<(), String>>,
+    ) -> Self {
         Self {
             auto_attacher: auto_attacher.clone(),
+            settings: settings.clone(),
+            background_command: background_command.clone(),
             ..Default::default()
         }
     }
 
+    /// Disables the device actions while a command started elsewhere is running.
+    pub fn update_busy_state(&self) {
+        self.update_device_details();
+    }
+
+    /// Fills the distribution selector with the installed WSL distributions and selects
+    /// the saved attach target. The first entries always target the default distribution
+    /// and ask for one each time.
+    fn init_distributions(&self) {
+        let mut distributions = vec![DEFAULT_DISTRIBUTION.to_owned(), ASK_DISTRIBUTION.to_owned()];
+        distributions.extend(wsl::list_distributions());
+
+        let selection = match &self.settings.borrow().attach_target {
+            AttachTarget::Default => 0,
+            AttachTarget::Ask => 1,
+            // Kept even if the distribution is no longer installed, attaching reports the error
+            AttachTarget::Distribution(distribution) => {
+                match distributions.iter().position(|d| d == distribution) {
+                    Some(index) => index,
+                    None => {
+                        distributions.push(distribution.clone());
+                        distributions.len() - 1
+                    }
+                }
+            }
+        };
+
+        self.distribution_combo.set_collection(distributions);
+        self.distribution_combo.set_selection(Some(selection));
+    }
+
+    /// Saves the attach target chosen in the distribution selector.
+    fn save_attach_target(&self) {
+        let target = match self.distribution_combo.selection() {
+            Some(0) | None => AttachTarget::Default,
+            Some(1) => AttachTarget::Ask,
+            Some(_) => match self.distribution_combo.selection_string() {
+                Some(distribution) => AttachTarget::Distribution(distribution),
+                None => AttachTarget::Default,
+            },
+        };
+
+        let result = {
+            let mut settings = self.settings.borrow_mut();
+            settings.attach_target = target;
+            settings.save()
+        };
+        if let Err(err) = result {
+            nwg::modal_error_message(self.window.get(), "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    /// Returns the distribution to attach to, or `None` for the default one,
+    /// asking the user if the attach target says so.
+    ///
+    /// Returns `None` overall if the user cancelled.
+    fn target_distribution(&self) -> Option<Option<String>> {
+        let target = self.settings.borrow().attach_target.clone();
+        target_distribution(self.window.get(), &target)
+    }
+
     fn init_list(&self) {
         let dv = &self.list_view;
         dv.clear();
-        dv.insert_column("Bus ID");
-        dv.insert_column("Device");
-        dv.insert_column("State");
+        let columns = ListColumn::visible(&self.settings.borrow());
+        for column in &columns {
+            dv.insert_column(column.title());
+        }
         dv.set_headers_enabled(true);
 
-        dv.set_column_width(0, LVSCW_AUTOSIZE_USEHEADER as isize);
-        dv.set_column_width(1, 415);
-        dv.set_column_width(2, LVSCW_AUTOSIZE_USEHEADER as isize);
+        // Restore the widths the user left the columns at, if any
+        let saved_widths = self.settings.borrow().connected_column_widths.clone();
+        match saved_widths {
+            Some(widths) if widths.len() == columns.len() => {
+                for (index, width) in widths.into_iter().enumerate() {
+                    dv.set_column_width(index, width);
+                }
+            }
+            _ => {
+                for (index, column) in columns.iter().enumerate() {
+                    dv.set_column_width(index, column.default_width());
+                }
+            }
+        }
+        *self.columns.borrow_mut() = columns;
+    }
+
+    /// Returns the column chooser items, along with the columns they show or hide.
+    fn header_menu_items(&self) -> [(&nwg::MenuItem, ListColumn); 4] {
+        [
+            (&self.header_menu_vid_pid, ListColumn::VidPid),
+            (&self.header_menu_serial, ListColumn::Serial),
+            (&self.header_menu_client_ip, ListColumn::ClientIp),
+            (&self.header_menu_speed, ListColumn::Speed),
+        ]
+    }
+
+    fn show_header_menu(&self) {
+        let columns = self.columns.borrow();
+        for (item, column) in self.header_menu_items() {
+            item.set_checked(columns.contains(&column));
+        }
+
+        let (x, y) = nwg::GlobalCursor::position();
+        self.header_menu.popup(x, y);
+    }
+
+    /// Shows or hides the column of the selected column chooser item, and saves the choice.
+    fn toggle_column(&self, handle: &nwg::ControlHandle) {
+        let column = match self
+            .header_menu_items()
+            .into_iter()
+            .find(|(item, _)| item.handle == *handle)
+        {
+            Some((_, column)) => column,
+            None => return,
+        };
+
+        {
+            let mut settings = self.settings.borrow_mut();
+            let shown = &mut settings.connected_columns;
+            match shown.iter().position(|key| key == column.key()) {
+                Some(index) => {
+                    shown.remove(index);
+                }
+                None => shown.push(column.key().to_owned()),
+            }
+            if let Err(err) = settings.save() {
+                nwg::modal_error_message(
+                    self.window.get(),
+                    "WSL USB Manager: Settings Error",
+                    &err,
+                );
+            }
+        }
+
+        self.rebuild_columns();
     }
 
-    /// Clears the device list and reloads it with the currently connected devices.
-    fn refresh_list(&self) {
-        self.update_devices();
+    /// Recreates the list columns after the shown columns changed.
+    /// Columns that stay keep their width, and the list stays sorted by the same column.
+    fn rebuild_columns(&self) {
+        let old_columns = self.columns.take();
+        let widths: HashMap<ListColumn, isize> = old_columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| (*column, self.list_view.column_width(index)))
+            .collect();
+        let sorted_by = self
+            .sort_column
+            .get()
+            .and_then(|(index, descending)| Some((*old_columns.get(index)?, descending)));
+
+        let columns = ListColumn::visible(&self.settings.borrow());
+        let dv = &self.list_view;
+        dv.clear();
+        for index in (0..dv.column_len()).rev() {
+            dv.remove_column(index);
+        }
+        for (index, column) in columns.iter().enumerate() {
+            dv.insert_column(column.title());
+            dv.set_column_width(
+                index,
+                widths
+                    .get(column)
+                    .copied()
+                    .unwrap_or_else(|| column.default_width()),
+            );
+        }
+
+        self.sort_column
+            .set(sorted_by.and_then(|(column, descending)| {
+                Some((columns.iter().position(|c| *c == column)?, descending))
+            }));
+        *self.columns.borrow_mut() = columns;
+        self.refresh_list_with_devices();
+    }
+
+    /// Returns the current column widths of the device list.
+    pub fn column_widths(&self) -> Vec<isize> {
+        (0..self.list_view.column_len())
+            .map(|index| self.list_view.column_width(index))
+            .collect()
+    }
+
+    /// Returns the number of connected devices and how many of them are attached,
+    /// as of the last refresh.
+    pub fn device_counts(&self) -> (usize, usize) {
+        let devices = self.all_devices.borrow();
+        let attached = devices.iter().filter(|d| d.is_attached()).count();
+        (devices.len(), attached)
+    }
+
+    /// Clears the device list and reloads it with the connected devices matching the filter,
+    /// without retrieving the devices again.
+    fn refresh_list_with_devices(&self) {
+        // Keep track of the selected devices, as their rows might change
+        let selected_ids: Vec<Option<String>> = self
+            .selected_devices()
+            .into_iter()
+            .map(|d| d.instance_id)
+            .collect();
+        let top_index = self.list_view.top_index();
+
+        let filter = self.filter_input.text().to_lowercase();
+        let hide_hubs = self.settings.borrow().hide_hubs;
+        let mut devices: Vec<UsbDevice> = self
+            .all_devices
+            .borrow()
+            .iter()
+            .filter(|d| !(hide_hubs && d.is_hub()))
+            .filter(|d| self.matches_filter(d, &filter))
+            .cloned()
+            .collect();
+        self.sort_devices(&mut devices);
+        *self.connected_devices.borrow_mut() = devices;
 
         self.list_view.clear();
+        let settings = self.settings.borrow();
+        let columns = self.columns.borrow();
+        let working_devices = self.working_devices.borrow();
         for device in self.connected_devices.borrow().iter() {
-            self.list_view.insert_items_row(
-                None,
-                &[
-                    device.bus_id.as_deref().unwrap_or("-"),
-                    device.description.as_deref().unwrap_or("Unknown device"),
-                    &device.state().to_string(),
-                ],
-            );
+            // Keep showing which devices are being operated on until the command completes
+            let working = device
+                .instance_id
+                .as_ref()
+                .is_some_and(|id| working_devices.contains(id));
+            let row: Vec<String> = columns
+                .iter()
+                .map(|column| match column {
+                    ListColumn::State if working => "Working...".to_owned(),
+                    _ => column.value(&settings, device),
+                })
+                .collect();
+            self.list_view.insert_items_row(None, &row);
         }
+
+        for (index, device) in self.connected_devices.borrow().iter().enumerate() {
+            if device.instance_id.is_some() && selected_ids.contains(&device.instance_id) {
+                self.list_view.select_item(index, true);
+            }
+        }
+        self.list_view.set_top_index(top_index);
+
+        // The device being renamed might have moved to another row
+        self.place_inline_rename_input();
     }
 
-    /// Updates the device details panel with the currently selected device.
-    fn update_device_details(&self) {
-        let devices = self.connected_devices.borrow();
-        let device = self.list_view.selected_item().and_then(|i| devices.get(i));
+    /// Sorts the devices by the current sort column. No-op if the list is not sorted.
+    fn sort_devices(&self, devices: &mut [UsbDevice]) {
+        let (column, descending) = match self.sort_column.get() {
+            Some((index, descending)) => match self.columns.borrow().get(index) {
+                Some(column) => (*column, descending),
+                None => return,
+            },
+            None => return,
+        };
+
+        let settings = self.settings.borrow();
+        devices.sort_by(|a, b| {
+            let ordering = match column {
+                ListColumn::BusId => usbipd::compare_bus_ids(
+                    a.bus_id.as_deref().unwrap_or_default(),
+                    b.bus_id.as_deref().unwrap_or_default(),
+                ),
+                ListColumn::Device => {
+                    let a = device_name(&settings, a);
+                    let b = device_name(&settings, b);
+                    a.to_lowercase().cmp(&b.to_lowercase())
+                }
+                ListColumn::State => a.state().to_string().cmp(&b.state().to_string()),
+                ListColumn::Speed => a.speed.cmp(&b.speed),
+                _ => column.value(&settings, a).cmp(&column.value(&settings, b)),
+            };
+
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
 
-        self.device_info.update(device);
+    /// Sorts the list by the clicked column.
+    /// Clicking the same column again toggles between ascending and descending order.
+    fn sort_by_column(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnListViewItemIndex { column_index, .. } = data {
+            let descending = match self.sort_column.get() {
+                Some((column, descending)) if column == *column_index => !descending,
+                _ => false,
+            };
+
+            self.sort_column.set(Some((*column_index, descending)));
+            self.refresh_list_with_devices();
+        }
+    }
+
+    /// Returns whether the name, description, bus ID or VID:PID of the device contain `filter`.
+    /// `filter` must be lowercase, matching is case-insensitive.
+    fn matches_filter(&self, device: &UsbDevice, filter: &str) -> bool {
+        if filter.is_empty() {
+            return true;
+        }
+
+        [
+            Some(device_name(&self.settings.borrow(), device)),
+            device.description.clone(),
+            device.bus_id.clone(),
+            device.vid_pid(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase().contains(filter))
+    }
+
+    /// Applies the current filter to the device list.
+    fn refresh_filter(&self) {
+        self.refresh_list_with_devices();
+        self.update_device_details();
+    }
+
+    /// Updates the device details panel with the first selected device,
+    /// and the buttons with the actions available for all the selected devices.
+    fn update_device_details(&self) {
+        let selected = self.selected_devices();
+        let device = selected.first();
+
+        let distributions = device
+            .map(|d| self.client_distributions(d))
+            .unwrap_or_default();
+        let last_attached = device.and_then(|d| {
+            self.settings
+                .borrow()
+                .last_attached(d.stable_key().as_deref())
+        });
+        let needs_rebind = device.is_some_and(|d| self.needs_rebind(d));
+        self.device_info
+            .update(device, &distributions, last_attached, needs_rebind);
+        self.copy_remote_attach_button
+            .set_enabled(selected.len() == 1 && device.is_some_and(|d| d.is_bound()));
 
         // Update buttons
         if let Some(device) = device {
-            if device.is_bound() {
-                self.bind_unbind_button.set_text("Unbind");
-                self.auto_attach_button.set_enabled(true);
-
-                // Attaching a bound device doesn't require admin privileges, hide the UAC shield icon
-                self.attach_detach_button.set_bitmap(None);
-            } else {
+            // Buttons act on the devices that are not attached or bound yet, if any
+            let attach = selected.iter().any(|d| !d.is_attached());
+            let bind = selected.iter().any(|d| !d.is_bound());
+            let attach_requires_bind = selected.iter().any(|d| !d.is_attached() && !d.is_bound());
+
+            if selected.iter().any(|d| self.needs_rebind(d)) {
+                self.bind_unbind_button.set_text("Rebind");
+            } else if bind {
                 self.bind_unbind_button.set_text("Bind");
-                self.auto_attach_button.set_enabled(false);
+            } else {
+                self.bind_unbind_button.set_text("Unbind");
+            }
+            // Auto attach profiles are added one device at a time
+            self.auto_attach_button
+                .set_enabled(selected.len() == 1 && device.is_bound());
 
+            if attach_requires_bind {
                 // Attaching an unbound device requires admin privileges, show the UAC shield icon
                 let shield_bitmap = self.shield_bitmap.take();
                 self.attach_detach_button.set_bitmap(Some(&shield_bitmap));
                 self.shield_bitmap.set(shield_bitmap);
+            } else {
+                // Attaching a bound device doesn't require admin privileges, hide the UAC shield icon
+                self.attach_detach_button.set_bitmap(None);
             }
 
-            if device.is_attached() {
+            if !attach {
                 self.attach_detach_button.set_text("Detach");
+                self.buttons_tooltip.set_text(
+                    &self.attach_detach_button.handle,
+                    "Detach the device from WSL",
+                );
+            } else if !attach_requires_bind {
+                self.attach_detach_button.set_text("Attach");
+                self.buttons_tooltip.set_text(
+                    &self.attach_detach_button.handle,
+                    "Attach the device to WSL",
+                );
             } else {
                 self.attach_detach_button.set_text("Attach");
+                self.buttons_tooltip.set_text(
+                    &self.attach_detach_button.handle,
+                    concat!(
+                        "Attaching an unbound device requires administrator privileges, ",
+                        "because it has to be bound first"
+                    ),
+                );
             }
 
-            self.bind_unbind_button.set_enabled(true);
-            self.attach_detach_button.set_enabled(true);
+            // Only one command can run at a time
+            let busy = self.background_command.is_running();
+            self.bind_unbind_button.set_enabled(!busy);
+            self.attach_detach_button.set_enabled(!busy);
+            if busy {
+                self.auto_attach_button.set_enabled(false);
+            }
         } else {
             self.attach_detach_button.set_text("Attach");
             self.bind_unbind_button.set_text("Bind");
@@ -211,39 +765,52 @@ impl ConnectedTab {
     }
 
     fn show_menu(&self) {
-        let selected_index = match self.list_view.selected_item() {
-            Some(index) => index,
-            None => return,
-        };
-        let devices = self.connected_devices.borrow();
-        let device = devices.get(selected_index).unwrap();
-
-        if device.is_attached() {
-            self.menu_detach.set_enabled(true);
-            self.menu_attach.set_enabled(false);
-        } else {
-            self.menu_detach.set_enabled(false);
-            self.menu_attach.set_enabled(true);
+        let selected = self.selected_devices();
+        if selected.is_empty() {
+            return;
         }
 
-        if device.is_bound() {
-            self.menu_bind.set_enabled(false);
-            self.menu_bind_force.set_enabled(false);
-            self.menu_unbind.set_enabled(true);
-
-            // Attaching a bound device doesn't require admin privileges, hide the UAC shield icon
-            self.menu_attach.set_bitmap(None);
-        } else {
-            self.menu_bind.set_enabled(true);
-            self.menu_bind_force.set_enabled(true);
-            self.menu_unbind.set_enabled(false);
-
+        // Only one command can run at a time
+        let busy = self.background_command.is_running();
+        self.menu_attach
+            .set_enabled(!busy && selected.iter().any(|d| !d.is_attached()));
+        self.menu_detach
+            .set_enabled(!busy && selected.iter().any(|d| d.is_attached()));
+        self.menu_reattach
+            .set_enabled(!busy && selected.iter().any(|d| d.is_attached()));
+
+        let any_unbound = selected.iter().any(|d| !d.is_bound());
+        self.menu_bind.set_enabled(!busy && any_unbound);
+        self.menu_bind_force.set_enabled(!busy && any_unbound);
+        self.menu_unbind
+            .set_enabled(!busy && selected.iter().any(|d| d.is_bound()));
+
+        if selected.iter().any(|d| !d.is_attached() && !d.is_bound()) {
             // Attaching an unbound device requires admin privileges, show the UAC shield icon
             let shield_bitmap = self.shield_bitmap.take();
             self.menu_attach.set_bitmap(Some(&shield_bitmap));
             self.shield_bitmap.set(shield_bitmap);
+        } else {
+            // Attaching a bound device doesn't require admin privileges, hide the UAC shield icon
+            self.menu_attach.set_bitmap(None);
         }
 
+        self.menu_copy_bus_id
+            .set_enabled(selected.iter().any(|d| d.bus_id.is_some()));
+        self.menu_copy_instance_id
+            .set_enabled(selected.iter().any(|d| d.instance_id.is_some()));
+        self.menu_rename.set_enabled(selected.len() == 1);
+
+        let single_device = match selected.as_slice() {
+            [device] => device.stable_key().map(|_| device),
+            _ => None,
+        };
+        self.menu_bind_on_connect
+            .set_enabled(single_device.is_some());
+        self.menu_bind_on_connect.set_checked(
+            single_device.is_some_and(|d| self.auto_attacher.borrow().has_bind_rule(d)),
+        );
+
         let (x, y) = nwg::GlobalCursor::position();
         // Disable menu animations because they cause incorrect rendering of the bitmaps
         self.menu
@@ -251,123 +818,857 @@ impl ConnectedTab {
     }
 
     fn bind_device(&self) {
-        self.run_command(|device| {
-            device.bind(false)?;
-            device.wait(|d| d.is_some_and(|d| d.is_bound()))
-        });
+        let devices = self.selected_devices_where(|d| !d.is_bound());
+        self.bind_devices(devices);
     }
 
     fn bind_device_force(&self) {
-        self.run_command(|device| {
+        let devices = self.selected_devices_where(|d| !d.is_bound());
+        self.bind_devices_force(devices);
+    }
+
+    fn unbind_device(&self) {
+        let devices = self.selected_devices_where(|d| d.is_bound());
+        self.unbind_devices(devices);
+    }
+
+    fn attach_device(&self) {
+        let devices = self.selected_devices_where(|d| !d.is_attached());
+        self.attach_devices(devices);
+    }
+
+    fn detach_device(&self) {
+        let devices = self.selected_devices_where(|d| d.is_attached());
+        self.detach_devices(devices);
+    }
+
+    fn reattach_device(&self) {
+        let devices = self.selected_devices_where(|d| d.is_attached());
+        self.reattach_devices(devices);
+    }
+
+    /// Renames the device in place if its name was double-clicked,
+    /// otherwise attaches or detaches it if it is bound.
+    fn list_double_click(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnListViewItemIndex { column_index, .. } = data {
+            if *column_index == DEVICE_COLUMN {
+                self.start_inline_rename();
+                return;
+            }
+        }
+        self.toggle_attach_bound_device();
+    }
+
+    /// Attaches the selected device, or detaches it if it is already attached.
+    /// Unbound devices are ignored, as attaching them would require binding them first.
+    fn toggle_attach_bound_device(&self) {
+        if self.selected_device().is_some_and(|d| d.is_bound()) {
+            self.attach_detach_device();
+        }
+    }
+
+    /// Attaches the selected devices that are not attached yet.
+    /// If all of them are attached, detaches them instead.
+    fn attach_detach_device(&self) {
+        let devices = self.selected_devices_where(|d| !d.is_attached());
+        if devices.is_empty() {
+            self.detach_device();
+        } else {
+            self.attach_devices(devices);
+        }
+    }
+
+    /// Binds the selected devices that are not bound yet.
+    /// If all of them are bound, unbinds them instead.
+    /// Devices in an inconsistent state are rebound first.
+    fn bind_unbind_device(&self) {
+        let devices = self.selected_devices_where(|d| self.needs_rebind(d));
+        if !devices.is_empty() {
+            self.rebind_devices(devices);
+            return;
+        }
+
+        let devices = self.selected_devices_where(|d| !d.is_bound());
+        if devices.is_empty() {
+            self.unbind_device();
+        } else {
+            self.bind_devices(devices);
+        }
+    }
+
+    fn bind_devices(&self, devices: Vec<UsbDevice>) {
+        let in_use_devices = self.in_use_devices.clone();
+        self.run_batch_command(devices, move |devices| {
+            let results = if let [device] = devices {
+                vec![device.bind(false)]
+            } else {
+                // Ask for admin privileges only once for all the devices
+                usbipd::bind_all(&devices.iter().collect::<Vec<_>>())
+            };
+
+            // Devices in use are not errors yet, a forced bind is offered for them instead
+            let mut in_use = Vec::new();
+            let mut others = Vec::new();
+            for (device, result) in devices.iter().zip(results) {
+                match result {
+                    Err(UsbipError::DeviceInUse) => in_use.push(device.clone()),
+                    result => others.push((device, result)),
+                }
+            }
+            *in_use_devices.lock().unwrap() = in_use;
+
+            usbipd::device_errors(others.into_iter().map(|(device, result)| {
+                let result = result
+                    .map_err(String::from)
+                    .and_then(|()| device.wait(|d| d.is_some_and(|d| d.is_bound())));
+                (device, result)
+            }))
+        });
+    }
+
+    fn bind_devices_force(&self, devices: Vec<UsbDevice>) {
+        self.run_command(devices, move |device| {
             device.bind(true)?;
             device.wait(|d| d.is_some_and(|d| d.is_bound() && d.is_forced))
         });
     }
 
-    fn unbind_device(&self) {
-        self.run_command(|device| {
+    /// Asks the user whether to retry a bind that failed because the device is in use
+    /// with a forced bind.
+    fn confirm_force_bind(&self) -> bool {
+        let content = concat!(
+            "The device seems to be in use by Windows or another driver. ",
+            "A forced bind shares it anyway, but the driver will not be able to use it ",
+            "until the device is unbound.\n\n",
+            "Retry with a forced bind?"
+        );
+        let choice = nwg::modal_message(
+            self.window.get(),
+            &nwg::MessageParams {
+                title: "WSL USB Manager: Bind Error",
+                content,
+                buttons: nwg::MessageButtons::YesNo,
+                icons: nwg::MessageIcons::Warning,
+            },
+        );
+
+        matches!(choice, nwg::MessageChoice::Yes)
+    }
+
+    fn unbind_devices(&self, devices: Vec<UsbDevice>) {
+        if !self.confirm("unbind", &devices) {
+            return;
+        }
+        self.run_command(devices, move |device| {
             device.unbind()?;
             device.wait(|d| d.is_some_and(|d| !d.is_bound()))
         });
     }
 
-    fn attach_device(&self) {
-        self.run_command(|device| {
-            device.attach()?;
-            device.wait(|d| d.is_some_and(|d| d.is_attached()))
+    /// Unbinds and binds the devices again, to fix an inconsistent sharing state.
+    fn rebind_devices(&self, devices: Vec<UsbDevice>) {
+        self.run_command(devices, move |device| {
+            device.unbind()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_bound()))?;
+            device.bind(false)?;
+            device.wait(|d| d.is_some_and(|d| d.is_bound()))
         });
     }
 
-    fn detach_device(&self) {
-        self.run_command(|device| {
+    /// Attaches the devices to the target distribution on a background thread.
+    ///
+    /// Devices that another usbip client attached since the last refresh are skipped,
+    /// and taken over once the command completes, by detaching them first, if the user confirms.
+    fn attach_devices(&self, devices: Vec<UsbDevice>) {
+        let distribution = match self.target_distribution() {
+            Some(distribution) => distribution,
+            None => return,
+        };
+
+        let attached_elsewhere = self.attached_elsewhere.clone();
+        self.run_batch_command(devices, move |devices| {
+            let (devices, elsewhere) = split_attached_elsewhere(devices);
+            *attached_elsewhere.lock().unwrap() = AttachedElsewhere {
+                devices: elsewhere,
+                distribution: distribution.clone(),
+            };
+
+            usbipd::run_on_devices(&devices, |device| {
+                device.attach(distribution.as_deref())?;
+                device.wait(|d| d.is_some_and(|d| d.is_attached()))
+            })
+        });
+    }
+
+    /// Asks the user whether to take over `devices` from the usbip clients they are attached to.
+    /// Each device comes with whether its client is known not to be WSL.
+    fn confirm_take_over(&self, devices: &[(UsbDevice, bool)]) -> bool {
+        let names: Vec<String> = {
+            let settings = self.settings.borrow();
+            devices
+                .iter()
+                .map(|(d, not_wsl)| {
+                    let client = match &d.client_ip_address {
+                        Some(ip) if *not_wsl => ip.as_str(),
+                        _ => "an unknown client",
+                    };
+                    format!("{} (in use by {client})", device_name(&settings, d))
+                })
+                .collect()
+        };
+
+        // Only warn about other clients when they are known not to be WSL
+        let not_wsl = devices.iter().any(|(_, not_wsl)| *not_wsl);
+        let instruction = match (names.len(), not_wsl) {
+            (1, true) => "This device is in use by another client. Take it over for WSL?",
+            (_, true) => "These devices are in use by another client. Take them over for WSL?",
+            (1, false) => "This device was attached by an unknown client. Take it over for WSL?",
+            (_, false) => {
+                "These devices were attached by an unknown client. Take them over for WSL?"
+            }
+        };
+        confirm(self.window.get(), &self.settings, instruction, &names)
+    }
+
+    /// Detaches the devices from the usbip clients they are attached to,
+    /// then attaches them to `distribution`.
+    fn take_over_devices(&self, devices: Vec<UsbDevice>, distribution: Option<String>) {
+        self.run_command(devices, move |device| {
             device.detach()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_attached()))?;
+            device.attach(distribution.as_deref())?;
             device.wait(|d| d.is_some_and(|d| d.is_attached()))
         });
     }
 
-    fn attach_detach_device(&self) {
-        self.run_command(|device| {
-            if !device.is_attached() {
-                device.attach()?;
-                device.wait(|d| d.is_some_and(|d| d.is_attached()))
-            } else {
-                device.detach()?;
-                device.wait(|d| d.is_some_and(|d| !d.is_attached()))
-            }
+    fn detach_devices(&self, devices: Vec<UsbDevice>) {
+        if !self.confirm("detach", &devices) {
+            return;
+        }
+        self.run_command(devices, move |device| {
+            device.detach()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_attached()))
         });
     }
 
-    fn bind_unbind_device(&self) {
-        self.run_command(|device| {
-            if !device.is_bound() {
-                device.bind(false)?;
-                device.wait(|d| d.is_some_and(|d| d.is_bound()))
-            } else {
-                device.unbind()?;
-                device.wait(|d| d.is_some_and(|d| !d.is_bound()))
-            }
+    /// Detaches the devices and attaches them again to the target distribution,
+    /// e.g. to recover devices that stopped working in WSL.
+    fn reattach_devices(&self, devices: Vec<UsbDevice>) {
+        if !self.confirm("reattach", &devices) {
+            return;
+        }
+        let distribution = match self.target_distribution() {
+            Some(distribution) => distribution,
+            None => return,
+        };
+        self.run_command(devices, move |device| {
+            device.detach()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_attached()))?;
+            device.attach(distribution.as_deref())?;
+            device.wait(|d| d.is_some_and(|d| d.is_attached()))
         });
     }
 
+    /// Adds an auto attach profile for the selected device.
+    ///
+    /// Checking WSL might start the distribution, so it runs in the background along with
+    /// starting the auto-attach process, in a progress dialog that lets the user cancel.
     fn auto_attach_device(&self) {
-        self.run_command(|device| {
-            self.auto_attacher.borrow_mut().add_device(device)?;
+        let device = match self.selected_device() {
+            Some(device) => device,
+            None => return,
+        };
+        let distribution = match self.target_distribution() {
+            Some(distribution) => distribution,
+            None => return,
+        };
 
-            let auto_attach_notice = self.auto_attach_notice.get().unwrap();
-            auto_attach_notice.notice();
-            self.auto_attach_notice.set(Some(auto_attach_notice));
+        // Profiles are owned by the UI thread, only checking WSL and starting the process
+        // run in the background, while the auto attacher is not borrowed
+        let window = self.window.get();
+        let new_profile = self
+            .auto_attacher
+            .borrow()
+            .new_profile(&device, distribution.as_deref());
+        let profile = match new_profile {
+            Ok(profile) => profile,
+            Err(err) => {
+                nwg::modal_error_message(window, "WSL USB Manager: Command Error", &err);
+                return;
+            }
+        };
 
-            Ok(())
+        let probe_command = self.settings.borrow().wsl_probe_command.clone();
+        let (task_profile, task_device) = (profile.clone(), device.clone());
+        let setup = run_with_progress(window, "Setting up auto attach...", move |cancelled| {
+            if !probe_command.trim().is_empty() {
+                let probed =
+                    wsl::probe_usb_support(distribution.as_deref(), &probe_command, cancelled)?;
+                if let Err(err) = probed {
+                    return Some(AutoAttachSetup::ProbeFailed(err));
+                }
+            }
+            start_auto_attach(task_profile, &task_device, cancelled).map(AutoAttachSetup::Started)
         });
+
+        let result = match setup {
+            Some(AutoAttachSetup::Started(result)) => result,
+            Some(AutoAttachSetup::ProbeFailed(err)) => {
+                if !self.confirm_failed_probe(&err) {
+                    return;
+                }
+                let started =
+                    run_with_progress(window, "Setting up auto attach...", move |cancelled| {
+                        start_auto_attach(profile, &device, cancelled)
+                    });
+                match started {
+                    Some(result) => result,
+                    None => return,
+                }
+            }
+            // Cancelled by the user, the profile is not added
+            None => return,
+        };
+
+        let result =
+            result.and_then(|pending| self.auto_attacher.borrow_mut().add_started(pending));
+        match result {
+            Ok(()) => {
+                let auto_attach_notice = self.auto_attach_notice.get().unwrap();
+                auto_attach_notice.notice();
+                self.auto_attach_notice.set(Some(auto_attach_notice));
+            }
+            Err(err) => nwg::modal_error_message(window, "WSL USB Manager: Command Error", &err),
+        }
+
+        self.refresh();
     }
 
-    /// Runs a `command` function on the currently selected device.
-    /// No-op if no device is selected.
-    ///
-    /// If the command completes successfully, the view is reloaded.
-    ///
-    /// If an error occurs, an error dialog is shown.
-    fn run_command(&self, command: impl Fn(&UsbDevice) -> Result<(), String>) {
-        let window = self.window.get();
+    /// Adds or removes the rule binding the selected device whenever it is connected.
+    fn toggle_bind_on_connect(&self) {
+        let device = match self.selected_device() {
+            Some(device) => device,
+            None => return,
+        };
 
-        let wait_cursor = nwg::Cursor::from_system(nwg::OemCursor::Wait);
-        let cursor_event =
-            nwg::full_bind_event_handler(&window, move |event, _event_data, _handle| match event {
-                nwg::Event::OnMousePress(_) | nwg::Event::OnMouseMove => {
-                    nwg::GlobalCursor::set(&wait_cursor)
+        let result = {
+            let mut auto_attacher = self.auto_attacher.borrow_mut();
+            match device.stable_key() {
+                Some(key) if auto_attacher.has_bind_rule(&device) => {
+                    auto_attacher.remove_bind_rule(&key)
                 }
-                _ => {}
-            });
+                _ => auto_attacher.add_bind_rule(&device),
+            }
+        };
+        if let Err(err) = result {
+            nwg::modal_error_message(self.window.get(), "WSL USB Manager: Auto Bind Error", &err);
+        }
 
-        let result = {
-            let selected_index = match self.list_view.selected_item() {
-                Some(index) => index,
-                None => return,
-            };
-            // Borrow devices in a scoped block so that the ref is released as soon as possible
-            let devices = self.connected_devices.borrow();
-            let device = match devices.get(selected_index) {
-                Some(device) => device,
-                None => return,
-            };
+        let auto_attach_notice = self.auto_attach_notice.get().unwrap();
+        auto_attach_notice.notice();
+        self.auto_attach_notice.set(Some(auto_attach_notice));
+    }
+
+    /// Asks the user whether to add a device to auto attach although checking that it will be
+    /// visible in the target WSL distribution failed with `err`.
+    fn confirm_failed_probe(&self, err: &str) -> bool {
+        let content = format!(
+            concat!(
+                "The device might not be visible inside WSL after attaching it. ",
+                "Please make sure that WSL is working and that the USB tools ",
+                "are installed in the distribution.\n\n",
+                "{}\n\n",
+                "Add the device to auto attach anyway?"
+            ),
+            err
+        );
+        let choice = nwg::modal_message(
+            self.window.get(),
+            &nwg::MessageParams {
+                title: "WSL USB Manager: WSL Check Failed",
+                content: &content,
+                buttons: nwg::MessageButtons::YesNo,
+                icons: nwg::MessageIcons::Warning,
+            },
+        );
+
+        matches!(choice, nwg::MessageChoice::Yes)
+    }
+
+    fn copy_bus_id(&self) {
+        let bus_ids: Vec<String> = self
+            .selected_devices()
+            .into_iter()
+            .filter_map(|d| d.bus_id)
+            .collect();
+        if !bus_ids.is_empty() {
+            nwg::Clipboard::set_data_text(self.window.get(), &bus_ids.join("\r\n"));
+        }
+    }
+
+    /// Copies the `usbip attach` command a remote client runs to attach the selected device.
+    fn copy_remote_attach_command(&self) {
+        if let Some(command) = self
+            .selected_device()
+            .and_then(|d| d.remote_attach_command())
+        {
+            nwg::Clipboard::set_data_text(self.window.get(), &command);
+        }
+    }
+
+    fn copy_instance_id(&self) {
+        let instance_ids: Vec<String> = self
+            .selected_devices()
+            .into_iter()
+            .filter_map(|d| d.instance_id)
+            .collect();
+        if !instance_ids.is_empty() {
+            nwg::Clipboard::set_data_text(self.window.get(), &instance_ids.join("\r\n"));
+        }
+    }
+
+    /// Asks for a new name for the selected device and stores it as its nickname.
+    fn rename_device(&self) {
+        let (device, key) = match self.selected_rename_target() {
+            Some(target) => target,
+            None => return,
+        };
+
+        let current_name = device_name(&self.settings.borrow(), &device);
+        if let Some(name) = ask_device_name(self.window.get(), &current_name) {
+            self.save_nickname(key, &name);
+        }
+    }
+
+    /// Starts renaming the selected device in place, over its cell in the list.
+    fn start_inline_rename(&self) {
+        let (device, key) = match self.selected_rename_target() {
+            Some(target) => target,
+            None => return,
+        };
 
-            command(device)
+        let current_name = device_name(&self.settings.borrow(), &device);
+        self.inline_rename_input.set_text(&current_name);
+        *self.inline_rename.borrow_mut() = Some((key, current_name));
+        self.place_inline_rename_input();
+        self.inline_rename_input.set_focus();
+        self.inline_rename_input.set_selection(0..u32::MAX);
+    }
+
+    /// Moves the inline rename input over the row of the device being renamed,
+    /// or cancels renaming if the device is no longer listed.
+    fn place_inline_rename_input(&self) {
+        let row = match self.inline_rename.borrow().as_ref() {
+            Some((key, _)) => self
+                .connected_devices
+                .borrow()
+                .iter()
+                .position(|d| d.stable_key().as_ref() == Some(key)),
+            None => return,
         };
 
+        let placed = row.is_some_and(|row| {
+            self.list_view
+                .overlay_cell(row, DEVICE_COLUMN, &self.inline_rename_input.handle)
+        });
+        if !placed {
+            self.cancel_inline_rename();
+        }
+    }
+
+    /// Stores the name entered in place, if renaming is in progress and the name changed.
+    /// Called when Enter is pressed or the input loses focus.
+    fn finish_inline_rename(&self) {
+        let (key, current_name) = match self.inline_rename.borrow_mut().take() {
+            Some(rename) => rename,
+            None => return,
+        };
+
+        self.inline_rename_input.set_visible(false);
+        let name = self.inline_rename_input.text();
+        if name != current_name {
+            self.save_nickname(key, &name);
+        }
+        self.list_view.set_focus();
+    }
+
+    /// Stops renaming in place, keeping the current name.
+    fn cancel_inline_rename(&self) {
+        if self.inline_rename.borrow_mut().take().is_some() {
+            self.inline_rename_input.set_visible(false);
+            self.list_view.set_focus();
+        }
+    }
+
+    /// Returns the selected device along with the key its nickname is stored under.
+    /// Shows an error dialog if the device cannot be told apart from others.
+    fn selected_rename_target(&self) -> Option<(UsbDevice, String)> {
+        let device = self.selected_device()?;
+        match device.stable_key() {
+            Some(key) => Some((device, key)),
+            None => {
+                nwg::modal_error_message(
+                    self.window.get(),
+                    "WSL USB Manager: Rename Error",
+                    "This device cannot be told apart from other devices, so it cannot be renamed.",
+                );
+                None
+            }
+        }
+    }
+
+    /// Stores `name` as the nickname of the device with the given `key` and shows it.
+    fn save_nickname(&self, key: String, name: &str) {
+        let result = {
+            let mut settings = self.settings.borrow_mut();
+            settings.set_nickname(key, name);
+            settings.save()
+        };
         if let Err(err) = result {
-            nwg::modal_error_message(window, "WSL USB Manager: Command Error", &err);
+            nwg::modal_error_message(self.window.get(), "WSL USB Manager: Settings Error", &err);
         }
 
-        self.window.set(window);
-        self.refresh();
-        nwg::unbind_event_handler(&cursor_event);
+        self.refresh_list_with_devices();
+        self.update_device_details();
     }
 
-    fn update_devices(&self) {
-        *self.connected_devices.borrow_mut() = usbipd::list_devices()
+    /// Returns the running WSL distributions the device is attached to, judging by its client
+    /// IP address. Empty until they are retrieved in the background.
+    fn client_distributions(&self, device: &UsbDevice) -> Vec<String> {
+        match (
+            &device.client_ip_address,
+            &*self.client_distributions.borrow(),
+        ) {
+            (Some(ip_address), Some(distributions)) => {
+                distributions.get(ip_address).cloned().unwrap_or_default()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Retrieves the running WSL distributions in the background when the client addresses
+    /// of the attached devices change, as querying WSL is slow.
+    ///
+    /// Distributions might have been restarted since, with different addresses, in which case
+    /// the devices attached to them were detached too.
+    fn retrieve_client_distributions(&self, devices: &[UsbDevice]) {
+        let addresses: HashSet<String> = devices
+            .iter()
+            .filter(|d| d.is_attached())
+            .filter_map(|d| d.client_ip_address.clone())
+            .collect();
+        if *self.client_addresses.borrow() == addresses {
+            return;
+        }
+
+        let retrieve = !addresses.is_empty();
+        *self.client_addresses.borrow_mut() = addresses;
+        *self.client_distributions.borrow_mut() = None;
+        if !retrieve {
+            return;
+        }
+
+        let mut receiver = self.client_distributions_receiver.borrow_mut();
+        if receiver.is_some() {
+            // Retrieve the distributions again once the current retrieval completes
+            self.client_distributions_outdated.set(true);
+            return;
+        }
+
+        *receiver = Some(worker::spawn(
+            self.client_distributions_notice.sender(),
+            wsl::distributions_by_ip,
+        ));
+    }
+
+    /// Shows the WSL distributions the selected device is attached to, once retrieved.
+    fn client_distributions_retrieved(&self) {
+        let distributions = match self.client_distributions_receiver.take() {
+            Some(receiver) => receiver.try_recv().ok().flatten(),
+            None => return,
+        };
+
+        if self.client_distributions_outdated.replace(false) {
+            *self.client_distributions_receiver.borrow_mut() = Some(worker::spawn(
+                self.client_distributions_notice.sender(),
+                wsl::distributions_by_ip,
+            ));
+            return;
+        }
+
+        *self.client_distributions.borrow_mut() = distributions;
+        self.update_device_details();
+    }
+
+    /// Returns a copy of the first selected device, if any.
+    fn selected_device(&self) -> Option<UsbDevice> {
+        let index = self.list_view.selected_item()?;
+        self.connected_devices.borrow().get(index).cloned()
+    }
+
+    /// Returns copies of all the selected devices, in list order.
+    fn selected_devices(&self) -> Vec<UsbDevice> {
+        let devices = self.connected_devices.borrow();
+        self.list_view
+            .selected_items()
+            .into_iter()
+            .filter_map(|index| devices.get(index).cloned())
+            .collect()
+    }
+
+    /// Returns copies of the selected devices matching `filter`.
+    fn selected_devices_where(&self, filter: impl Fn(&UsbDevice) -> bool) -> Vec<UsbDevice> {
+        self.selected_devices()
             .into_iter()
+            .filter(|d| filter(d))
+            .collect()
+    }
+
+    /// Asks the user to confirm that `action` should be performed on `devices`.
+    fn confirm(&self, action: &str, devices: &[UsbDevice]) -> bool {
+        if devices.is_empty() {
+            return false;
+        }
+
+        let names: Vec<String> = {
+            let settings = self.settings.borrow();
+            devices.iter().map(|d| device_name(&settings, d)).collect()
+        };
+        confirm_action(self.window.get(), &self.settings, action, &names)
+    }
+
+    /// Runs a `command` function on each of `devices`, on a background thread.
+    ///
+    /// All devices are attempted even if some fail, and errors are collected into a single message.
+    /// See [`ConnectedTab::run_batch_command`].
+    fn run_command<F>(&self, devices: Vec<UsbDevice>, command: F) -> bool
+    where
+        F: Fn(&UsbDevice) -> Result<(), String> + Send + 'static,
+    {
+        self.run_batch_command(devices, move |devices| {
+            usbipd::run_on_devices(devices, &command)
+        })
+    }
+
+    /// Runs a `command` function on all of `devices` at once, on a background thread.
+    /// No-op if `devices` is empty or if another command is still running.
+    /// Returns whether the command was started.
+    ///
+    /// Once the command completes, the view is reloaded once in [`ConnectedTab::command_done`].
+    fn run_batch_command<F>(&self, devices: Vec<UsbDevice>, command: F) -> bool
+    where
+        F: FnOnce(&[UsbDevice]) -> Result<(), String> + Send + 'static,
+    {
+        if devices.is_empty() {
+            return false;
+        }
+
+        let working_devices: HashSet<String> = devices
+            .iter()
+            .filter_map(|d| d.instance_id.clone())
+            .collect();
+
+        let started = self.background_command.start(
+            &self.window.get(),
+            self.command_notice.sender(),
+            move || command(&devices),
+        );
+        if !started {
+            return false;
+        }
+
+        // Show which devices are being operated on until the list is refreshed
+        *self.working_devices.borrow_mut() = working_devices;
+        self.refresh_list_with_devices();
+        self.list_view.repaint();
+        self.update_device_details();
+        true
+    }
+
+    /// Completes a command started by [`ConnectedTab::run_command`].
+    ///
+    /// The view is reloaded, and an error dialog is shown if the command failed.
+    /// Binds that failed because the device is in use offer to retry with a forced bind instead.
+    fn command_done(&self) {
+        self.working_devices.borrow_mut().clear();
+        if let Some(Err(err)) = self.background_command.finish() {
+            nwg::modal_error_message(self.window.get(), "WSL USB Manager: Command Error", &err);
+        }
+
+        self.refresh();
+        if let Some(busy_notice) = self.busy_notice.get() {
+            busy_notice.notice();
+        }
+
+        let in_use_devices = std::mem::take(&mut *self.in_use_devices.lock().unwrap());
+        if !in_use_devices.is_empty() && self.confirm_force_bind() {
+            self.bind_devices_force(in_use_devices);
+        }
+
+        let attached_elsewhere = std::mem::take(&mut *self.attached_elsewhere.lock().unwrap());
+        if !attached_elsewhere.devices.is_empty()
+            && self.confirm_take_over(&attached_elsewhere.devices)
+        {
+            let devices = attached_elsewhere
+                .devices
+                .into_iter()
+                .map(|(d, _)| d)
+                .collect();
+            self.take_over_devices(devices, attached_elsewhere.distribution);
+        }
+    }
+
+    /// Checks in the background whether the WSL kernel supports usbip, once devices are
+    /// attached and WSL is thus likely running. No-op once known or while checking.
+    fn check_usbip_support(&self, devices: &[UsbDevice]) {
+        if self.usbip_support.get().is_some()
+            || self.usbip_support_receiver.borrow().is_some()
+            || !devices.iter().any(|d| d.is_attached())
+        {
+            return;
+        }
+
+        *self.usbip_support_receiver.borrow_mut() = Some(worker::spawn(
+            self.usbip_support_notice.sender(),
+            wsl::kernel_supports_usbip,
+        ));
+    }
+
+    /// Shows a warning below the device list if the WSL kernel lacks usbip support.
+    fn usbip_support_checked(&self) {
+        let supported = match self.usbip_support_receiver.take() {
+            Some(receiver) => receiver.try_recv().ok().flatten(),
+            None => return,
+        };
+        // Check again later if WSL was not running
+        self.usbip_support.set(supported);
+
+        if supported == Some(false) {
+            let style = Style {
+                size: Size {
+                    width: D::Auto,
+                    height: D::Points(35.0),
+                },
+                margin: PADDING_TOP,
+                ..Default::default()
+            };
+            if self
+                .list_layout
+                .add_child(&self.usbip_warning, style)
+                .is_ok()
+            {
+                self.usbip_warning.set_visible(true);
+            }
+        }
+    }
+
+    fn open_wsl_usb_docs(&self) {
+        open_url(WSL_USB_DOCS_URL);
+    }
+
+    /// Returns whether `device` was found in an inconsistent state in two consecutive lists.
+    fn needs_rebind(&self, device: &UsbDevice) -> bool {
+        device
+            .instance_id
+            .as_ref()
+            .is_some_and(|id| self.inconsistent_devices.borrow().contains(id))
+    }
+
+    /// Looks for devices in an inconsistent sharing state.
+    ///
+    /// States are often inconsistent for a moment while usbipd operates on a device, so
+    /// devices are only flagged once the device list, retrieved again, confirms them.
+    fn check_needs_rebind(&self, devices: &[UsbDevice]) {
+        let suspects: HashSet<String> = devices
+            .iter()
+            .filter(|d| d.needs_rebind())
+            .filter_map(|d| d.instance_id.clone())
+            .collect();
+
+        let mut inconsistent_devices = self.inconsistent_devices.borrow_mut();
+        inconsistent_devices.retain(|id| suspects.contains(id));
+        if suspects.is_subset(&inconsistent_devices)
+            || self.rebind_check_receiver.borrow().is_some()
+        {
+            return;
+        }
+
+        *self.rebind_check_receiver.borrow_mut() = Some(worker::spawn(
+            self.rebind_check_notice.sender(),
+            usbipd::list_devices,
+        ));
+    }
+
+    /// Flags the devices that are still inconsistent in the device list retrieved again.
+    fn rebind_check_done(&self) {
+        let devices = match self.rebind_check_receiver.take() {
+            Some(receiver) => match receiver.try_recv() {
+                Ok(Ok(devices)) => devices,
+                _ => return,
+            },
+            None => return,
+        };
+
+        let confirmed: HashSet<String> = {
+            let all_devices = self.all_devices.borrow();
+            devices
+                .into_iter()
+                .filter(|d| d.needs_rebind())
+                .filter(|d| {
+                    all_devices
+                        .iter()
+                        .any(|a| a.instance_id == d.instance_id && a.needs_rebind())
+                })
+                .filter_map(|d| d.instance_id)
+                .collect()
+        };
+        self.inconsistent_devices.borrow_mut().extend(confirmed);
+        self.update_device_details();
+    }
+
+    /// Keeps the connected devices out of `devices`.
+    fn set_devices(&self, devices: &[UsbDevice]) {
+        self.retrieve_client_distributions(devices);
+        self.record_attach_times(devices);
+        self.check_usbip_support(devices);
+        self.check_needs_rebind(devices);
+        *self.all_devices.borrow_mut() = devices
+            .iter()
             .filter(|d| d.is_connected())
+            .cloned()
+            .collect();
+    }
+
+    /// Records the devices that were attached since the last refresh as attached now.
+    ///
+    /// Devices already attached at the first refresh might have been attached long before,
+    /// so they are only recorded if they were never attached before.
+    fn record_attach_times(&self, devices: &[UsbDevice]) {
+        let attached_keys: HashSet<String> = devices
+            .iter()
+            .filter(|d| d.is_attached())
+            .filter_map(|d| d.stable_key())
             .collect();
+        let previous_keys = self.attached_keys.replace(Some(attached_keys.clone()));
+
+        let mut settings = self.settings.borrow_mut();
+        let mut changed = false;
+        for key in attached_keys {
+            let newly_attached = match &previous_keys {
+                Some(previous_keys) => !previous_keys.contains(&key),
+                None => !settings.last_attached.contains_key(&key),
+            };
+            if newly_attached {
+                settings.set_attached_now(key);
+                changed = true;
+            }
+        }
+
+        if changed {
+            if let Err(err) = settings.save() {
+                logging::error(format_args!("Failed to save attach times: {err}"));
+            }
+        }
     }
 
     /// Inhibits the window close event.
@@ -378,6 +1679,88 @@ impl ConnectedTab {
     }
 }
 
+/// The devices an attach command skipped because another usbip client attached them,
+/// see [`ConnectedTab::attach_devices`].
+#[derive(Default)]
+struct AttachedElsewhere {
+    /// The devices as currently attached, each with whether its client is known not to be WSL
+    devices: Vec<(UsbDevice, bool)>,
+    /// The distribution the devices were to be attached to, or `None` for the default one
+    distribution: Option<String>,
+}
+
+/// Splits `devices` into the devices to attach and those another usbip client attached since
+/// the last refresh, according to a freshly retrieved device list.
+///
+/// The latter are returned as currently attached, each with whether its client is known not
+/// to be WSL. That is unknown if the addresses of the WSL distributions cannot be retrieved,
+/// or if usbipd does not report the address of the client. Devices that were attached to WSL
+/// in the meantime are left out, as there is nothing left to do.
+fn split_attached_elsewhere(devices: &[UsbDevice]) -> (Vec<UsbDevice>, Vec<(UsbDevice, bool)>) {
+    let current_devices = match usbipd::list_devices() {
+        Ok(current_devices) => current_devices,
+        // Attaching reports the error, if any
+        Err(_) => return (devices.to_vec(), Vec::new()),
+    };
+    let find_current = |device: &UsbDevice| {
+        current_devices.iter().find(|current| {
+            current.is_attached()
+                && current.bus_id == device.bus_id
+                && current.instance_id == device.instance_id
+        })
+    };
+    if !devices.iter().any(|d| find_current(d).is_some()) {
+        return (devices.to_vec(), Vec::new());
+    }
+
+    // Only retrieved when needed, as it runs WSL
+    let distributions = wsl::distributions_by_ip();
+    let mut to_attach = Vec::new();
+    let mut attached_elsewhere = Vec::new();
+    for device in devices {
+        let current = match find_current(device) {
+            Some(current) => current,
+            None => {
+                to_attach.push(device.clone());
+                continue;
+            }
+        };
+
+        let client_ip = current
+            .client_ip_address
+            .as_deref()
+            .filter(|ip| ip.parse::<IpAddr>().is_ok());
+        match (client_ip, &distributions) {
+            (Some(ip), Some(distributions)) if distributions.contains_key(ip) => {}
+            (Some(_), Some(_)) => attached_elsewhere.push((current.clone(), true)),
+            _ => attached_elsewhere.push((current.clone(), false)),
+        }
+    }
+
+    (to_attach, attached_elsewhere)
+}
+
+/// How setting up auto attach in the background ended, see [`ConnectedTab::auto_attach_device`].
+enum AutoAttachSetup {
+    /// WSL was checked, and the auto-attach process was started or failed to start
+    Started(Result<PendingProfile, String>),
+    /// Checking WSL failed with the given error, the process was not started
+    ProbeFailed(String),
+}
+
+/// Starts the auto-attach process of `profile` for `device` and waits until it is running.
+/// Returns `None` if `cancelled` is set in the meantime.
+fn start_auto_attach(
+    profile: AutoAttachProfile,
+    device: &UsbDevice,
+    cancelled: &AtomicBool,
+) -> Option<Result<PendingProfile, String>> {
+    match PendingProfile::start(profile, device) {
+        Ok(pending) => pending.wait_started(cancelled),
+        Err(err) => Some(Err(err)),
+    }
+}
+
 impl GuiTab for ConnectedTab {
     fn init(&self, window: &nwg::Window) {
         self.window.replace(window.handle);
@@ -392,12 +1775,96 @@ impl GuiTab for ConnectedTab {
 
         self.shield_bitmap.set(shield_bitmap);
 
+        self.buttons_tooltip.register(
+            &self.bind_unbind_button,
+            "Binding and unbinding devices requires administrator privileges",
+        );
+        // The text is updated along with the selected device
+        self.buttons_tooltip
+            .register(&self.attach_detach_button, "Attach the device to WSL");
+        self.buttons_tooltip.register(
+            &self.copy_remote_attach_button,
+            "Copy the command a usbip client on the network runs to attach the device",
+        );
+
+        // Clicking elsewhere completes renaming in place, like in Explorer.
+        // Focus changes are window messages, which have no derived event
+        let sender = self.inline_rename_notice.sender();
+        let handler = nwg::bind_raw_event_handler(
+            &self.inline_rename_input.handle,
+            INLINE_RENAME_HANDLER_ID,
+            move |_hwnd, msg, _wparam, _lparam| {
+                if msg == WM_KILLFOCUS {
+                    sender.notice();
+                }
+                None
+            },
+        );
+        *self.inline_rename_handler.borrow_mut() = handler.ok();
+
+        // Pressing Enter on the selected devices attaches or detaches them. The key is
+        // normally taken by the dialog navigation, so the list view has to ask for it
+        let sender = self.list_enter_notice.sender();
+        let handler = nwg::bind_raw_event_handler(
+            &self.list_view.handle,
+            LIST_ENTER_HANDLER_ID,
+            move |_hwnd, msg, wparam, _lparam| match msg {
+                WM_GETDLGCODE if wparam == VK_RETURN as usize => Some(DLGC_WANTALLKEYS as isize),
+                WM_KEYDOWN if wparam == VK_RETURN as usize => {
+                    sender.notice();
+                    Some(0)
+                }
+                _ => None,
+            },
+        );
+        *self.list_enter_handler.borrow_mut() = handler.ok();
+
+        // Right-clicking the header shows the column chooser. The header passes the click
+        // on to the list view, which has no derived event for it
+        let header = self.list_view.header();
+        let sender = self.header_menu_notice.sender();
+        let handler = nwg::bind_raw_event_handler(
+            &self.list_view.handle,
+            HEADER_MENU_HANDLER_ID,
+            move |_hwnd, msg, wparam, _lparam| {
+                if msg == WM_CONTEXTMENU && header != 0 && wparam as isize == header {
+                    sender.notice();
+                    return Some(0);
+                }
+                None
+            },
+        );
+        *self.header_menu_handler.borrow_mut() = handler.ok();
+
+        // Tab from the list to the actions first, in the order they are shown,
+        // and only then to the details of the device
+        set_tab_order(&[
+            &self.buttons_frame.handle,
+            &self.distribution_combo.handle,
+            &self.copy_remote_attach_button.handle,
+            &self.device_info_frame.handle,
+        ]);
+        set_tab_order(&[
+            &self.auto_attach_button.handle,
+            &self.bind_unbind_button.handle,
+            &self.attach_detach_button.handle,
+        ]);
+
+        self.device_info.init();
+        self.init_distributions();
         self.init_list();
         self.refresh();
     }
 
     fn refresh(&self) {
-        self.refresh_list();
+        if let Some(refresh_notice) = self.refresh_all_notice.get() {
+            refresh_notice.notice();
+        }
+    }
+
+    fn refresh_with_devices(&self, devices: &[UsbDevice]) {
+        self.set_devices(devices);
+        self.refresh_list_with_devices();
         self.update_device_details();
     }
 }
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+
+use native_windows_derive::NwgUi;
+use native_windows_gui as nwg;
+use nwg::NativeUi;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::EnableWindow;
+use windows_sys::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+
+use super::helpers::DEFAULT_DISTRIBUTION;
+use super::nwg_ext::WindowEx;
+use crate::settings::AttachTarget;
+use crate::wsl;
+
+/// A small modal dialog asking which WSL distribution to attach devices to.
+#[derive(Default, NwgUi)]
+pub struct DistributionDialog {
+    parent: nwg::ControlHandle,
+    /// The chosen distribution, `Some(None)` for the default one, set when the dialog is confirmed
+    result: RefCell<Option<Option<String>>>,
+
+    // Shown once centered over the parent window
+    #[nwg_control(parent: Some(data.parent), size: (340, 125),
+        title: "WSL USB Manager: Attach Device", flags: "WINDOW",
+    )]
+    #[nwg_events(OnWindowClose: [DistributionDialog::cancel])]
+    window: nwg::Window,
+
+    #[nwg_control(text: "Attach to:", position: (10, 10), size: (320, 20))]
+    label: nwg::Label,
+
+    #[nwg_control(position: (10, 35), size: (320, 23), focus: true)]
+    distribution_combo: nwg::ComboBox<String>,
+
+    #[nwg_control(text: "OK", position: (154, 88), size: (85, 27))]
+    #[nwg_events(OnButtonClick: [DistributionDialog::ok])]
+    ok_button: nwg::Button,
+
+    #[nwg_control(text: "Cancel", position: (245, 88), size: (85, 27))]
+    #[nwg_events(OnButtonClick: [DistributionDialog::cancel])]
+    cancel_button: nwg::Button,
+}
+
+impl DistributionDialog {
+    fn ok(&self) {
+        let distribution = match self.distribution_combo.selection() {
+            Some(0) | None => None,
+            Some(_) => self.distribution_combo.selection_string(),
+        };
+        *self.result.borrow_mut() = Some(distribution);
+        self.close();
+    }
+
+    fn cancel(&self) {
+        self.close();
+    }
+
+    /// Hides the dialog and exits its event loop.
+    fn close(&self) {
+        self.window.set_visible(false);
+        nwg::stop_thread_dispatch();
+    }
+}
+
+/// Shows a modal dialog asking which of the installed WSL distributions to attach to.
+///
+/// Returns the chosen distribution, `None` for the default one,
+/// or `None` overall if the dialog was cancelled.
+pub fn ask_distribution(parent: nwg::ControlHandle) -> Option<Option<String>> {
+    let dialog = DistributionDialog {
+        parent,
+        ..Default::default()
+    };
+    let dialog = DistributionDialog::build_ui(dialog).ok()?;
+
+    let mut distributions = vec![DEFAULT_DISTRIBUTION.to_owned()];
+    distributions.extend(wsl::list_distributions());
+    dialog.distribution_combo.set_collection(distributions);
+    dialog.distribution_combo.set_selection(Some(0));
+
+    dialog.window.center_on(parent);
+    dialog.window.set_visible(true);
+    dialog.distribution_combo.set_focus();
+
+    // Disable the parent window while the dialog is open to make it modal
+    let parent_hwnd = parent.hwnd().map_or(0, |hwnd| hwnd as HWND);
+    unsafe { EnableWindow(parent_hwnd, 0) };
+
+    // Runs until the dialog is closed
+    nwg::dispatch_thread_events();
+
+    unsafe {
+        EnableWindow(parent_hwnd, 1);
+        SetForegroundWindow(parent_hwnd);
+    }
+
+    dialog.result.take()
+}
+
+/// Returns the distribution `target` attaches to, `None` for the default one,
+/// asking the user if it is [`AttachTarget::Ask`].
+///
+/// Returns `None` overall if the user cancelled.
+pub fn target_distribution(
+    parent: nwg::ControlHandle,
+    target: &AttachTarget,
+) -> Option<Option<String>> {
+    match target {
+        AttachTarget::Default => Some(None),
+        AttachTarget::Distribution(distribution) => Some(Some(distribution.clone())),
+        AttachTarget::Ask => ask_distribution(parent),
+    }
+}
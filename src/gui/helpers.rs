@@ -0,0 +1,142 @@
+//! Small helpers shared by the GUI.
+
+use std::time::{Duration, SystemTime};
+
+use crate::auto_attach::{AutoAttachProfile, AutoBindRule};
+use crate::settings::Settings;
+use crate::usbipd::{UsbDevice, UsbipState};
+
+/// The maximum length of a device label in menus, in characters.
+pub const MENU_LABEL_MAX_LEN: usize = 48;
+
+/// The entry that targets the default WSL distribution, in distribution selectors.
+pub const DEFAULT_DISTRIBUTION: &str = "Default distribution";
+
+/// Shortens `s` to at most `max_len` characters by replacing its middle with an ellipsis.
+///
+/// Both the start and the end are kept, as device descriptions often differ only in the end.
+/// Lengths are counted in `char`s rather than bytes, so that the string is never split
+/// inside a multi-byte character.
+pub fn ellipsize_middle(s: &str, max_len: usize) -> String {
+    let len = s.chars().count();
+    if len <= max_len {
+        return s.to_owned();
+    }
+    if max_len == 0 {
+        return String::new();
+    }
+
+    // One character is taken by the ellipsis, give any leftover one to the start
+    let end_len = (max_len - 1) / 2;
+    let start_len = max_len - 1 - end_len;
+
+    // Byte offsets of the character boundaries to cut at
+    let start_end = s.char_indices().nth(start_len).map_or(s.len(), |(i, _)| i);
+    let end_start = s
+        .char_indices()
+        .nth(len - end_len)
+        .map_or(s.len(), |(i, _)| i);
+
+    format!("{}…{}", &s[..start_end], &s[end_start..])
+}
+
+/// Describes how long ago `unix_secs`, in seconds since the Unix epoch, was,
+/// e.g. "5 minutes ago".
+pub fn time_ago(unix_secs: u64) -> String {
+    let elapsed = (SystemTime::UNIX_EPOCH + Duration::from_secs(unix_secs))
+        .elapsed()
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    let (count, unit) = match elapsed {
+        0..=59 => return "Just now".to_owned(),
+        60..=3599 => (elapsed / 60, "minute"),
+        3600..=86399 => (elapsed / 3600, "hour"),
+        _ => (elapsed / 86400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// The marker prepended to the state of forced-bound devices in lists.
+const FORCED_STATE_MARKER: &str = "\u{26a0}";
+
+/// Returns the label shown for `state` in lists.
+///
+/// Forced bindings are marked with a warning sign, so that they stand out in a dense list.
+/// The textual state is kept, so that the marker is not the only way to tell them apart.
+pub fn state_label(state: &UsbipState) -> String {
+    if state.is_forced() {
+        format!("{FORCED_STATE_MARKER} {state}")
+    } else {
+        state.to_string()
+    }
+}
+
+/// Returns the name shown for `device`: its nickname if one is set,
+/// otherwise its description.
+pub fn device_name(settings: &Settings, device: &UsbDevice) -> String {
+    settings
+        .nickname(device.stable_key().as_deref())
+        .or(device.description.as_deref())
+        .unwrap_or("Unknown device")
+        .to_owned()
+}
+
+/// Returns the name shown for the device of `profile`: its nickname if one is set,
+/// otherwise its description.
+pub fn profile_name(settings: &Settings, profile: &AutoAttachProfile) -> String {
+    settings
+        .nickname(profile.stable_key().as_deref())
+        .or(profile.description.as_deref())
+        .unwrap_or("Unknown device")
+        .to_owned()
+}
+
+/// Returns the name shown for the device of the auto-bind `rule`: its nickname if one is set,
+/// otherwise its description.
+pub fn bind_rule_name(settings: &Settings, rule: &AutoBindRule) -> String {
+    settings
+        .nickname(Some(&rule.key))
+        .or(rule.description.as_deref())
+        .unwrap_or("Unknown device")
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ellipsize_middle_keeps_strings_within_the_limit() {
+        assert_eq!(ellipsize_middle("", 0), "");
+        assert_eq!(ellipsize_middle("abc", 3), "abc");
+        assert_eq!(ellipsize_middle("abc", 10), "abc");
+        assert_eq!(ellipsize_middle("键盘鼠标", 4), "键盘鼠标");
+    }
+
+    #[test]
+    fn ellipsize_middle_shortens_around_the_middle() {
+        assert_eq!(ellipsize_middle("abcd", 3), "a…d");
+        assert_eq!(ellipsize_middle("abcdefghij", 6), "abc…ij");
+        assert_eq!(ellipsize_middle("abcdefghij", 7), "abc…hij");
+    }
+
+    #[test]
+    fn ellipsize_middle_handles_tiny_limits() {
+        assert_eq!(ellipsize_middle("abcdef", 0), "");
+        assert_eq!(ellipsize_middle("abcdef", 1), "…");
+        assert_eq!(ellipsize_middle("abcdef", 2), "a…");
+        assert_eq!(ellipsize_middle("abcdef", 3), "a…f");
+    }
+
+    #[test]
+    fn ellipsize_middle_cuts_multi_byte_strings_on_char_boundaries() {
+        assert_eq!(ellipsize_middle("键盘鼠标接收器", 5), "键盘…收器");
+        assert_eq!(ellipsize_middle("🔌🎹🖱⌨🔋", 3), "🔌…🔋");
+        assert_eq!(ellipsize_middle("Clé USB «Ünïcödé»", 8), "Clé …dé»");
+
+        let result = ellipsize_middle("🔌🔌🔌🔌🔌🔌🔌🔌", 5);
+        assert_eq!(result, "🔌🔌…🔌🔌");
+        assert_eq!(result.chars().count(), 5);
+    }
+}
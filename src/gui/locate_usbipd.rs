@@ -0,0 +1,110 @@
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use std::path::PathBuf;
+
+use windows_sys::Win32::UI::Controls::Dialogs::{
+    GetOpenFileNameW, OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR, OFN_PATHMUSTEXIST, OPENFILENAMEW,
+};
+use windows_sys::Win32::UI::Controls::{
+    TaskDialogIndirect, TASKDIALOGCONFIG, TASKDIALOG_BUTTON, TDCBF_CLOSE_BUTTON,
+    TDF_ALLOW_DIALOG_CANCELLATION, TDF_USE_COMMAND_LINKS, TD_ERROR_ICON,
+};
+
+use crate::win_utils::{open_url, to_wide};
+
+/// The page the latest version of USBIPD can be downloaded from.
+const USBIPD_RELEASES_URL: &str = "https://github.com/dorssel/usbipd-win/releases";
+
+const BUTTON_LOCATE: i32 = 100;
+const BUTTON_DOWNLOAD: i32 = 101;
+
+/// Shows an error telling the user that USBIPD was not found, offering to locate
+/// `usbipd.exe` manually or to download USBIPD.
+///
+/// Returns the path chosen by the user, or `None` if the dialog was closed.
+pub fn locate_usbipd() -> Option<PathBuf> {
+    let title = to_wide("WSL USB Manager: USBIPD Not Found");
+    let instruction = to_wide("USBIPD was not found");
+    let content = to_wide(
+        "Please make sure that USBIPD is installed and available in the system PATH, \
+        or locate the usbipd.exe executable manually.",
+    );
+    let locate_text = to_wide("Locate usbipd.exe...\nChoose where USBIPD is installed");
+    let download_text = to_wide("Download USBIPD\nOpen the usbipd-win releases page");
+
+    let buttons = [
+        TASKDIALOG_BUTTON {
+            nButtonID: BUTTON_LOCATE,
+            pszButtonText: locate_text.as_ptr(),
+        },
+        TASKDIALOG_BUTTON {
+            nButtonID: BUTTON_DOWNLOAD,
+            pszButtonText: download_text.as_ptr(),
+        },
+    ];
+
+    let mut config: TASKDIALOGCONFIG = unsafe { std::mem::zeroed() };
+    config.cbSize = std::mem::size_of::<TASKDIALOGCONFIG>() as u32;
+    config.dwFlags = TDF_ALLOW_DIALOG_CANCELLATION | TDF_USE_COMMAND_LINKS;
+    config.dwCommonButtons = TDCBF_CLOSE_BUTTON;
+    config.pszWindowTitle = title.as_ptr();
+    config.Anonymous1.pszMainIcon = TD_ERROR_ICON;
+    config.pszMainInstruction = instruction.as_ptr();
+    config.pszContent = content.as_ptr();
+    config.cButtons = buttons.len() as u32;
+    config.pButtons = buttons.as_ptr();
+    config.nDefaultButton = BUTTON_LOCATE;
+
+    loop {
+        let mut button = 0;
+        let result = unsafe {
+            TaskDialogIndirect(
+                &config as *const _,
+                &mut button as *mut _,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if result < 0 {
+            return None;
+        }
+
+        match button {
+            BUTTON_LOCATE => {
+                // Ask again if the file picker is cancelled
+                if let Some(path) = pick_usbipd_exe() {
+                    return Some(path);
+                }
+            }
+            // Keep the dialog open, so that USBIPD can be located after installing it
+            BUTTON_DOWNLOAD => open_url(USBIPD_RELEASES_URL),
+            _ => return None,
+        }
+    }
+}
+
+/// Shows a file picker for the `usbipd.exe` executable.
+fn pick_usbipd_exe() -> Option<PathBuf> {
+    let title = to_wide("Locate usbipd.exe");
+    // Filter pairs are separated by nulls, and the list is terminated by a double null
+    let filter: Vec<u16> = "usbipd.exe\0usbipd.exe\0Executables (*.exe)\0*.exe\0\0"
+        .encode_utf16()
+        .collect();
+    let mut file = [0u16; 1024];
+
+    let mut open_file_name: OPENFILENAMEW = unsafe { std::mem::zeroed() };
+    open_file_name.lStructSize = std::mem::size_of::<OPENFILENAMEW>() as u32;
+    open_file_name.lpstrFilter = filter.as_ptr();
+    open_file_name.nFilterIndex = 1;
+    open_file_name.lpstrFile = file.as_mut_ptr();
+    open_file_name.nMaxFile = file.len() as u32;
+    open_file_name.lpstrTitle = title.as_ptr();
+    open_file_name.Flags = OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR;
+
+    if unsafe { GetOpenFileNameW(&mut open_file_name as *mut _) } == 0 {
+        return None;
+    }
+
+    let len = file.iter().position(|&c| c == 0).unwrap_or(file.len());
+    Some(PathBuf::from(OsString::from_wide(&file[..len])))
+}
@@ -1,21 +1,43 @@
+mod about;
 mod auto_attach_tab;
+mod confirm;
 mod connected_tab;
+mod distribution_dialog;
+mod helpers;
+mod locate_usbipd;
 mod nwg_ext;
 mod persisted_tab;
+mod progress_dialog;
+mod rename_dialog;
 mod usbipd_gui;
+mod worker;
 
 use std::{cell::RefCell, rc::Rc};
 
 use native_windows_gui as nwg;
 use nwg::NativeUi;
+use windows_sys::Win32::UI::Controls::{
+    TaskDialogIndirect, TASKDIALOGCONFIG, TDCBF_OK_BUTTON, TDF_ALLOW_DIALOG_CANCELLATION,
+    TD_WARNING_ICON,
+};
 
 use crate::auto_attach::AutoAttacher;
+use crate::settings::Settings;
+use crate::usbipd;
+use crate::win_utils::to_wide;
 use usbipd_gui::UsbipdGui;
 
+pub use locate_usbipd::locate_usbipd;
+
 /// Starts the GUI and runs the event loop.
+/// If `start_minimized` is `true`, the main window is hidden and only the tray icon is shown.
 ///
 /// This function will not return until the app is closed.
-pub fn start(auto_attacher: &Rc<RefCell<AutoAttacher>>) -> Result<(), nwg::NwgError> {
+pub fn start(
+    auto_attacher: &Rc<RefCell<AutoAttacher>>,
+    settings: &Rc<RefCell<Settings>>,
+    start_minimized: bool,
+) -> Result<(), nwg::NwgError> {
     nwg::init()?;
 
     let mut font = nwg::Font::default();
@@ -27,7 +49,7 @@ pub fn start(auto_attacher: &Rc<RefCell<AutoAttacher>>) -> Result<(), nwg::NwgEr
 
     nwg::Font::set_global_default(Some(font));
 
-    let _gui = UsbipdGui::build_ui(UsbipdGui::new(auto_attacher))?;
+    let _gui = UsbipdGui::build_ui(UsbipdGui::new(auto_attacher, settings, start_minimized))?;
 
     // Run the event loop
     nwg::dispatch_thread_events();
@@ -36,7 +58,8 @@ pub fn start(auto_attacher: &Rc<RefCell<AutoAttacher>>) -> Result<(), nwg::NwgEr
 
 /// Shows a warning message telling the user that another instance is already running.
 ///
-/// This function is called when the app fails to obtain the instance lock because one is already held.
+/// This function is called when the app fails to obtain the instance lock because one is already held,
+/// and the running instance did not respond to the request to show its window.
 pub fn show_multiple_instance_warning() {
     nwg::message(&nwg::MessageParams {
         title: "WSL USB Manager: Multiple Instances Detected",
@@ -49,32 +72,67 @@ pub fn show_multiple_instance_warning() {
     });
 }
 
-/// Shows an error message telling the user that USBIPD was not found.
+/// Shows a warning message telling the user that an untested version of USBIPD was found.
 ///
-/// This function is called when the app fails to find the USBIPD executable during startup.
-pub fn show_usbipd_not_found_error() {
-    nwg::message(&nwg::MessageParams {
-        title: "WSL USB Manager: USBIPD Not Found",
-        content: concat!(
-            "USBIPD was not found, please make sure that it is installed and available in the system PATH."
-        ),
-        buttons: nwg::MessageButtons::Ok,
-        icons: nwg::MessageIcons::Error,
-    });
+/// This function is called when the app finds a version of USBIPD newer than the latest tested one.
+/// The app keeps running after the warning is dismissed.
+///
+/// Returns `true` if the user asked not to be warned again about this major version.
+pub fn show_usbipd_untested_version_warning(version: &usbipd::Version) -> bool {
+    let title = to_wide("WSL USB Manager: Untested USBIPD Version");
+    let instruction = to_wide(&format!(
+        "USBIPD version {version} is newer than the versions this app was tested with."
+    ));
+    let content = to_wide(concat!(
+        "Some features may not work correctly.\n\n",
+        "Please check for an updated version of WSL USB Manager."
+    ));
+    let verification = to_wide(&format!(
+        "Don't warn me again about USBIPD {}.x",
+        version.major
+    ));
+
+    let mut config: TASKDIALOGCONFIG = unsafe { std::mem::zeroed() };
+    config.cbSize = std::mem::size_of::<TASKDIALOGCONFIG>() as u32;
+    config.dwFlags = TDF_ALLOW_DIALOG_CANCELLATION;
+    config.dwCommonButtons = TDCBF_OK_BUTTON;
+    config.pszWindowTitle = title.as_ptr();
+    config.Anonymous1.pszMainIcon = TD_WARNING_ICON;
+    config.pszMainInstruction = instruction.as_ptr();
+    config.pszContent = content.as_ptr();
+    config.pszVerificationText = verification.as_ptr();
+
+    let mut dont_warn_again = 0;
+    let result = unsafe {
+        TaskDialogIndirect(
+            &config as *const _,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut dont_warn_again as *mut _,
+        )
+    };
+
+    result >= 0 && dont_warn_again != 0
 }
 
-/// Shows a warning message telling the user that an untested version of USBIPD was found.
+/// Shows an error message telling the user that the installed version of USBIPD is not supported.
 ///
-/// This function is called when the app finds a version of USBIPD lower than 4.
-pub fn show_usbipd_untested_version_warning() {
-    nwg::message(&nwg::MessageParams {
-        title: "WSL USB Manager: Untested USBIPD Version",
-        content: concat!(
-            "An untested version of USBIPD was found, this app may not work correctly. ",
-            "Please install USBIPD version 4 or newer."
+/// This function is called when the app finds a version of USBIPD older than the minimum supported one.
+pub fn show_usbipd_unsupported_version_error(version: &usbipd::Version) {
+    let content = format!(
+        concat!(
+            "USBIPD version {} is not supported by this app.\n\n",
+            "Please install USBIPD version {} or newer."
         ),
+        version,
+        usbipd::MIN_SUPPORTED
+    );
+
+    nwg::message(&nwg::MessageParams {
+        title: "WSL USB Manager: Unsupported USBIPD Version",
+        content: &content,
         buttons: nwg::MessageButtons::Ok,
-        icons: nwg::MessageIcons::Warning,
+        icons: nwg::MessageIcons::Error,
     });
 }
 
@@ -1,15 +1,64 @@
+use std::ptr::null;
+
 use native_windows_gui as nwg;
 
-use windows_sys::Win32::Foundation::HANDLE;
-use windows_sys::Win32::Graphics::Gdi::DeleteObject;
+use windows_sys::Win32::Foundation::{BOOL, HANDLE, HWND, LPARAM, RECT};
+use windows_sys::Win32::Graphics::Dwm::{DwmSetWindowAttribute, DWMWA_USE_IMMERSIVE_DARK_MODE};
+use windows_sys::Win32::Graphics::Gdi::{
+    DeleteObject, GetMonitorInfoW, GetSysColor, InvalidateRect, MonitorFromRect, UpdateWindow,
+    COLOR_WINDOW, COLOR_WINDOWTEXT, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+};
+use windows_sys::Win32::UI::Controls::{
+    SetWindowTheme, LVIR_LABEL, LVM_ENSUREVISIBLE, LVM_GETHEADER, LVM_GETSUBITEMRECT,
+    LVM_GETTOPINDEX, LVM_SETBKCOLOR, LVM_SETTEXTBKCOLOR, LVM_SETTEXTCOLOR,
+};
+use windows_sys::Win32::UI::HiDpi::GetDpiForWindow;
 use windows_sys::Win32::UI::Shell::{
     SHGetStockIconInfo, SHGSI_ICON, SHGSI_SMALLICON, SHSTOCKICONID, SHSTOCKICONINFO,
 };
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    CopyImage, DestroyIcon, GetIconInfoExW, SetMenuItemInfoW, HMENU, ICONINFOEXW, IMAGE_BITMAP,
-    LR_CREATEDIBSECTION, MENUITEMINFOW, MF_BYCOMMAND, MIIM_BITMAP,
+    CopyImage, DestroyIcon, EnumChildWindows, GetClassNameW, GetIconInfoExW, GetWindowLongW,
+    GetWindowRect, IsIconic, SendMessageW, SetForegroundWindow, SetMenuItemInfoW, SetWindowLongW,
+    SetWindowPos, ShowWindow, GWL_STYLE, HMENU, HWND_NOTOPMOST, HWND_TOP, HWND_TOPMOST,
+    ICONINFOEXW, IMAGE_BITMAP, LR_CREATEDIBSECTION, MENUITEMINFOW, MF_BYCOMMAND, MIIM_BITMAP,
+    SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, SWP_NOZORDER, SWP_SHOWWINDOW, SW_RESTORE, WS_TABSTOP,
 };
 
+use crate::win_utils::to_wide;
+
+/// The background color of list views in dark mode, as a `COLORREF`.
+const DARK_BACKGROUND_COLOR: u32 = 0x0020_2020;
+
+/// The text color of list views in dark mode, as a `COLORREF`.
+const DARK_TEXT_COLOR: u32 = 0x00F0_F0F0;
+
+/// Makes the Tab key move focus through the sibling `controls` in the given order,
+/// regardless of the order they were created in.
+///
+/// Frames are entered when they receive focus, so ordering a frame orders all its children.
+pub fn set_tab_order(controls: &[&nwg::ControlHandle]) {
+    let mut previous = HWND_TOP;
+    for control in controls {
+        let hwnd = match control.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => continue,
+        };
+        // The tab order follows the z-order of the controls
+        unsafe {
+            SetWindowPos(
+                hwnd,
+                previous,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+            )
+        };
+        previous = hwnd;
+    }
+}
+
 /// Extends [`nwg::Bitmap`] with additional functionality.
 pub trait BitmapEx {
     fn from_system_icon(icon: SHSTOCKICONID) -> nwg::Bitmap;
@@ -127,3 +176,339 @@ impl MenuItemEx for nwg::MenuItem {
         }
     }
 }
+
+/// Extends [`nwg::ListView`] with additional functionality.
+pub trait ListViewEx {
+    fn repaint(&self);
+    fn top_index(&self) -> usize;
+    fn set_top_index(&self, index: usize);
+    fn overlay_cell(&self, row: usize, column: usize, control: &nwg::ControlHandle) -> bool;
+    fn header(&self) -> HWND;
+}
+
+impl ListViewEx for nwg::ListView {
+    /// Repaints the list view immediately, without waiting for the event loop.
+    ///
+    /// Useful to reflect changes before running a blocking operation on the GUI thread.
+    fn repaint(&self) {
+        if let Some(hwnd) = self.handle.hwnd() {
+            unsafe { UpdateWindow(hwnd as HWND) };
+        }
+    }
+
+    /// Returns the handle of the column header control, or 0 if there is none.
+    fn header(&self) -> HWND {
+        match self.handle.hwnd() {
+            Some(hwnd) => unsafe { SendMessageW(hwnd as HWND, LVM_GETHEADER, 0, 0) },
+            None => 0,
+        }
+    }
+
+    /// Returns the index of the topmost visible item, i.e. the scroll position of the list.
+    fn top_index(&self) -> usize {
+        match self.handle.hwnd() {
+            Some(hwnd) => unsafe { SendMessageW(hwnd as HWND, LVM_GETTOPINDEX, 0, 0) as usize },
+            None => 0,
+        }
+    }
+
+    /// Scrolls the list so that the item at `index` is the topmost visible one,
+    /// or as close to the top as possible near the end of the list.
+    fn set_top_index(&self, index: usize) {
+        let hwnd = match self.handle.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => return,
+        };
+        let last = match self.len().checked_sub(1) {
+            Some(last) => last,
+            None => return,
+        };
+
+        // Scrolling up to an item makes it the topmost one, so scroll to the end first
+        unsafe {
+            SendMessageW(hwnd, LVM_ENSUREVISIBLE, last, 0);
+            SendMessageW(hwnd, LVM_ENSUREVISIBLE, index.min(last), 0);
+        }
+    }
+
+    /// Places `control`, a child of the list view, over the cell at `row` and `column`
+    /// and shows it, e.g. to edit the cell in place. Returns `false` if there is no such cell.
+    fn overlay_cell(&self, row: usize, column: usize, control: &nwg::ControlHandle) -> bool {
+        let (hwnd, control_hwnd) = match (self.handle.hwnd(), control.hwnd()) {
+            (Some(hwnd), Some(control_hwnd)) => (hwnd as HWND, control_hwnd as HWND),
+            _ => return false,
+        };
+
+        // The subitem and the part of the cell are passed in the rectangle
+        let mut rect = RECT {
+            left: LVIR_LABEL as i32,
+            top: column as i32,
+            right: 0,
+            bottom: 0,
+        };
+        if unsafe { SendMessageW(hwnd, LVM_GETSUBITEMRECT, row, &mut rect as *mut _ as LPARAM) }
+            == 0
+        {
+            return false;
+        }
+
+        unsafe {
+            SetWindowPos(
+                control_hwnd,
+                0,
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                SWP_SHOWWINDOW,
+            )
+        };
+        true
+    }
+}
+
+/// Extends [`nwg::RichLabel`] with additional functionality.
+pub trait RichLabelEx {
+    fn set_tab_stop(&self, tab_stop: bool);
+}
+
+impl RichLabelEx for nwg::RichLabel {
+    /// Adds the rich label to the tab order, or removes it from it.
+    ///
+    /// Rich labels are read-only, but focusing them lets keyboard and screen reader
+    /// users reach their text. Screen readers name a focused rich label after the
+    /// label created right before it, e.g. "Bus ID:".
+    fn set_tab_stop(&self, tab_stop: bool) {
+        let hwnd = match self.handle.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => return,
+        };
+
+        unsafe {
+            let style = GetWindowLongW(hwnd, GWL_STYLE) as u32;
+            let style = if tab_stop {
+                style | WS_TABSTOP
+            } else {
+                style & !WS_TABSTOP
+            };
+            SetWindowLongW(hwnd, GWL_STYLE, style as i32);
+        }
+    }
+}
+
+/// Extends [`nwg::Window`] with additional functionality.
+pub trait WindowEx {
+    fn rect(&self) -> Option<[i32; 4]>;
+    fn set_rect_clamped(&self, rect: [i32; 4], min_size: (i32, i32));
+    fn center_on(&self, parent: nwg::ControlHandle);
+    fn set_always_on_top(&self, always_on_top: bool);
+    fn bring_to_front(&self);
+    fn set_dark_mode(&self, dark: bool);
+}
+
+impl WindowEx for nwg::Window {
+    /// Returns the window rectangle as `[left, top, right, bottom]` in physical pixels.
+    /// Returns `None` if the window is minimized.
+    fn rect(&self) -> Option<[i32; 4]> {
+        let hwnd = self.handle.hwnd()? as HWND;
+        if unsafe { IsIconic(hwnd) } != 0 {
+            return None;
+        }
+
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        if unsafe { GetWindowRect(hwnd, &mut rect as *mut _) } == 0 {
+            return None;
+        }
+
+        Some([rect.left, rect.top, rect.right, rect.bottom])
+    }
+
+    /// Moves and resizes the window to `rect`, given as `[left, top, right, bottom]`
+    /// in physical pixels.
+    ///
+    /// The window is clamped to the work area of the nearest monitor, so that it is never
+    /// placed offscreen, and it is made at least `min_size` large, in logical pixels.
+    fn set_rect_clamped(&self, rect: [i32; 4], min_size: (i32, i32)) {
+        let hwnd = match self.handle.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => return,
+        };
+
+        let [left, top, right, bottom] = rect;
+        let rect = RECT {
+            left,
+            top,
+            right,
+            bottom,
+        };
+
+        let mut monitor_info: MONITORINFO = unsafe { std::mem::zeroed() };
+        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+        let monitor = unsafe { MonitorFromRect(&rect as *const _, MONITOR_DEFAULTTONEAREST) };
+        if unsafe { GetMonitorInfoW(monitor, &mut monitor_info as *mut _) } == 0 {
+            return;
+        }
+        let work = monitor_info.rcWork;
+
+        // Scale the minimum size to physical pixels
+        let dpi = unsafe { GetDpiForWindow(hwnd) }.max(96) as i32;
+        let min_width = min_size.0 * dpi / 96;
+        let min_height = min_size.1 * dpi / 96;
+
+        let width = (right - left).max(min_width).min(work.right - work.left);
+        let height = (bottom - top).max(min_height).min(work.bottom - work.top);
+        let x = left.clamp(work.left, work.right - width);
+        let y = top.clamp(work.top, work.bottom - height);
+
+        unsafe { SetWindowPos(hwnd, 0, x, y, width, height, SWP_NOZORDER | SWP_NOACTIVATE) };
+    }
+
+    /// Centers the window over the `parent` window, keeping its size.
+    ///
+    /// Unlike the `center` flag of [`nwg::Window`], which centers on the primary monitor,
+    /// this keeps dialogs on the monitor the parent is on. The window is clamped to the
+    /// work area of that monitor, so that it is not clipped when the parent is partially offscreen.
+    fn center_on(&self, parent: nwg::ControlHandle) {
+        let (hwnd, parent_hwnd) = match (self.handle.hwnd(), parent.hwnd()) {
+            (Some(hwnd), Some(parent_hwnd)) => (hwnd as HWND, parent_hwnd as HWND),
+            _ => return,
+        };
+
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        let mut parent_rect = rect;
+        unsafe {
+            if GetWindowRect(hwnd, &mut rect as *mut _) == 0
+                || GetWindowRect(parent_hwnd, &mut parent_rect as *mut _) == 0
+            {
+                return;
+            }
+        }
+
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        let left = parent_rect.left + (parent_rect.right - parent_rect.left - width) / 2;
+        let top = parent_rect.top + (parent_rect.bottom - parent_rect.top - height) / 2;
+
+        let mut monitor_info: MONITORINFO = unsafe { std::mem::zeroed() };
+        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+        let monitor =
+            unsafe { MonitorFromRect(&parent_rect as *const _, MONITOR_DEFAULTTONEAREST) };
+        if unsafe { GetMonitorInfoW(monitor, &mut monitor_info as *mut _) } == 0 {
+            return;
+        }
+        let work = monitor_info.rcWork;
+
+        let x = left.clamp(work.left, (work.right - width).max(work.left));
+        let y = top.clamp(work.top, (work.bottom - height).max(work.top));
+
+        unsafe {
+            SetWindowPos(
+                hwnd,
+                0,
+                x,
+                y,
+                0,
+                0,
+                SWP_NOZORDER | SWP_NOACTIVATE | SWP_NOSIZE,
+            )
+        };
+    }
+
+    /// Keeps the window above all non-topmost windows, or restores its normal z-order.
+    fn set_always_on_top(&self, always_on_top: bool) {
+        let hwnd = match self.handle.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => return,
+        };
+
+        let insert_after = if always_on_top {
+            HWND_TOPMOST
+        } else {
+            HWND_NOTOPMOST
+        };
+        unsafe {
+            SetWindowPos(
+                hwnd,
+                insert_after,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+            )
+        };
+    }
+    /// Restores the window if it is minimized and brings it to the foreground.
+    fn bring_to_front(&self) {
+        let hwnd = match self.handle.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => return,
+        };
+
+        unsafe {
+            if IsIconic(hwnd) != 0 {
+                ShowWindow(hwnd, SW_RESTORE);
+            }
+            SetForegroundWindow(hwnd);
+        }
+    }
+
+    /// Switches the title bar and the list views of the window to dark or light colors.
+    ///
+    /// Other controls keep the system colors, as they have no dark theme.
+    fn set_dark_mode(&self, dark: bool) {
+        let hwnd = match self.handle.hwnd() {
+            Some(hwnd) => hwnd as HWND,
+            None => return,
+        };
+
+        let value = BOOL::from(dark);
+        unsafe {
+            DwmSetWindowAttribute(
+                hwnd,
+                DWMWA_USE_IMMERSIVE_DARK_MODE,
+                &value as *const _ as *const _,
+                std::mem::size_of::<BOOL>() as u32,
+            );
+            EnumChildWindows(hwnd, Some(set_list_view_dark_mode), LPARAM::from(dark));
+        }
+    }
+}
+
+/// Switches `hwnd` to dark colors if `dark` is non-zero, or back to the system colors.
+/// Windows other than list views are left as they are.
+///
+/// Used as the callback of `EnumChildWindows`, which enumerates all descendants.
+unsafe extern "system" fn set_list_view_dark_mode(hwnd: HWND, dark: LPARAM) -> BOOL {
+    let mut class_name = [0u16; 32];
+    let len = GetClassNameW(hwnd, class_name.as_mut_ptr(), class_name.len() as i32);
+    if String::from_utf16_lossy(&class_name[..len.max(0) as usize]) != "SysListView32" {
+        // Continue with the next window
+        return 1;
+    }
+
+    let (background, text) = if dark != 0 {
+        let theme = to_wide("DarkMode_Explorer");
+        SetWindowTheme(hwnd, theme.as_ptr(), null());
+        (DARK_BACKGROUND_COLOR, DARK_TEXT_COLOR)
+    } else {
+        SetWindowTheme(hwnd, null(), null());
+        (GetSysColor(COLOR_WINDOW), GetSysColor(COLOR_WINDOWTEXT))
+    };
+    SendMessageW(hwnd, LVM_SETBKCOLOR, 0, background as LPARAM);
+    SendMessageW(hwnd, LVM_SETTEXTBKCOLOR, 0, background as LPARAM);
+    SendMessageW(hwnd, LVM_SETTEXTCOLOR, 0, text as LPARAM);
+    InvalidateRect(hwnd, null(), 1);
+
+    1
+}
@@ -1,6 +1,9 @@
 mod persisted_info;
 
-use std::cell::{Cell, RefCell};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use native_windows_derive::NwgPartial;
 use native_windows_gui as nwg;
@@ -12,9 +15,12 @@ use windows_sys::Win32::UI::{Controls::LVSCW_AUTOSIZE_USEHEADER, Shell::SIID_SHI
 
 use self::persisted_info::PersistedInfo;
 use crate::gui::{
-    nwg_ext::{BitmapEx, MenuItemEx},
+    confirm::confirm_action,
+    helpers::device_name,
+    nwg_ext::{BitmapEx, ListViewEx, MenuItemEx},
     usbipd_gui::GuiTab,
 };
+use crate::settings::Settings;
 use crate::usbipd::{self, UsbDevice};
 
 const PADDING_LEFT: Rect<D> = Rect {
@@ -29,10 +35,17 @@ const DETAILS_PANEL_PADDING: u32 = 4;
 
 #[derive(Default, NwgPartial)]
 pub struct PersistedTab {
+    settings: Rc<RefCell<Settings>>,
+
     window: Cell<nwg::ControlHandle>,
     shield_bitmap: Cell<nwg::Bitmap>,
 
+    /// A notice sender to request a refresh of all tabs from the main window
+    pub refresh_all_notice: Cell<Option<nwg::NoticeSender>>,
+
     persisted_devices: RefCell<Vec<usbipd::UsbDevice>>,
+    /// Whether the list is sorted by description, and whether the order is descending
+    sort_descending: Cell<Option<bool>>,
 
     #[nwg_layout(flex_direction: FlexDirection::Row)]
     persisted_tab_layout: nwg::FlexboxLayout,
@@ -42,7 +55,8 @@ pub struct PersistedTab {
         ex_flags: nwg::ListViewExFlags::FULL_ROW_SELECT,
     )]
     #[nwg_events(OnListViewRightClick: [PersistedTab::show_menu],
-        OnListViewItemChanged: [PersistedTab::update_persisted_details]
+        OnListViewItemChanged: [PersistedTab::update_persisted_details],
+        OnListViewColumnClick: [PersistedTab::sort_by_description]
     )]
     #[nwg_layout_item(layout: persisted_tab_layout, flex_grow: 1.0)]
     list_view: nwg::ListView,
@@ -75,21 +89,35 @@ pub struct PersistedTab {
     #[nwg_layout(parent: buttons_frame, flex_direction: FlexDirection::RowReverse, auto_spacing: None)]
     buttons_layout: nwg::FlexboxLayout,
 
-    #[nwg_control(parent: buttons_frame, text: "Delete")]
+    #[nwg_control(parent: buttons_frame, text: "Forget")]
     #[nwg_layout_item(layout: buttons_layout, flex_grow: 0.33)]
-    #[nwg_events(OnButtonClick: [PersistedTab::delete])]
-    delete_button: nwg::Button,
+    #[nwg_events(OnButtonClick: [PersistedTab::forget])]
+    forget_button: nwg::Button,
 
     // Device context menu
     #[nwg_control(text: "Device", popup: true)]
     menu: nwg::Menu,
 
-    #[nwg_control(parent: menu, text: "Delete")]
-    #[nwg_events(OnMenuItemSelected: [PersistedTab::delete])]
-    menu_delete: nwg::MenuItem,
+    #[nwg_control(parent: menu, text: "Forget")]
+    #[nwg_events(OnMenuItemSelected: [PersistedTab::forget])]
+    menu_forget: nwg::MenuItem,
+
+    #[nwg_control(parent: menu)]
+    menu_sep: nwg::MenuSeparator,
+
+    #[nwg_control(parent: menu, text: "Copy instance ID")]
+    #[nwg_events(OnMenuItemSelected: [PersistedTab::copy_instance_id])]
+    menu_copy_instance_id: nwg::MenuItem,
 }
 
 impl PersistedTab {
+    pub fn new(settings: &Rc<RefCell<Settings>>) -> Self {
+        Self {
+            settings: settings.clone(),
+            ..Default::default()
+        }
+    }
+
     fn init_list(&self) {
         let dv = &self.list_view;
         dv.clear();
@@ -100,17 +128,58 @@ impl PersistedTab {
         dv.set_column_width(0, LVSCW_AUTOSIZE_USEHEADER as isize);
     }
 
-    /// Clears the device list and reloads it with the currently persisted devices.
-    fn refresh_list(&self) {
-        self.update_devices();
+    /// Clears the device list and reloads it in the current sort order,
+    /// without retrieving the devices again.
+    fn refresh_list_with_devices(&self) {
+        // Keep track of the selected device, as its row might change
+        let selected_guid = self.list_view.selected_item().and_then(|i| {
+            self.persisted_devices
+                .borrow()
+                .get(i)?
+                .persisted_guid
+                .clone()
+        });
+        let top_index = self.list_view.top_index();
+
+        if let Some(descending) = self.sort_descending.get() {
+            self.persisted_devices.borrow_mut().sort_by(|a, b| {
+                let a = a.description.as_deref().unwrap_or_default().to_lowercase();
+                let b = b.description.as_deref().unwrap_or_default().to_lowercase();
+                if descending {
+                    b.cmp(&a)
+                } else {
+                    a.cmp(&b)
+                }
+            });
+        }
 
         self.list_view.clear();
+        let settings = self.settings.borrow();
         for device in self.persisted_devices.borrow().iter() {
-            self.list_view.insert_items_row(
-                None,
-                &[device.description.as_deref().unwrap_or("Unknown device")],
-            );
+            self.list_view
+                .insert_items_row(None, &[&device_name(&settings, device)]);
         }
+
+        let selected_index = self
+            .persisted_devices
+            .borrow()
+            .iter()
+            .position(|d| selected_guid.is_some() && d.persisted_guid == selected_guid);
+        if let Some(index) = selected_index {
+            self.list_view.select_item(index, true);
+        }
+        self.list_view.set_top_index(top_index);
+    }
+
+    /// Sorts the list by description.
+    /// Clicking the column again toggles between ascending and descending order.
+    fn sort_by_description(&self) {
+        let descending = self
+            .sort_descending
+            .get()
+            .is_some_and(|descending| !descending);
+        self.sort_descending.set(Some(descending));
+        self.refresh_list_with_devices();
     }
 
     /// Updates the details panel with the currently selected device.
@@ -119,18 +188,20 @@ impl PersistedTab {
         let device = self.list_view.selected_item().and_then(|i| devices.get(i));
 
         if device.is_some() {
-            self.delete_button.set_enabled(true);
+            self.forget_button.set_enabled(true);
         } else {
-            self.delete_button.set_enabled(false);
+            self.forget_button.set_enabled(false);
         }
 
         self.persisted_info.update(device);
     }
 
     fn show_menu(&self) {
-        if self.list_view.selected_item().is_none() {
-            return;
-        }
+        let has_instance_id = match self.list_view.selected_item() {
+            Some(i) => self.persisted_devices.borrow()[i].instance_id.is_some(),
+            None => return,
+        };
+        self.menu_copy_instance_id.set_enabled(has_instance_id);
 
         let (x, y) = nwg::GlobalCursor::position();
         // Disable menu animations because they cause incorrect rendering of the bitmaps
@@ -138,9 +209,39 @@ impl PersistedTab {
             .popup_with_flags(x, y, nwg::PopupMenuFlags::ANIMATE_NONE);
     }
 
-    fn delete(&self) {
+    fn copy_instance_id(&self) {
+        let instance_id = self
+            .list_view
+            .selected_item()
+            .and_then(|i| self.persisted_devices.borrow().get(i)?.instance_id.clone());
+        if let Some(instance_id) = instance_id {
+            nwg::Clipboard::set_data_text(self.window.get(), &instance_id);
+        }
+    }
+
+    /// Unbinds the selected device, so that usbipd forgets it.
+    /// Persisted devices are not connected, so they are removed from the list.
+    fn forget(&self) {
+        let name = match self.list_view.selected_item() {
+            Some(i) => device_name(&self.settings.borrow(), &self.persisted_devices.borrow()[i]),
+            None => return,
+        };
+        if !confirm_action(self.window.get(), &self.settings, "forget", &[name]) {
+            return;
+        }
+
         self.run_command(|device| {
-            device.unbind()?;
+            if let Err(err) = device.unbind() {
+                // The device might already be unknown to usbipd, e.g. if it was unbound
+                // elsewhere, in which case there is nothing left to forget
+                let is_persisted = usbipd::list_devices()?
+                    .iter()
+                    .any(|d| d.persisted_guid == device.persisted_guid);
+                if is_persisted {
+                    return Err(err.into());
+                }
+                return Ok(());
+            }
             device.wait(|d| d.is_none())
         });
     }
@@ -187,10 +288,12 @@ impl PersistedTab {
         nwg::unbind_event_handler(&cursor_event);
     }
 
-    fn update_devices(&self) {
-        *self.persisted_devices.borrow_mut() = usbipd::list_devices()
-            .into_iter()
+    /// Keeps the persisted devices that are not connected out of `devices`.
+    fn set_devices(&self, devices: &[UsbDevice]) {
+        *self.persisted_devices.borrow_mut() = devices
+            .iter()
             .filter(|d| !d.is_connected())
+            .cloned()
             .collect();
     }
 
@@ -207,8 +310,8 @@ impl GuiTab for PersistedTab {
         self.window.replace(window.handle);
 
         let shield_bitmap = nwg::Bitmap::from_system_icon(SIID_SHIELD);
-        self.delete_button.set_bitmap(Some(&shield_bitmap));
-        self.menu_delete.set_bitmap(Some(&shield_bitmap));
+        self.forget_button.set_bitmap(Some(&shield_bitmap));
+        self.menu_forget.set_bitmap(Some(&shield_bitmap));
 
         self.shield_bitmap.set(shield_bitmap);
 
@@ -217,7 +320,14 @@ impl GuiTab for PersistedTab {
     }
 
     fn refresh(&self) {
-        self.refresh_list();
+        if let Some(refresh_notice) = self.refresh_all_notice.get() {
+            refresh_notice.notice();
+        }
+    }
+
+    fn refresh_with_devices(&self, devices: &[UsbDevice]) {
+        self.set_devices(devices);
+        self.refresh_list_with_devices();
         self.update_persisted_details();
     }
 }
@@ -0,0 +1,105 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use native_windows_derive::NwgUi;
+use native_windows_gui as nwg;
+use nwg::NativeUi;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::EnableWindow;
+use windows_sys::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+
+use super::nwg_ext::WindowEx;
+use super::worker;
+
+/// A small modal dialog shown while a task runs in the background, which can be cancelled.
+#[derive(Default, NwgUi)]
+pub struct ProgressDialog {
+    parent: nwg::ControlHandle,
+    /// Set when the user cancels, the task is expected to stop as soon as possible
+    cancelled: Arc<AtomicBool>,
+
+    // Shown once centered over the parent window
+    #[nwg_control(parent: Some(data.parent), size: (340, 125),
+        title: "WSL USB Manager", flags: "WINDOW",
+    )]
+    #[nwg_events(OnWindowClose: [ProgressDialog::cancel_on_close(SELF, EVT_DATA)])]
+    window: nwg::Window,
+
+    #[nwg_control(position: (10, 10), size: (320, 20))]
+    label: nwg::Label,
+
+    #[nwg_control(position: (10, 35), size: (320, 20),
+        flags: "VISIBLE|MARQUEE", marquee: true, marquee_update: 30,
+    )]
+    progress_bar: nwg::ProgressBar,
+
+    #[nwg_control(text: "Cancel", position: (245, 88), size: (85, 27), focus: true)]
+    #[nwg_events(OnButtonClick: [ProgressDialog::cancel])]
+    cancel_button: nwg::Button,
+
+    #[nwg_control]
+    #[nwg_events(OnNotice: [ProgressDialog::close])]
+    done_notice: nwg::Notice,
+}
+
+impl ProgressDialog {
+    /// Asks the task to stop. The dialog stays open until it has.
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.label.set_text("Cancelling...");
+        self.cancel_button.set_enabled(false);
+    }
+
+    fn cancel_on_close(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnWindowClose(close_data) = data {
+            close_data.close(false);
+        }
+        self.cancel();
+    }
+
+    /// Hides the dialog and exits its event loop.
+    fn close(&self) {
+        self.window.set_visible(false);
+        nwg::stop_thread_dispatch();
+    }
+}
+
+/// Runs `task` on a background thread while showing a modal dialog with `message`.
+///
+/// The task is passed a flag that is set when the user cancels, it should then stop and
+/// return `None`. Returns the result of the task, or `None` if it was cancelled.
+pub fn run_with_progress<T, F>(parent: nwg::ControlHandle, message: &str, task: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&AtomicBool) -> Option<T> + Send + 'static,
+{
+    let dialog = ProgressDialog {
+        parent,
+        ..Default::default()
+    };
+    let dialog = ProgressDialog::build_ui(dialog).ok()?;
+    dialog.label.set_text(message);
+
+    let cancelled = dialog.cancelled.clone();
+    let receiver = worker::spawn(dialog.done_notice.sender(), move || task(&cancelled));
+
+    dialog.window.center_on(parent);
+    dialog.window.set_visible(true);
+    dialog.cancel_button.set_focus();
+
+    // Disable the parent window while the dialog is open to make it modal
+    let parent_hwnd = parent.hwnd().map_or(0, |hwnd| hwnd as HWND);
+    unsafe { EnableWindow(parent_hwnd, 0) };
+
+    // Runs until the task completes
+    nwg::dispatch_thread_events();
+
+    unsafe {
+        EnableWindow(parent_hwnd, 1);
+        SetForegroundWindow(parent_hwnd);
+    }
+
+    receiver.try_recv().ok().flatten()
+}
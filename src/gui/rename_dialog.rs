@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+
+use native_windows_derive::NwgUi;
+use native_windows_gui as nwg;
+use nwg::NativeUi;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::EnableWindow;
+use windows_sys::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+
+use super::nwg_ext::WindowEx;
+
+/// A small modal dialog asking for a new device name.
+#[derive(Default, NwgUi)]
+pub struct RenameDialog {
+    parent: nwg::ControlHandle,
+    /// The entered name, set when the dialog is confirmed
+    result: RefCell<Option<String>>,
+
+    // Shown once centered over the parent window
+    #[nwg_control(parent: Some(data.parent), size: (340, 125),
+        title: "WSL USB Manager: Rename Device", flags: "WINDOW",
+    )]
+    #[nwg_events(OnWindowClose: [RenameDialog::cancel])]
+    window: nwg::Window,
+
+    #[nwg_control(text: "Name (leave empty to show the device description):",
+        position: (10, 10), size: (320, 20),
+    )]
+    label: nwg::Label,
+
+    #[nwg_control(position: (10, 35), size: (320, 23), focus: true)]
+    #[nwg_events(OnKeyEnter: [RenameDialog::ok], OnKeyEsc: [RenameDialog::cancel])]
+    name_input: nwg::TextInput,
+
+    #[nwg_control(text: "OK", position: (154, 88), size: (85, 27))]
+    #[nwg_events(OnButtonClick: [RenameDialog::ok])]
+    ok_button: nwg::Button,
+
+    #[nwg_control(text: "Cancel", position: (245, 88), size: (85, 27))]
+    #[nwg_events(OnButtonClick: [RenameDialog::cancel])]
+    cancel_button: nwg::Button,
+}
+
+impl RenameDialog {
+    fn ok(&self) {
+        *self.result.borrow_mut() = Some(self.name_input.text());
+        self.close();
+    }
+
+    fn cancel(&self) {
+        self.close();
+    }
+
+    /// Hides the dialog and exits its event loop.
+    fn close(&self) {
+        self.window.set_visible(false);
+        nwg::stop_thread_dispatch();
+    }
+}
+
+/// Shows a modal dialog asking for a new name for a device, prefilled with `current_name`.
+///
+/// Returns the entered name, which may be empty, or `None` if the dialog was cancelled.
+pub fn ask_device_name(parent: nwg::ControlHandle, current_name: &str) -> Option<String> {
+    let dialog = RenameDialog {
+        parent,
+        ..Default::default()
+    };
+    let dialog = RenameDialog::build_ui(dialog).ok()?;
+
+    dialog.window.center_on(parent);
+    dialog.window.set_visible(true);
+    dialog.name_input.set_focus();
+
+    dialog.name_input.set_text(current_name);
+    dialog
+        .name_input
+        .set_selection(0..current_name.chars().count() as u32);
+
+    // Disable the parent window while the dialog is open to make it modal
+    let parent_hwnd = parent.hwnd().map_or(0, |hwnd| hwnd as HWND);
+    unsafe { EnableWindow(parent_hwnd, 0) };
+
+    // Runs until the dialog is closed
+    nwg::dispatch_thread_events();
+
+    unsafe {
+        EnableWindow(parent_hwnd, 1);
+        SetForegroundWindow(parent_hwnd);
+    }
+
+    dialog.result.take()
+}
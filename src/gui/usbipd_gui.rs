@@ -1,30 +1,150 @@
 use std::{
     cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    path::PathBuf,
     rc::Rc,
+    sync::{mpsc::Receiver, Arc, Mutex},
+    time::Duration,
 };
 
 use native_windows_derive::NwgUi;
 use native_windows_gui as nwg;
+use nwg::stretch::{
+    geometry::Size,
+    style::{Dimension as D, FlexDirection},
+};
+use serde::{Deserialize, Serialize};
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::WindowsAndMessaging::{WM_HOTKEY, WM_SETTINGCHANGE};
 
+use super::about;
 use super::auto_attach_tab::AutoAttachTab;
 use super::connected_tab::ConnectedTab;
+use super::distribution_dialog::target_distribution;
+use super::helpers::{device_name, ellipsize_middle, DEFAULT_DISTRIBUTION, MENU_LABEL_MAX_LEN};
+use super::nwg_ext::WindowEx;
 use super::persisted_tab::PersistedTab;
+use super::worker::{self, BackgroundCommand};
 use crate::{
     auto_attach::AutoAttacher,
-    win_utils::{self, DeviceNotification},
+    config::Configuration,
+    diagnostics, logging,
+    settings::{self, Settings},
+    toast::{self, Toast, ToastButton},
+    usbipd::{self, UsbDevice},
+    win_utils::{self, DeviceNotification, HotKey},
+    wsl,
 };
 
+/// The minimum size of the main window, in logical pixels.
+const MIN_WINDOW_SIZE: (i32, i32) = (600, 440);
+
+const REFRESH_BUTTON_TEXT: &str = "\u{21bb} Refresh";
+
+/// The maximum number of buttons of a toast notification.
+const MAX_TOAST_BUTTONS: usize = 5;
+
+/// How long to wait for further USB device notifications before refreshing the device list,
+/// so that bursts of notifications, e.g. when plugging in a hub, cause a single refresh.
+const USB_NOTIFICATION_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// The maximum time spent detaching devices when exiting.
+const DETACH_ON_EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often auto-attach processes are checked for having exited.
+const AUTO_ATTACH_WATCHDOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The ID of the global hotkey that shows or hides the window.
+const TOGGLE_WINDOW_HOTKEY_ID: i32 = 1;
+
+/// The ID of the raw event handler receiving hotkey messages. Must be greater than 0xFFFF.
+const HOTKEY_HANDLER_ID: usize = 0x10001;
+
+/// The ID of the raw event handler receiving system setting changes. Must be greater than 0xFFFF.
+const SETTING_CHANGE_HANDLER_ID: usize = 0x10002;
+
+/// What selecting a device item of the tray menu does.
+#[derive(Clone)]
+enum TrayDeviceAction {
+    /// Attaches the device to the given distribution, or the default one if `None`.
+    Attach(Option<String>),
+    Detach,
+}
+
+/// What a button of the toast offering to attach a connected device does.
+/// Serialized as the argument of the button.
+#[derive(Serialize, Deserialize)]
+struct AttachOffer {
+    device: UsbDevice,
+    /// The distribution to attach the device to, or the default one if `None`
+    distribution: Option<String>,
+}
+
 pub(super) trait GuiTab {
     /// Initializes the tab. The root window handle is provided.
     fn init(&self, window: &nwg::Window);
 
-    /// Refreshes the data displayed in the tab.
+    /// Requests a refresh of the data displayed in the tab. The main window retrieves the
+    /// device list in the background, then calls [`GuiTab::refresh_with_devices`].
     fn refresh(&self);
+
+    /// Refreshes the data displayed in the tab with an already retrieved device list.
+    fn refresh_with_devices(&self, devices: &[UsbDevice]);
 }
 
 #[derive(Default, NwgUi)]
 pub struct UsbipdGui {
+    auto_attacher: Rc<RefCell<AutoAttacher>>,
+    settings: Rc<RefCell<Settings>>,
     device_notification: Cell<DeviceNotification>,
+    start_minimized: bool,
+
+    /// The last known attach state of bound devices, by instance ID
+    attach_states: RefCell<HashMap<String, bool>>,
+    /// The devices as of the last retrieved device list, listed in the tray menu
+    devices: RefCell<Vec<UsbDevice>>,
+
+    /// Bound devices that were connected at the last device list, by instance ID.
+    /// `None` until the first list, so that no attach is offered for devices connected before
+    connected_bound_devices: RefCell<Option<HashSet<String>>>,
+    /// The last toast offering to attach a device, kept so that its buttons keep working
+    attach_offer_toast: Arc<Mutex<Option<Toast>>>,
+    /// The argument of the clicked button of an attach offer toast
+    attach_offer_activation: Arc<Mutex<Option<String>>>,
+
+    /// Bound devices listed in the tray menu, along with the handles of their menu items
+    /// and what selecting them does
+    tray_devices: Rc<RefCell<Vec<(nwg::ControlHandle, UsbDevice, TrayDeviceAction)>>>,
+    tray_device_menus: RefCell<Vec<nwg::Menu>>,
+    tray_device_items: RefCell<Vec<nwg::MenuItem>>,
+    tray_menu_handler: RefCell<Option<nwg::EventHandler>>,
+
+    /// A device command running in the background, started from the menus.
+    /// Shared with the connected devices tab, so that commands never overlap
+    background_command: BackgroundCommand<Result<(), String>>,
+    /// Devices to bind on connect that arrived while another command was running
+    pending_auto_bind: RefCell<Vec<UsbDevice>>,
+    /// Diagnostics being collected in the background
+    diagnostics_command: BackgroundCommand<Result<PathBuf, String>>,
+
+    /// The device list being retrieved in the background, to refresh all tabs with
+    device_list_receiver: RefCell<Option<Receiver<Result<Vec<UsbDevice>, String>>>>,
+    /// Whether another refresh was requested while the device list was being retrieved
+    device_list_outdated: Cell<bool>,
+    /// Whether USB device notifications arrived since the device list was last retrieved
+    devices_changed: Cell<bool>,
+    /// Whether the auto-attach profiles and auto-bind rules are matched against the device
+    /// list being retrieved, as devices changed before retrieving it
+    match_retrieved_devices: Cell<bool>,
+    /// Whether devices changed while refreshing was paused because the window was hidden
+    refresh_pending: Cell<bool>,
+
+    /// The global hotkey that shows or hides the window, if enabled
+    toggle_window_hotkey: RefCell<Option<HotKey>>,
+    hotkey_handler: RefCell<Option<nwg::RawEventHandler>>,
+
+    /// Receives system setting changes, to follow the light or dark theme
+    setting_change_handler: RefCell<Option<nwg::RawEventHandler>>,
 
     #[nwg_resource]
     embed: nwg::EmbedResource,
@@ -32,6 +152,14 @@ pub struct UsbipdGui {
     #[nwg_resource(source_embed: Some(&data.embed), source_embed_str: Some("MAINICON"))]
     app_icon: nwg::Icon,
 
+    #[nwg_resource(title: "Export Configuration", action: nwg::FileDialogAction::Save,
+        filters: "JSON (*.json)|All files (*.*)")]
+    export_dialog: nwg::FileDialog,
+
+    #[nwg_resource(title: "Import Configuration", action: nwg::FileDialogAction::Open,
+        filters: "JSON (*.json)|All files (*.*)")]
+    import_dialog: nwg::FileDialog,
+
     // Window
     #[nwg_control(size: (780, 430), center: true, title: "WSL USB Manager", icon: Some(&data.app_icon))]
     #[nwg_events(
@@ -41,16 +169,79 @@ pub struct UsbipdGui {
     )]
     window: nwg::Window,
 
-    #[nwg_layout(parent: window, auto_spacing: Some(2))]
+    #[nwg_layout(parent: window, flex_direction: FlexDirection::Column, auto_spacing: Some(2))]
     window_layout: nwg::FlexboxLayout,
 
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::usb_devices_changed])]
+    usb_notice: nwg::Notice,
+
+    #[nwg_control(parent: window, interval: USB_NOTIFICATION_DEBOUNCE, active: false)]
+    #[nwg_events(OnTimerTick: [UsbipdGui::retrieve_device_list])]
+    usb_debounce_timer: nwg::AnimationTimer,
+
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::device_list_retrieved])]
+    device_list_notice: nwg::Notice,
+
     #[nwg_control(parent: window)]
     #[nwg_events(OnNotice: [UsbipdGui::refresh])]
     refresh_notice: nwg::Notice,
 
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::background_command_done])]
+    background_command_notice: nwg::Notice,
+
+    /// Notified when a device command starts or completes outside of the menus
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::busy_state_changed])]
+    busy_notice: nwg::Notice,
+
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::diagnostics_done])]
+    diagnostics_notice: nwg::Notice,
+
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::toggle_window])]
+    hotkey_notice: nwg::Notice,
+
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::bring_to_front])]
+    show_window_notice: nwg::Notice,
+
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::apply_theme])]
+    theme_notice: nwg::Notice,
+
+    /// Notified when a button of an attach offer toast is clicked
+    #[nwg_control(parent: window)]
+    #[nwg_events(OnNotice: [UsbipdGui::attach_offer_activated])]
+    attach_offer_notice: nwg::Notice,
+
+    #[nwg_control(parent: window, active: false)]
+    #[nwg_events(OnTimerTick: [UsbipdGui::auto_refresh])]
+    auto_refresh_timer: nwg::AnimationTimer,
+
+    #[nwg_control(parent: window, active: false)]
+    #[nwg_events(OnTimerTick: [UsbipdGui::check_auto_attach_processes])]
+    auto_attach_watchdog_timer: nwg::AnimationTimer,
+
+    // Toolbar, shared by all tabs
+    #[nwg_control(parent: window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: window_layout, size: Size { width: D::Auto, height: D::Points(25.0) })]
+    toolbar_frame: nwg::Frame,
+
+    #[nwg_layout(parent: toolbar_frame, flex_direction: FlexDirection::RowReverse, auto_spacing: None)]
+    toolbar_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: toolbar_frame, text: REFRESH_BUTTON_TEXT)]
+    #[nwg_layout_item(layout: toolbar_layout, size: Size { width: D::Points(100.0), height: D::Auto })]
+    #[nwg_events(OnButtonClick: [UsbipdGui::refresh_from_toolbar])]
+    refresh_button: nwg::Button,
+
     // Tabs
     #[nwg_control(parent: window)]
-    #[nwg_layout_item(layout: window_layout)]
+    #[nwg_layout_item(layout: window_layout, flex_grow: 1.0)]
     tabs_container: nwg::TabsContainer,
 
     // Connected devices tab
@@ -82,6 +273,20 @@ pub struct UsbipdGui {
     #[nwg_control(parent: window, popup: true)]
     menu_tray: nwg::Menu,
 
+    #[nwg_control(parent: menu_tray, text: "Devices")]
+    menu_tray_devices: nwg::Menu,
+
+    #[nwg_control(parent: menu_tray, text: "Attach all")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::attach_all])]
+    menu_tray_attach_all: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_tray, text: "Detach all")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::detach_all])]
+    menu_tray_detach_all: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_tray)]
+    menu_tray_sep0: nwg::MenuSeparator,
+
     #[nwg_control(parent: menu_tray, text: "Open")]
     #[nwg_events(OnMenuItemSelected: [UsbipdGui::show])]
     menu_tray_open: nwg::MenuItem,
@@ -101,71 +306,1192 @@ pub struct UsbipdGui {
     #[nwg_events(OnMenuItemSelected: [UsbipdGui::refresh])]
     menu_file_refresh: nwg::MenuItem,
 
+    #[nwg_control(parent: menu_file, text: "Attach all")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::attach_all])]
+    menu_file_attach_all: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_file, text: "Detach all")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::detach_all])]
+    menu_file_detach_all: nwg::MenuItem,
+
     #[nwg_control(parent: menu_file)]
     menu_file_sep1: nwg::MenuSeparator,
 
+    #[nwg_control(parent: menu_file, text: "Export configuration...")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::export_configuration])]
+    menu_file_export: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_file, text: "Import configuration...")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::import_configuration])]
+    menu_file_import: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_file, text: "Open settings and logs folder")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::open_settings_folder])]
+    menu_file_open_settings_folder: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_file)]
+    menu_file_sep2: nwg::MenuSeparator,
+
     #[nwg_control(parent: menu_file, text: "Exit")]
     #[nwg_events(OnMenuItemSelected: [UsbipdGui::exit])]
     menu_file_exit: nwg::MenuItem,
+
+    // View menu
+    #[nwg_control(parent: window, text: "View", popup: false)]
+    menu_view: nwg::Menu,
+
+    #[nwg_control(parent: menu_view, text: "Always on top")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_always_on_top])]
+    menu_view_always_on_top: nwg::MenuItem,
+
+    // Options menu
+    #[nwg_control(parent: window, text: "Options", popup: false)]
+    menu_options: nwg::Menu,
+
+    #[nwg_control(parent: menu_options, text: "Notify on attach and detach")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_attach_notifications])]
+    menu_options_notify: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_options, text: "Offer to attach bound devices on connect")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_offer_attach_on_connect])]
+    menu_options_offer_attach: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_options, text: "Start with Windows")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_start_with_windows])]
+    menu_options_start_with_windows: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_options, text: "Refresh automatically")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_auto_refresh])]
+    menu_options_auto_refresh: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_options, text: "Pause refreshing while hidden")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_pause_refresh_while_hidden])]
+    menu_options_pause_refresh: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_options, text: "Detach all devices on exit")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_detach_all_on_exit])]
+    menu_options_detach_on_exit: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_options, text: "Hide USB hubs")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::toggle_hide_hubs])]
+    menu_options_hide_hubs: nwg::MenuItem,
+
+    // Help menu
+    #[nwg_control(parent: window, text: "Help", popup: false)]
+    menu_help: nwg::Menu,
+
+    #[nwg_control(parent: menu_help, text: "Generate diagnostics")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::generate_diagnostics])]
+    menu_help_diagnostics: nwg::MenuItem,
+
+    #[nwg_control(parent: menu_help)]
+    menu_help_sep: nwg::MenuSeparator,
+
+    #[nwg_control(parent: menu_help, text: "About")]
+    #[nwg_events(OnMenuItemSelected: [UsbipdGui::show_about])]
+    menu_help_about: nwg::MenuItem,
 }
 
 impl UsbipdGui {
-    pub fn new(auto_attacher: &Rc<RefCell<AutoAttacher>>) -> Self {
+    pub fn new(
+        auto_attacher: &Rc<RefCell<AutoAttacher>>,
+        settings: &Rc<RefCell<Settings>>,
+        start_minimized: bool,
+    ) -> Self {
+        let background_command = BackgroundCommand::default();
         Self {
-            connected_tab_content: ConnectedTab::new(auto_attacher),
-            auto_attach_tab_content: AutoAttachTab::new(auto_attacher),
+            auto_attacher: auto_attacher.clone(),
+            settings: settings.clone(),
+            start_minimized,
+            connected_tab_content: ConnectedTab::new(auto_attacher, settings, &background_command),
+            persisted_tab_content: PersistedTab::new(settings),
+            auto_attach_tab_content: AutoAttachTab::new(
+                auto_attacher,
+                settings,
+                &background_command,
+            ),
+            background_command,
             ..Default::default()
         }
     }
 
     fn init(&self) {
+        if let Some(rect) = self.settings.borrow().window_rect {
+            self.window.set_rect_clamped(rect, MIN_WINDOW_SIZE);
+        }
+
         self.connected_tab_content.init(&self.window);
         self.persisted_tab_content.init(&self.window);
         self.auto_attach_tab_content.init(&self.window);
+        self.update_tray_tip();
 
         // Give the connected tab a way to notify the auto attach tab that it needs to refresh
         self.connected_tab_content
             .auto_attach_notice
             .set(Some(self.auto_attach_tab_content.refresh_notice.sender()));
 
-        let sender = self.refresh_notice.sender();
+        // Let the tabs and the tray menu report the device commands they start or complete
+        let busy_notice = Some(self.busy_notice.sender());
+        self.connected_tab_content.busy_notice.set(busy_notice);
+        self.auto_attach_tab_content.busy_notice.set(busy_notice);
+
+        // Let the tabs request a refresh, which retrieves the device list for all of them
+        let refresh_notice = Some(self.refresh_notice.sender());
+        self.connected_tab_content
+            .refresh_all_notice
+            .set(refresh_notice);
+        self.persisted_tab_content
+            .refresh_all_notice
+            .set(refresh_notice);
+        self.auto_attach_tab_content
+            .refresh_all_notice
+            .set(refresh_notice);
+
+        let sender = self.usb_notice.sender();
         self.device_notification.set(
             win_utils::register_usb_device_notifications(move || {
                 sender.notice();
             })
             .expect("Failed to register USB device notifications"),
         );
+
+        if self.start_minimized {
+            self.window.set_visible(false);
+        }
+
+        // Starting the app again shows the window of this instance
+        let sender = self.show_window_notice.sender();
+        if let Err(err) = win_utils::listen_for_show_window_requests(move || sender.notice()) {
+            logging::error(format_args!("{err}"));
+        }
+
+        self.init_tray_menu_handler();
+        self.init_hotkey();
+        self.init_theme();
+        self.apply_settings();
+
+        self.auto_attach_watchdog_timer
+            .set_interval(AUTO_ATTACH_WATCHDOG_INTERVAL);
+        self.auto_attach_watchdog_timer.start();
+
+        // The tabs are filled once the device list is retrieved
+        self.refresh();
+    }
+
+    /// Updates the menus, the window and the timers to reflect the current settings.
+    fn apply_settings(&self) {
+        let settings = self.settings.borrow();
+        self.menu_options_notify
+            .set_checked(settings.notify_attach_changes);
+        self.attach_states.borrow_mut().clear();
+        if settings.notify_attach_changes {
+            // Start tracking from the current state
+            self.notify_attach_changes(&self.devices.borrow());
+        }
+
+        self.menu_options_offer_attach
+            .set_checked(settings.offer_attach_on_connect);
+        if !settings.offer_attach_on_connect {
+            self.connected_bound_devices.take();
+        }
+
+        self.menu_options_start_with_windows
+            .set_checked(settings.start_with_windows);
+        self.menu_options_auto_refresh
+            .set_checked(settings.auto_refresh);
+        self.menu_options_detach_on_exit
+            .set_checked(settings.detach_all_on_exit);
+        self.menu_options_hide_hubs.set_checked(settings.hide_hubs);
+        self.menu_options_pause_refresh
+            .set_checked(settings.pause_refresh_while_hidden);
+        self.menu_view_always_on_top
+            .set_checked(settings.always_on_top);
+        self.window.set_always_on_top(settings.always_on_top);
+        self.auto_refresh_timer.set_interval(Duration::from_secs(
+            settings.auto_refresh_interval_secs.max(1),
+        ));
+        if settings.auto_refresh {
+            self.auto_refresh_timer.start();
+        } else {
+            self.auto_refresh_timer.stop();
+        }
+    }
+
+    /// Binds the handler for the device items of the tray menu.
+    ///
+    /// Device items are created dynamically, so they cannot use the derived event handlers.
+    /// Commands run in the background to keep the UI responsive, and complete in
+    /// [`UsbipdGui::background_command_done`].
+    fn init_tray_menu_handler(&self) {
+        let window = self.window.handle;
+        let tray_devices = self.tray_devices.clone();
+        let settings = self.settings.clone();
+        let background_command = self.background_command.clone();
+        let notice = self.background_command_notice.sender();
+        let busy_notice = self.busy_notice.sender();
+
+        let handler = nwg::full_bind_event_handler(&window, move |event, _event_data, handle| {
+            if !matches!(event, nwg::Event::OnMenuItemSelected) {
+                return;
+            }
+
+            let (device, action) = match tray_devices.borrow().iter().find(|(h, ..)| *h == handle) {
+                Some((_, device, action)) => (device.clone(), action.clone()),
+                None => return,
+            };
+
+            let name = device_name(&settings.borrow(), &device);
+
+            let started = background_command.start(&window, notice, move || {
+                run_tray_device_action(&device, &name, action)
+            });
+            if started {
+                busy_notice.notice();
+            }
+        });
+
+        *self.tray_menu_handler.borrow_mut() = Some(handler);
+    }
+
+    /// Registers the global hotkey that shows or hides the window, if one is configured.
+    ///
+    /// An error dialog is shown if the hotkey is invalid or already used by another app.
+    fn init_hotkey(&self) {
+        let hotkey = match self.settings.borrow().toggle_window_hotkey.clone() {
+            Some(hotkey) => hotkey,
+            None => return,
+        };
+
+        let hwnd = self.window.handle.hwnd().map_or(0, |hwnd| hwnd as HWND);
+        match win_utils::register_hotkey(hwnd, TOGGLE_WINDOW_HOTKEY_ID, &hotkey) {
+            Ok(registered) => *self.toggle_window_hotkey.borrow_mut() = Some(registered),
+            Err(err) => {
+                logging::error(format_args!("{err}"));
+                nwg::modal_error_message(&self.window, "WSL USB Manager: Hotkey Error", &err);
+                return;
+            }
+        }
+
+        // Hotkeys are delivered as window messages, which have no derived event
+        let sender = self.hotkey_notice.sender();
+        let handler = nwg::bind_raw_event_handler(
+            &self.window.handle,
+            HOTKEY_HANDLER_ID,
+            move |_hwnd, msg, wparam, _lparam| {
+                if msg == WM_HOTKEY && wparam == TOGGLE_WINDOW_HOTKEY_ID as usize {
+                    sender.notice();
+                }
+                None
+            },
+        );
+        *self.hotkey_handler.borrow_mut() = handler.ok();
+    }
+
+    /// Follows the light or dark theme of Windows, switching when it changes.
+    fn init_theme(&self) {
+        self.apply_theme();
+
+        // Setting changes are delivered as window messages, which have no derived event.
+        // The theme is read again on any change, as the changed setting is not always named
+        let sender = self.theme_notice.sender();
+        let handler = nwg::bind_raw_event_handler(
+            &self.window.handle,
+            SETTING_CHANGE_HANDLER_ID,
+            move |_hwnd, msg, _wparam, _lparam| {
+                if msg == WM_SETTINGCHANGE {
+                    sender.notice();
+                }
+                None
+            },
+        );
+        *self.setting_change_handler.borrow_mut() = handler.ok();
+    }
+
+    fn apply_theme(&self) {
+        self.window.set_dark_mode(win_utils::apps_use_dark_theme());
+    }
+
+    /// Completes a device command started from the menus.
+    ///
+    /// The view is reloaded, and an error dialog is shown if the command failed.
+    fn background_command_done(&self) {
+        if let Some(Err(err)) = self.background_command.finish() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Command Error", &err);
+        }
+
+        self.refresh();
+        self.bind_pending_devices();
+    }
+
+    /// Updates the device actions after a command started or completed outside of the menus,
+    /// and binds the devices that waited for it to complete.
+    fn busy_state_changed(&self) {
+        self.connected_tab_content.update_busy_state();
+        if !self.background_command.is_running() {
+            self.bind_pending_devices();
+        }
+    }
+
+    /// Runs a device command started from the menus on a background thread.
+    /// Returns `false` without running it if another command is still running.
+    fn start_command<F>(&self, command: F) -> bool
+    where
+        F: FnOnce() -> Result<(), String> + Send + 'static,
+    {
+        let started = self.background_command.start(
+            &self.window.handle,
+            self.background_command_notice.sender(),
+            command,
+        );
+        if started {
+            self.connected_tab_content.update_busy_state();
+        }
+        started
+    }
+
+    fn attach_all(&self) {
+        let distribution = match self.target_distribution() {
+            Some(distribution) => distribution,
+            None => return,
+        };
+        self.start_command(move || usbipd::attach_all(distribution.as_deref()));
+    }
+
+    /// Returns the distribution to attach to, or `None` for the default one,
+    /// asking the user if the attach target says so.
+    ///
+    /// Returns `None` overall if the user cancelled.
+    fn target_distribution(&self) -> Option<Option<String>> {
+        let target = self.settings.borrow().attach_target.clone();
+        target_distribution(self.window.handle, &target)
+    }
+
+    fn detach_all(&self) {
+        self.start_command(usbipd::detach_all);
+    }
+
+    /// Rebuilds the device items of the tray menu with the bound devices of the last
+    /// retrieved device list.
+    ///
+    /// Each device gets a submenu to attach it to the default or a specific distribution,
+    /// or to detach it if attached.
+    fn update_tray_devices(&self) {
+        let mut menus = self.tray_device_menus.borrow_mut();
+        let mut items = self.tray_device_items.borrow_mut();
+        let mut tray_devices = self.tray_devices.borrow_mut();
+
+        // Dropping the menus and their items removes them from the menu
+        items.clear();
+        menus.clear();
+        tray_devices.clear();
+
+        let busy = self.background_command.is_running();
+        let mut devices: Vec<UsbDevice> = self
+            .devices
+            .borrow()
+            .iter()
+            .filter(|d| d.is_bound())
+            .cloned()
+            .collect();
+        devices.sort_by(|a, b| {
+            usbipd::compare_bus_ids(
+                a.bus_id.as_deref().unwrap_or_default(),
+                b.bus_id.as_deref().unwrap_or_default(),
+            )
+        });
+
+        // Only listed when needed, as it runs WSL
+        let distributions = if devices.is_empty() {
+            Vec::new()
+        } else {
+            wsl::list_distributions()
+        };
+
+        for device in devices {
+            let description = ellipsize_middle(
+                &device_name(&self.settings.borrow(), &device),
+                MENU_LABEL_MAX_LEN,
+            );
+            // Show the bus ID to tell apart devices with the same description
+            let mut text = match &device.bus_id {
+                Some(bus_id) => format!("{bus_id}: {description}"),
+                None => description,
+            };
+            let attached = device.is_attached();
+            if attached {
+                text.push_str(" (attached)");
+            }
+
+            let mut menu = nwg::Menu::default();
+            let built = nwg::Menu::builder()
+                .text(&text)
+                .disabled(busy)
+                .parent(&self.menu_tray_devices)
+                .build(&mut menu);
+            if built.is_err() {
+                continue;
+            }
+
+            // Attach entries are disabled while attached, and the detach entry otherwise
+            let mut entries = vec![(
+                DEFAULT_DISTRIBUTION,
+                TrayDeviceAction::Attach(None),
+                !attached,
+            )];
+            for distribution in &distributions {
+                let action = TrayDeviceAction::Attach(Some(distribution.clone()));
+                entries.push((distribution.as_str(), action, !attached));
+            }
+            entries.push(("Detach", TrayDeviceAction::Detach, attached));
+
+            for (text, action, enabled) in entries {
+                let mut item = nwg::MenuItem::default();
+                let built = nwg::MenuItem::builder()
+                    .text(text)
+                    .disabled(!enabled)
+                    .parent(&menu)
+                    .build(&mut item);
+
+                if built.is_ok() {
+                    tray_devices.push((item.handle, device.clone(), action));
+                    items.push(item);
+                }
+            }
+
+            menus.push(menu);
+        }
+
+        if menus.is_empty() {
+            let mut item = nwg::MenuItem::default();
+            let built = nwg::MenuItem::builder()
+                .text("No bound devices")
+                .disabled(true)
+                .parent(&self.menu_tray_devices)
+                .build(&mut item);
+
+            if built.is_ok() {
+                items.push(item);
+            }
+        }
     }
 
     fn min_max_info(data: &nwg::EventData) {
         if let nwg::EventData::OnMinMaxInfo(info) = data {
-            info.set_min_size(600, 410);
+            info.set_min_size(MIN_WINDOW_SIZE.0, MIN_WINDOW_SIZE.1);
         }
     }
 
+    /// Saves the window size and position, and the list column widths, to the settings.
+    fn save_window_layout(&self) {
+        if !self.window.visible() {
+            return;
+        }
+
+        let mut settings = self.settings.borrow_mut();
+        if let Some(rect) = self.window.rect() {
+            settings.window_rect = Some(rect);
+        }
+        settings.connected_column_widths = Some(self.connected_tab_content.column_widths());
+        let _ = settings.save();
+    }
+
     fn hide(&self, data: &nwg::EventData) {
         if let nwg::EventData::OnWindowClose(close_data) = data {
             close_data.close(false);
         }
+        self.save_window_layout();
         self.window.set_visible(false);
     }
 
     fn show(&self) {
+        let was_visible = self.window.visible();
         self.window.set_visible(true);
+
+        // Periodic refreshes are skipped while hidden, catch up on any changes
+        let refresh_pending = self.refresh_pending.replace(false);
+        if !was_visible && (refresh_pending || self.settings.borrow().auto_refresh) {
+            self.refresh();
+        }
+    }
+
+    /// Returns whether refreshing is paused because the window is hidden.
+    fn is_refresh_paused(&self) -> bool {
+        self.settings.borrow().pause_refresh_while_hidden && !self.window.visible()
+    }
+
+    /// Shows the window, or hides it if it is visible.
+    fn toggle_window(&self) {
+        if self.window.visible() {
+            self.save_window_layout();
+            self.window.set_visible(false);
+        } else {
+            self.show();
+            self.window.set_focus();
+        }
+    }
+
+    /// Shows the window, restoring it if minimized, and brings it to the foreground.
+    fn bring_to_front(&self) {
+        self.show();
+        self.window.bring_to_front();
     }
 
     fn show_tray_menu(&self) {
+        self.update_tray_devices();
+
+        // Devices that changed while refreshing was paused are listed the next time
+        if self.refresh_pending.get() {
+            self.retrieve_device_list();
+        }
+
         let (x, y) = nwg::GlobalCursor::position();
         self.menu_tray.popup(x, y);
     }
 
+    /// Handles USB devices being connected or disconnected.
+    /// Schedules a refresh once USB device notifications stop arriving.
+    ///
+    /// While refreshing is paused, the devices are only retrieved if auto-attach profiles,
+    /// auto-bind rules or attach offers need them, and the view is refreshed once the window
+    /// is shown.
+    fn usb_devices_changed(&self) {
+        self.devices_changed.set(true);
+        if self.is_refresh_paused() {
+            self.refresh_pending.set(true);
+            if !self.auto_attacher.borrow().watches_devices()
+                && !self.settings.borrow().offer_attach_on_connect
+            {
+                return;
+            }
+        }
+
+        self.usb_debounce_timer.stop();
+        self.usb_debounce_timer.start();
+    }
+
+    /// Retrieves the device list on a background thread, to keep the UI responsive.
+    /// All tabs are refreshed with it in [`UsbipdGui::device_list_retrieved`].
+    fn retrieve_device_list(&self) {
+        self.usb_debounce_timer.stop();
+
+        let mut receiver = self.device_list_receiver.borrow_mut();
+        if receiver.is_some() {
+            // Retrieve the list again once the current retrieval completes
+            self.device_list_outdated.set(true);
+            return;
+        }
+
+        self.match_retrieved_devices
+            .set(self.devices_changed.replace(false));
+        *receiver = Some(worker::spawn(
+            self.device_list_notice.sender(),
+            usbipd::list_devices_with_speed,
+        ));
+    }
+
+    fn device_list_retrieved(&self) {
+        let result = self
+            .device_list_receiver
+            .borrow_mut()
+            .take()
+            .and_then(|receiver| receiver.try_recv().ok());
+
+        let devices_changed = self.match_retrieved_devices.replace(false);
+        if self.device_list_outdated.replace(false) {
+            self.retrieve_device_list();
+        } else {
+            self.refresh_button.set_text(REFRESH_BUTTON_TEXT);
+            self.refresh_button.set_enabled(true);
+        }
+
+        let devices = match result {
+            Some(Ok(devices)) => devices,
+            Some(Err(err)) => {
+                logging::error(format_args!("Failed to refresh the device list: {err}"));
+                // Not shown after device changes, which happen without any user interaction
+                if !devices_changed {
+                    nwg::modal_error_message(
+                        &self.window,
+                        "WSL USB Manager: Device List Error",
+                        &err,
+                    );
+                }
+                return;
+            }
+            None => return,
+        };
+
+        if devices_changed {
+            let to_bind = self.auto_attacher.borrow_mut().devices_to_bind(&devices);
+            if !to_bind.is_empty() {
+                self.bind_connected_devices(to_bind);
+            }
+
+            let _ = self
+                .auto_attacher
+                .borrow_mut()
+                .attach_matching_devices_in(&devices);
+        }
+
+        if self.settings.borrow().notify_attach_changes {
+            self.notify_attach_changes(&devices);
+        }
+        if self.settings.borrow().offer_attach_on_connect {
+            self.offer_attach_with_devices(&devices);
+        }
+        *self.devices.borrow_mut() = devices.clone();
+
+        if self.is_refresh_paused() {
+            self.refresh_pending.set(true);
+            return;
+        }
+        self.refresh_with_devices(&devices);
+    }
+
+    /// Binds newly connected devices that match an auto-bind rule, in a single elevated
+    /// operation on a background thread. Completes in [`UsbipdGui::background_command_done`].
+    ///
+    /// If another command is running, the devices are bound once it completes.
+    fn bind_connected_devices(&self, devices: Vec<UsbDevice>) {
+        logging::info(format_args!(
+            "Binding {} newly connected device(s) on connect",
+            devices.len()
+        ));
+
+        let command_devices = devices.clone();
+        let started = self.start_command(move || {
+            usbipd::bind_all_and_wait(&command_devices.iter().collect::<Vec<_>>())
+        });
+        if started {
+            return;
+        }
+
+        logging::info(format_args!(
+            "Another command is running, binding the devices once it completes"
+        ));
+        let mut pending = self.pending_auto_bind.borrow_mut();
+        for device in devices {
+            if !pending.iter().any(|d| d.instance_id == device.instance_id) {
+                pending.push(device);
+            }
+        }
+    }
+
+    /// Binds the devices to bind on connect that waited for another command to complete.
+    fn bind_pending_devices(&self) {
+        let devices = self.pending_auto_bind.take();
+        if !devices.is_empty() {
+            self.bind_connected_devices(devices);
+        }
+    }
+
+    /// Refreshes the view periodically, to pick up changes that do not trigger
+    /// USB device notifications, e.g. a device being detached from WSL.
+    ///
+    /// Nothing is done while the window is hidden, to avoid running `usbipd` needlessly.
+    fn auto_refresh(&self) {
+        if !self.window.visible() || self.background_command.is_running() {
+            return;
+        }
+
+        self.refresh();
+    }
+
+    /// Restarts auto-attach processes that exited, and refreshes the view if any did.
+    fn check_auto_attach_processes(&self) {
+        if self.auto_attacher.borrow_mut().restart_exited() {
+            self.auto_attach_tab_content.refresh();
+        }
+    }
+
+    /// Shows the number of connected and attached devices in the tray icon tooltip.
+    fn update_tray_tip(&self) {
+        let (connected, attached) = self.connected_tab_content.device_counts();
+        self.tray.set_tip(&format!(
+            "WSL USB Manager\n{connected} connected, {attached} attached"
+        ));
+    }
+
+    /// Refreshes all tabs, showing the progress on the toolbar button until the device list
+    /// is retrieved.
+    fn refresh_from_toolbar(&self) {
+        self.refresh_button.set_enabled(false);
+        self.refresh_button.set_text("Refreshing...");
+        self.refresh();
+    }
+
+    /// Refreshes all tabs once the device list is retrieved in the background.
     fn refresh(&self) {
+        self.retrieve_device_list();
+    }
+
+    /// Refreshes all tabs with an already retrieved device list.
+    fn refresh_with_devices(&self, devices: &[UsbDevice]) {
+        self.connected_tab_content.refresh_with_devices(devices);
+        self.persisted_tab_content.refresh_with_devices(devices);
+        self.auto_attach_tab_content.refresh_with_devices(devices);
+        self.update_tray_tip();
+    }
+
+    /// Shows a tray notification for each bound device of `devices` that was attached
+    /// or detached since the last time this function was called.
+    fn notify_attach_changes(&self, devices: &[UsbDevice]) {
+        let mut attach_states = self.attach_states.borrow_mut();
+        let mut new_states = HashMap::new();
+
+        for device in devices.iter().filter(|d| d.is_bound()) {
+            let instance_id = match &device.instance_id {
+                Some(instance_id) => instance_id,
+                None => continue,
+            };
+
+            let attached = device.is_attached();
+            new_states.insert(instance_id.clone(), attached);
+
+            // Only notify about changes, not about devices seen for the first time
+            if attach_states
+                .get(instance_id)
+                .is_some_and(|a| *a != attached)
+            {
+                let description = device_name(&self.settings.borrow(), device);
+                let (title, text) = if attached {
+                    (
+                        "Device attached",
+                        format!("{description} was attached to WSL."),
+                    )
+                } else {
+                    (
+                        "Device detached",
+                        format!("{description} was detached from WSL."),
+                    )
+                };
+
+                self.tray.show(
+                    &text,
+                    Some(title),
+                    Some(nwg::TrayNotificationFlags::INFO_ICON),
+                    None,
+                );
+            }
+        }
+
+        *attach_states = new_states;
+    }
+
+    /// Shows a toast offering to attach a bound device of `devices` that was connected
+    /// since the last device list, with a button for the default distribution and one
+    /// for each installed distribution.
+    ///
+    /// Devices matching an auto-attach profile are left to it.
+    fn offer_attach_with_devices(&self, devices: &[UsbDevice]) {
+        let previous = match self
+            .connected_bound_devices
+            .replace(Some(bound_instance_ids(devices)))
+        {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let profiles = self.auto_attacher.borrow().profiles();
+        let device = devices.iter().find(|d| {
+            d.is_bound()
+                && !d.is_attached()
+                && d.instance_id
+                    .as_ref()
+                    .is_some_and(|id| !previous.contains(id))
+                && !profiles.iter().any(|p| p.matches(d))
+        });
+        let device = match device {
+            Some(device) => device.clone(),
+            None => return,
+        };
+
+        let text = format!(
+            "{} was connected. Attach it to WSL?",
+            device_name(&self.settings.borrow(), &device)
+        );
+        let attach_offer_toast = self.attach_offer_toast.clone();
+        let activation = self.attach_offer_activation.clone();
+        let notice = self.attach_offer_notice.sender();
+
+        // Listing the distributions runs `wsl`, which can take a while
+        std::thread::spawn(move || {
+            let buttons: Vec<ToastButton> = std::iter::once(None)
+                .chain(wsl::list_distributions().into_iter().map(Some))
+                .take(MAX_TOAST_BUTTONS)
+                .map(|distribution| ToastButton {
+                    label: match &distribution {
+                        Some(distribution) => format!("Attach to {distribution}"),
+                        None => "Attach".to_owned(),
+                    },
+                    argument: serde_json::to_string(&AttachOffer {
+                        device: device.clone(),
+                        distribution,
+                    })
+                    .unwrap_or_default(),
+                })
+                .collect();
+
+            let result = toast::show("Device connected", &text, &buttons, move |argument| {
+                *activation.lock().unwrap() = Some(argument);
+                notice.notice();
+            });
+            match result {
+                Ok(toast) => *attach_offer_toast.lock().unwrap() = Some(toast),
+                Err(err) => logging::error(format_args!("{err}")),
+            }
+        });
+    }
+
+    /// Attaches the device offered by the clicked button of an attach offer toast.
+    /// Clicking the toast itself shows the window.
+    fn attach_offer_activated(&self) {
+        let argument = match self.attach_offer_activation.lock().unwrap().take() {
+            Some(argument) => argument,
+            None => return,
+        };
+
+        let offer: AttachOffer = match serde_json::from_str(&argument) {
+            Ok(offer) => offer,
+            Err(_) => {
+                self.bring_to_front();
+                return;
+            }
+        };
+
+        let name = device_name(&self.settings.borrow(), &offer.device);
+        let command_name = name.clone();
+        let started = self.start_command(move || {
+            run_tray_device_action(
+                &offer.device,
+                &command_name,
+                TrayDeviceAction::Attach(offer.distribution),
+            )
+        });
+        if !started {
+            nwg::modal_error_message(
+                &self.window,
+                "WSL USB Manager: Command Error",
+                &format!("Another command is running, {name} was not attached."),
+            );
+        }
+    }
+
+    fn toggle_attach_notifications(&self) {
+        let enabled = !self.menu_options_notify.checked();
+        self.menu_options_notify.set_checked(enabled);
+
+        // Start tracking from the current state
+        self.attach_states.borrow_mut().clear();
+        if enabled {
+            self.notify_attach_changes(&self.devices.borrow());
+        }
+
+        let mut settings = self.settings.borrow_mut();
+        settings.notify_attach_changes = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn toggle_offer_attach_on_connect(&self) {
+        let enabled = !self.menu_options_offer_attach.checked();
+        self.menu_options_offer_attach.set_checked(enabled);
+
+        // Only devices connected from now on are offered
+        *self.connected_bound_devices.borrow_mut() =
+            enabled.then(|| bound_instance_ids(&self.devices.borrow()));
+        if !enabled {
+            self.attach_offer_toast.lock().unwrap().take();
+        }
+
+        let mut settings = self.settings.borrow_mut();
+        settings.offer_attach_on_connect = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn toggle_start_with_windows(&self) {
+        let enabled = !self.menu_options_start_with_windows.checked();
+        if let Err(err) = win_utils::set_start_with_windows(enabled) {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+            return;
+        }
+        self.menu_options_start_with_windows.set_checked(enabled);
+
+        let mut settings = self.settings.borrow_mut();
+        settings.start_with_windows = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn toggle_auto_refresh(&self) {
+        let enabled = !self.menu_options_auto_refresh.checked();
+        self.menu_options_auto_refresh.set_checked(enabled);
+
+        if enabled {
+            self.auto_refresh_timer.start();
+        } else {
+            self.auto_refresh_timer.stop();
+        }
+
+        let mut settings = self.settings.borrow_mut();
+        settings.auto_refresh = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn toggle_pause_refresh_while_hidden(&self) {
+        let enabled = !self.menu_options_pause_refresh.checked();
+        self.menu_options_pause_refresh.set_checked(enabled);
+
+        let mut settings = self.settings.borrow_mut();
+        settings.pause_refresh_while_hidden = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn toggle_detach_all_on_exit(&self) {
+        let enabled = !self.menu_options_detach_on_exit.checked();
+        self.menu_options_detach_on_exit.set_checked(enabled);
+
+        let mut settings = self.settings.borrow_mut();
+        settings.detach_all_on_exit = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn toggle_hide_hubs(&self) {
+        let enabled = !self.menu_options_hide_hubs.checked();
+        self.menu_options_hide_hubs.set_checked(enabled);
+
+        {
+            let mut settings = self.settings.borrow_mut();
+            settings.hide_hubs = enabled;
+            if let Err(err) = settings.save() {
+                nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+            }
+        }
+
         self.connected_tab_content.refresh();
-        self.persisted_tab_content.refresh();
-        self.auto_attach_tab_content.refresh();
+    }
+
+    fn toggle_always_on_top(&self) {
+        let enabled = !self.menu_view_always_on_top.checked();
+        self.menu_view_always_on_top.set_checked(enabled);
+        self.window.set_always_on_top(enabled);
+
+        let mut settings = self.settings.borrow_mut();
+        settings.always_on_top = enabled;
+        if let Err(err) = settings.save() {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
+    }
+
+    fn export_configuration(&self) {
+        if !self.export_dialog.run(Some(&self.window)) {
+            return;
+        }
+        let mut path = match self.export_dialog.get_selected_item() {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return,
+        };
+        if path.extension().is_none() {
+            path.set_extension("json");
+        }
+
+        let configuration = Configuration::new(
+            self.settings.borrow().clone(),
+            self.auto_attacher.borrow().profiles(),
+        );
+        if let Err(err) = configuration.export(&path) {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Export Error", &err);
+        }
+    }
+
+    /// Imports the settings and auto-attach profiles from a configuration file.
+    /// The user chooses whether to replace the existing profiles or to merge them.
+    fn import_configuration(&self) {
+        if !self.import_dialog.run(Some(&self.window)) {
+            return;
+        }
+        let path = match self.import_dialog.get_selected_item() {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return,
+        };
+
+        let configuration = match Configuration::import(&path) {
+            Ok(configuration) => configuration,
+            Err(err) => {
+                nwg::modal_error_message(&self.window, "WSL USB Manager: Import Error", &err);
+                return;
+            }
+        };
+
+        let choice = nwg::modal_message(
+            &self.window,
+            &nwg::MessageParams {
+                title: "WSL USB Manager: Import Configuration",
+                content: concat!(
+                    "Replace the existing auto attach profiles with the imported ones?\n\n",
+                    "Choose No to keep the existing profiles and add the imported ones."
+                ),
+                buttons: nwg::MessageButtons::YesNoCancel,
+                icons: nwg::MessageIcons::Question,
+            },
+        );
+        let replace = match choice {
+            nwg::MessageChoice::Yes => true,
+            nwg::MessageChoice::No => false,
+            _ => return,
+        };
+
+        {
+            let mut settings = self.settings.borrow_mut();
+            let mut imported = configuration.settings;
+
+            // The window layout depends on the monitors of this machine
+            imported.window_rect = settings.window_rect;
+            imported.connected_column_widths = settings.connected_column_widths.take();
+            // The column widths only match the columns shown here
+            imported.connected_columns = std::mem::take(&mut settings.connected_columns);
+            // The installed distributions differ between machines
+            imported.attach_target = settings.attach_target.clone();
+            *settings = imported;
+
+            settings.apply_usbipd_settings();
+            let result = win_utils::set_start_with_windows(settings.start_with_windows)
+                .and_then(|_| settings.save());
+            if let Err(err) = result {
+                nwg::modal_error_message(&self.window, "WSL USB Manager: Import Error", &err);
+            }
+        }
+        self.apply_settings();
+
+        let result = self
+            .auto_attacher
+            .borrow_mut()
+            .import_profiles(configuration.auto_attach_profiles, replace);
+        if let Err(err) = result {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Import Error", &err);
+        }
+
+        self.refresh();
+    }
+
+    fn show_about(&self) {
+        about::show_about(self.window.handle);
+    }
+
+    /// Collects diagnostics for bug reports on a background thread.
+    /// Completes in [`UsbipdGui::diagnostics_done`].
+    fn generate_diagnostics(&self) {
+        self.diagnostics_command.start(
+            &self.window.handle,
+            self.diagnostics_notice.sender(),
+            diagnostics::collect,
+        );
+    }
+
+    /// Shows where the diagnostics were saved and opens their folder.
+    fn diagnostics_done(&self) {
+        let path = match self.diagnostics_command.finish() {
+            Some(Ok(path)) => path,
+            Some(Err(err)) => {
+                nwg::modal_error_message(&self.window, "WSL USB Manager: Diagnostics Error", &err);
+                return;
+            }
+            None => return,
+        };
+
+        nwg::modal_info_message(
+            &self.window,
+            "WSL USB Manager: Diagnostics",
+            &format!(
+                concat!(
+                    "Diagnostics were saved to:\n{}\n\n",
+                    "They include the usbipd device list, the logs and the settings, ",
+                    "see README.txt inside for details. Nothing is redacted, ",
+                    "review them before attaching them to a bug report."
+                ),
+                path.display()
+            ),
+        );
+        if let Some(folder) = path.parent() {
+            let _ = win_utils::open_folder(folder);
+        }
+    }
+
+    /// Opens the folder holding the settings and the log file in Explorer.
+    fn open_settings_folder(&self) {
+        let result = settings::ensure_settings_dir().and_then(|dir| win_utils::open_folder(&dir));
+        if let Err(err) = result {
+            nwg::modal_error_message(&self.window, "WSL USB Manager: Settings Error", &err);
+        }
     }
 
     fn exit(&self) {
+        self.save_window_layout();
+
+        if self.settings.borrow().detach_all_on_exit {
+            // Stop auto-attach processes first, so that they do not attach the devices again
+            self.auto_attacher.borrow_mut().stop_all();
+            usbipd::detach_all_within(DETACH_ON_EXIT_TIMEOUT);
+        }
+
+        // Release the hotkey right away, so that it is not held while the app shuts down
+        if let Some(handler) = self.hotkey_handler.borrow_mut().take() {
+            let _ = nwg::unbind_raw_event_handler(&handler);
+        }
+        self.toggle_window_hotkey.borrow_mut().take();
+
         nwg::stop_thread_dispatch();
     }
 }
+
+/// Runs `action` on `device`, named `name` in errors, from the tray or a toast.
+///
+/// Tray menus and toasts show a snapshot, the device might have been unplugged
+/// or plugged into another port since. Its current state is looked up first.
+fn run_tray_device_action(
+    device: &UsbDevice,
+    name: &str,
+    action: TrayDeviceAction,
+) -> Result<(), String> {
+    let stable_key = device.stable_key();
+    let device = usbipd::list_devices()?
+        .into_iter()
+        .filter(|d| d.is_connected())
+        .find(|d| {
+            d.instance_id == device.instance_id
+                || (stable_key.is_some() && d.stable_key() == stable_key)
+        })
+        .ok_or_else(|| format!("{name} is no longer connected."))?;
+
+    match action {
+        TrayDeviceAction::Detach if device.is_attached() => {
+            device.detach()?;
+            device.wait(|d| d.is_some_and(|d| !d.is_attached()))
+        }
+        TrayDeviceAction::Attach(distribution) if !device.is_attached() => {
+            device.attach(distribution.as_deref())?;
+            device.wait(|d| d.is_some_and(|d| d.is_attached()))
+        }
+        // Already in the requested state
+        _ => Ok(()),
+    }
+}
+
+/// Returns the instance IDs of the bound devices of `devices`.
+fn bound_instance_ids(devices: &[UsbDevice]) -> HashSet<String> {
+    devices
+        .iter()
+        .filter(|d| d.is_bound())
+        .filter_map(|d| d.instance_id.clone())
+        .collect()
+}
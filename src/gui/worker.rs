@@ -0,0 +1,102 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+};
+
+use native_windows_gui as nwg;
+
+/// Runs `task` on a background thread and triggers `notice` once it completes.
+///
+/// The result of the task can be retrieved from the returned receiver
+/// in the `OnNotice` event handler.
+pub fn spawn<T, F>(notice: nwg::NoticeSender, task: F) -> Receiver<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let _ = sender.send(task());
+        notice.notice();
+    });
+
+    receiver
+}
+
+/// Shows the wait cursor while the mouse is over `window`.
+///
+/// The returned handler must be unbound with [`nwg::unbind_event_handler`]
+/// to restore the default cursor.
+pub fn bind_wait_cursor(window: &nwg::ControlHandle) -> nwg::EventHandler {
+    let wait_cursor = nwg::Cursor::from_system(nwg::OemCursor::Wait);
+    nwg::full_bind_event_handler(window, move |event, _event_data, _handle| match event {
+        nwg::Event::OnMousePress(_) | nwg::Event::OnMouseMove => {
+            nwg::GlobalCursor::set(&wait_cursor)
+        }
+        _ => {}
+    })
+}
+
+/// A command running on a background thread, which shows the wait cursor until it completes.
+///
+/// Only one command can run at a time. Clones share the same running command.
+pub struct BackgroundCommand<T> {
+    receiver: Rc<RefCell<Option<Receiver<T>>>>,
+    cursor_handler: Rc<RefCell<Option<nwg::EventHandler>>>,
+}
+
+impl<T> Default for BackgroundCommand<T> {
+    fn default() -> Self {
+        Self {
+            receiver: Default::default(),
+            cursor_handler: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for BackgroundCommand<T> {
+    fn clone(&self) -> Self {
+        Self {
+            receiver: self.receiver.clone(),
+            cursor_handler: self.cursor_handler.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> BackgroundCommand<T> {
+    /// Returns whether a command is currently running.
+    pub fn is_running(&self) -> bool {
+        self.receiver.borrow().is_some()
+    }
+
+    /// Starts running `task` on a background thread, showing the wait cursor on `window`.
+    /// `notice` is triggered once the task completes, its handler should call [`Self::finish`].
+    ///
+    /// Returns `false` without running `task` if a command is already running.
+    pub fn start<F>(&self, window: &nwg::ControlHandle, notice: nwg::NoticeSender, task: F) -> bool
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if self.is_running() {
+            return false;
+        }
+
+        *self.receiver.borrow_mut() = Some(spawn(notice, task));
+        *self.cursor_handler.borrow_mut() = Some(bind_wait_cursor(window));
+        true
+    }
+
+    /// Returns the result of the completed command and restores the cursor.
+    pub fn finish(&self) -> Option<T> {
+        if let Some(handler) = self.cursor_handler.borrow_mut().take() {
+            nwg::unbind_event_handler(&handler);
+        }
+
+        self.receiver
+            .borrow_mut()
+            .take()
+            .and_then(|receiver| receiver.try_recv().ok())
+    }
+}
@@ -0,0 +1,141 @@
+//! This module provides a minimal log file for troubleshooting.
+//!
+//! The log is written to the settings directory and rotated when it grows too large,
+//! keeping a single previous log next to it.
+
+use std::fmt::Arguments;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use windows_sys::Win32::Foundation::SYSTEMTIME;
+use windows_sys::Win32::System::SystemInformation::GetLocalTime;
+
+use crate::settings;
+
+/// The name of the log file, inside the settings directory.
+const LOG_FILE_NAME: &str = "wsl-usb-manager.log";
+
+/// The name the log file is renamed to when it is rotated.
+const OLD_LOG_FILE_NAME: &str = "wsl-usb-manager.old.log";
+
+/// The size in bytes after which the log file is rotated.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+struct Logger {
+    dir: PathBuf,
+    file: File,
+    /// Whether entries are also written to stderr
+    mirror_to_stderr: bool,
+    /// Whether debug entries are written
+    verbose: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Level {
+    Debug,
+    Info,
+    Error,
+}
+
+/// Opens the log file, rotating it if it is too large.
+///
+/// If `verbose` is `true`, debug entries are written too, and all entries are also written
+/// to stderr in debug builds.
+/// Logging is silently disabled if the log file cannot be opened.
+pub fn init(verbose: bool) {
+    let dir = match settings::ensure_settings_dir() {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+
+    let path = dir.join(LOG_FILE_NAME);
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_SIZE) {
+        let _ = std::fs::rename(&path, dir.join(OLD_LOG_FILE_NAME));
+    }
+
+    let file = match OpenOptions::new().create(true).append(true).open(&path) {
+        Ok(file) => file,
+        Err(_) => return,
+    };
+
+    *LOGGER.lock().unwrap() = Some(Logger {
+        dir,
+        file,
+        mirror_to_stderr: verbose && cfg!(debug_assertions),
+        verbose,
+    });
+
+    info(format_args!(
+        "WSL USB Manager {} started",
+        env!("CARGO_PKG_VERSION")
+    ));
+}
+
+/// Writes a debug entry to the log, only if verbose logging is enabled.
+pub fn debug(message: Arguments) {
+    write(Level::Debug, message);
+}
+
+/// Writes an informational entry to the log.
+pub fn info(message: Arguments) {
+    write(Level::Info, message);
+}
+
+/// Writes an error entry to the log.
+pub fn error(message: Arguments) {
+    write(Level::Error, message);
+}
+
+fn write(level: Level, message: Arguments) {
+    let mut logger = LOGGER.lock().unwrap();
+    let logger = match logger.as_mut() {
+        Some(logger) => logger,
+        None => return,
+    };
+
+    let level = match level {
+        Level::Debug if !logger.verbose => return,
+        Level::Debug => "DEBUG",
+        Level::Info => "INFO ",
+        Level::Error => "ERROR",
+    };
+    // Keep multi-line messages (e.g. usbipd errors) on a single line
+    let message = message.to_string().replace(['\r', '\n'], " ");
+    let line = format!("{} {level} {}\n", timestamp(), message.trim());
+
+    if logger.mirror_to_stderr {
+        eprint!("{line}");
+    }
+
+    let _ = logger.file.write_all(line.as_bytes());
+
+    // Rotate the log if it grew too large while running
+    if logger.file.metadata().is_ok_and(|m| m.len() > MAX_LOG_SIZE) {
+        let path = logger.dir.join(LOG_FILE_NAME);
+        let _ = std::fs::rename(&path, logger.dir.join(OLD_LOG_FILE_NAME));
+        if let Ok(file) = OpenOptions::new().create(true).append(true).open(&path) {
+            logger.file = file;
+        }
+    }
+}
+
+/// Returns the current local time formatted as `YYYY-MM-DD HH:MM:SS.mmm`.
+fn timestamp() -> String {
+    let mut time: SYSTEMTIME = unsafe { std::mem::zeroed() };
+    unsafe { GetLocalTime(&mut time as *mut _) };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        time.wYear,
+        time.wMonth,
+        time.wDay,
+        time.wHour,
+        time.wMinute,
+        time.wSecond,
+        time.wMilliseconds
+    )
+}
@@ -1,37 +1,125 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 #![cfg(target_os = "windows")]
 
+mod args;
 mod auto_attach;
+mod cli;
+mod config;
+mod diagnostics;
 mod gui;
+mod logging;
+mod settings;
+mod toast;
 mod usbipd;
 mod win_utils;
+mod wsl;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, process::ExitCode, rc::Rc};
 
+use args::Args;
 use auto_attach::AutoAttacher;
+use settings::Settings;
+use usbipd::VersionSupport;
+
+fn main() -> ExitCode {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            win_utils::attach_parent_console();
+            eprintln!("error: {err}\n\n{}", args::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    // The log is written to the settings directory, which depends on portable mode
+    settings::init_portable(args.portable);
+    logging::init(args.verbose);
+
+    let settings = Rc::new(RefCell::new(Settings::load()));
+
+    settings.borrow().apply_usbipd_settings();
+
+    // Run the requested command without starting the GUI
+    if let Some(command) = args.command {
+        win_utils::attach_parent_console();
+        return cli::run(command);
+    }
 
-fn main() {
     // Ensure that only one instance of the application is running
     if !win_utils::acquire_single_instance_lock() {
+        // Bring the running instance forward instead
+        if win_utils::request_show_window() {
+            return ExitCode::SUCCESS;
+        }
         gui::show_multiple_instance_warning();
-        return;
+        return ExitCode::FAILURE;
     }
 
+    // Let the user locate usbipd if it is not installed in the usual places
     if !usbipd::check_installed() {
-        gui::show_usbipd_not_found_error();
-        return;
+        while !usbipd::check_installed() {
+            match gui::locate_usbipd() {
+                Some(path) => {
+                    usbipd::set_path(Some(&path));
+                    settings.borrow_mut().usbipd_path = Some(path);
+                }
+                None => return ExitCode::FAILURE,
+            }
+        }
+
+        if let Err(err) = settings.borrow().save() {
+            logging::error(format_args!("Failed to save the usbipd path: {err}"));
+        }
+    }
+
+    match usbipd::version() {
+        Ok(version) => match usbipd::is_supported(&version) {
+            VersionSupport::Unsupported => {
+                gui::show_usbipd_unsupported_version_error(&version);
+                return ExitCode::FAILURE;
+            }
+            VersionSupport::Untested => {
+                logging::info(format_args!("Running with untested usbipd {version}"));
+
+                // Warn again once an even newer major version is installed
+                let acknowledged = settings
+                    .borrow()
+                    .acknowledged_usbipd_major
+                    .is_some_and(|major| major >= version.major);
+                if !acknowledged && gui::show_usbipd_untested_version_warning(&version) {
+                    let mut settings = settings.borrow_mut();
+                    settings.acknowledged_usbipd_major = Some(version.major);
+                    if let Err(err) = settings.save() {
+                        logging::error(format_args!("Failed to save the settings: {err}"));
+                    }
+                }
+            }
+            VersionSupport::Supported => {}
+        },
+        Err(err) => {
+            gui::show_start_failure(&err.to_string());
+            return ExitCode::FAILURE;
+        }
     }
 
-    if usbipd::version().major < 4 {
-        gui::show_usbipd_untested_version_warning();
-        return;
+    // Keep the startup registration pointing at the current executable, in case it was moved
+    if settings.borrow().start_with_windows {
+        if let Err(err) = win_utils::set_start_with_windows(true) {
+            logging::error(format_args!("{err}"));
+        }
     }
 
+    // The command line flag takes precedence over the persisted setting
+    let start_minimized = args.minimized || settings.borrow().start_minimized;
+
     let auto_attacher = Rc::new(RefCell::new(AutoAttacher::new()));
 
-    let start = gui::start(&auto_attacher);
+    let start = gui::start(&auto_attacher, &settings, start_minimized);
 
     if let Err(err) = start {
         gui::show_start_failure(&err.to_string());
+        return ExitCode::FAILURE;
     }
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,280 @@
+//! This module provides the application settings and access to the settings directory.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::logging;
+use crate::usbipd;
+
+/// The name of the settings directory, created inside `%LOCALAPPDATA%`.
+const SETTINGS_DIR_NAME: &str = "WSL USB Manager";
+
+/// The name of the settings file, inside the settings directory.
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// The name of the copy kept of a settings file that could not be loaded,
+/// inside the settings directory.
+const SETTINGS_BACKUP_FILE_NAME: &str = "settings.backup.json";
+
+/// The name of the file that enables portable mode when placed next to the executable.
+const PORTABLE_MARKER_FILE_NAME: &str = "portable.txt";
+
+/// Whether the settings directory is the directory of the executable, see [`init_portable`].
+static PORTABLE: AtomicBool = AtomicBool::new(false);
+
+/// The persistent application settings.
+///
+/// Missing fields are filled in with their default values when loading,
+/// so that settings files written by older versions can still be read.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// Whether the app starts minimized to the tray.
+    pub start_minimized: bool,
+
+    /// Whether the app is started minimized when the user logs in.
+    pub start_with_windows: bool,
+
+    /// A custom path to the `usbipd` executable.
+    pub usbipd_path: Option<PathBuf>,
+
+    /// Whether to ask for confirmation before detaching or unbinding a device.
+    pub confirm_before_detach: bool,
+
+    /// The WSL distribution devices are attached to from the connected devices list and the tray.
+    pub attach_target: AttachTarget,
+
+    /// The command run inside WSL before adding an auto-attach profile, to check that
+    /// attached devices will be visible there. An empty command disables the check.
+    pub wsl_probe_command: String,
+
+    /// Whether to show a notification when a bound device is attached or detached.
+    pub notify_attach_changes: bool,
+
+    /// Whether connecting a bound device shows a notification with buttons to attach it.
+    pub offer_attach_on_connect: bool,
+
+    /// Whether the device lists are refreshed periodically while the window is visible.
+    pub auto_refresh: bool,
+
+    /// The interval between periodic refreshes, in seconds.
+    pub auto_refresh_interval_secs: u64,
+
+    /// Whether the device lists are left as they are while the window is hidden,
+    /// and refreshed once it is shown again.
+    pub pause_refresh_while_hidden: bool,
+
+    /// Whether all attached devices are detached when exiting the app.
+    pub detach_all_on_exit: bool,
+
+    /// Whether USB hubs are hidden from the connected devices list.
+    pub hide_hubs: bool,
+
+    /// Whether the main window stays above other windows.
+    pub always_on_top: bool,
+
+    /// The newest major version of `usbipd` the user chose not to be warned about,
+    /// despite it being newer than the versions the app was tested with.
+    pub acknowledged_usbipd_major: Option<u32>,
+
+    /// How long to wait for a device to reach the desired state after an operation,
+    /// in milliseconds. Slow systems might need more time, e.g. for devices behind hubs.
+    pub wait_timeout_ms: u64,
+
+    /// The initial interval between device state checks while waiting, in milliseconds.
+    pub wait_poll_interval_ms: u64,
+
+    /// The global hotkey that shows or hides the main window, e.g. `Ctrl+Alt+U`.
+    /// Disabled if `None`.
+    pub toggle_window_hotkey: Option<String>,
+
+    /// The optional columns shown in the connected devices list, by key, e.g. `vid_pid`.
+    pub connected_columns: Vec<String>,
+
+    /// The column widths of the connected devices list, in physical pixels.
+    pub connected_column_widths: Option<Vec<isize>>,
+
+    /// The last main window rectangle as `[left, top, right, bottom]`, in physical pixels.
+    pub window_rect: Option<[i32; 4]>,
+
+    /// Custom names shown instead of the device descriptions, keyed by [`crate::usbipd::stable_key`].
+    pub nicknames: HashMap<String, String>,
+
+    /// When each device was last attached, in seconds since the Unix epoch,
+    /// keyed by [`crate::usbipd::stable_key`].
+    pub last_attached: HashMap<String, u64>,
+}
+
+/// The WSL distribution devices are attached to.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub enum AttachTarget {
+    /// The default WSL distribution.
+    #[default]
+    Default,
+    /// The distribution with the given name.
+    Distribution(String),
+    /// A distribution chosen each time devices are attached.
+    Ask,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            start_minimized: false,
+            start_with_windows: false,
+            usbipd_path: None,
+            confirm_before_detach: true,
+            attach_target: AttachTarget::Default,
+            wsl_probe_command: "lsusb".to_owned(),
+            notify_attach_changes: false,
+            offer_attach_on_connect: false,
+            auto_refresh: false,
+            auto_refresh_interval_secs: 5,
+            pause_refresh_while_hidden: false,
+            detach_all_on_exit: false,
+            hide_hubs: false,
+            always_on_top: false,
+            acknowledged_usbipd_major: None,
+            wait_timeout_ms: usbipd::DEFAULT_WAIT_TIMEOUT.as_millis() as u64,
+            wait_poll_interval_ms: usbipd::DEFAULT_WAIT_POLL_INTERVAL.as_millis() as u64,
+            toggle_window_hotkey: None,
+            connected_columns: Vec::new(),
+            connected_column_widths: None,
+            window_rect: None,
+            nicknames: HashMap::new(),
+            last_attached: HashMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings from the settings file.
+    ///
+    /// A missing or corrupt file results in the default settings. A corrupt file is copied
+    /// to [`SETTINGS_BACKUP_FILE_NAME`] first, as saving the settings overwrites it.
+    pub fn load() -> Self {
+        let path = match ensure_settings_dir() {
+            Ok(dir) => dir.join(SETTINGS_FILE_NAME),
+            Err(err) => {
+                logging::error(format_args!("{err}"));
+                return Self::default();
+            }
+        };
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    logging::error(format_args!("Failed to read the settings: {err}"));
+                }
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_str(&json) {
+            Ok(settings) => settings,
+            Err(err) => {
+                logging::error(format_args!("Failed to load the settings: {err}"));
+
+                let backup = path.with_file_name(SETTINGS_BACKUP_FILE_NAME);
+                match std::fs::copy(&path, &backup) {
+                    Ok(_) => logging::info(format_args!(
+                        "Kept a copy of the settings at {}",
+                        backup.display()
+                    )),
+                    Err(err) => {
+                        logging::error(format_args!("Failed to keep a copy of the settings: {err}"))
+                    }
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// Saves the settings to the settings file.
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(ensure_settings_dir()?.join(SETTINGS_FILE_NAME), json)
+            .map_err(|err| format!("Failed to save settings: {err}"))
+    }
+
+    /// Returns the nickname stored under `key`, if any.
+    pub fn nickname(&self, key: Option<&str>) -> Option<&str> {
+        self.nicknames.get(key?).map(String::as_str)
+    }
+
+    /// Returns when the device with the given key was last attached,
+    /// in seconds since the Unix epoch, if known.
+    pub fn last_attached(&self, key: Option<&str>) -> Option<u64> {
+        self.last_attached.get(key?).copied()
+    }
+
+    /// Records that the device with the given key was attached just now.
+    pub fn set_attached_now(&mut self, key: String) {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.last_attached.insert(key, now);
+    }
+
+    /// Applies the settings of the `usbipd` operations.
+    pub fn apply_usbipd_settings(&self) {
+        usbipd::set_path(self.usbipd_path.as_deref());
+        usbipd::set_wait_timing(
+            Duration::from_millis(self.wait_timeout_ms),
+            Duration::from_millis(self.wait_poll_interval_ms),
+        );
+    }
+
+    /// Stores `nickname` under `key`. An empty nickname removes the stored one.
+    pub fn set_nickname(&mut self, key: String, nickname: &str) {
+        let nickname = nickname.trim();
+        if nickname.is_empty() {
+            self.nicknames.remove(&key);
+        } else {
+            self.nicknames.insert(key, nickname.to_owned());
+        }
+    }
+}
+
+/// Enables portable mode if `portable` is `true`, or if a `portable.txt` file
+/// is next to the executable. Must be called before the settings directory is used.
+///
+/// In portable mode, the settings, the logs and the auto-attach profiles are stored
+/// next to the executable instead of in `%LOCALAPPDATA%`, e.g. to run the app from a USB stick.
+pub fn init_portable(portable: bool) {
+    let portable =
+        portable || exe_dir().is_some_and(|dir| dir.join(PORTABLE_MARKER_FILE_NAME).is_file());
+    PORTABLE.store(portable, Ordering::Relaxed);
+}
+
+/// Returns whether the app runs in portable mode, see [`init_portable`].
+pub fn is_portable() -> bool {
+    PORTABLE.load(Ordering::Relaxed)
+}
+
+/// Returns the directory containing the executable.
+fn exe_dir() -> Option<PathBuf> {
+    let exe_path = std::env::current_exe().ok()?;
+    exe_path.parent().map(PathBuf::from)
+}
+
+/// Returns the path of the settings directory, creating it if it does not exist.
+///
+/// This is the directory of the executable in portable mode.
+pub fn ensure_settings_dir() -> Result<PathBuf, String> {
+    if is_portable() {
+        return exe_dir().ok_or("The directory of the executable could not be found.".to_owned());
+    }
+
+    let local_app_data = std::env::var_os("LOCALAPPDATA")
+        .ok_or("The LOCALAPPDATA environment variable is not set.".to_owned())?;
+
+    let dir = PathBuf::from(local_app_data).join(SETTINGS_DIR_NAME);
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+    Ok(dir)
+}
@@ -0,0 +1,191 @@
+//! This module shows toast notifications with buttons, through the Windows notifications API.
+//!
+//! Unlike tray notifications, toasts can offer actions. Clicking a button or the toast itself
+//! calls back the app with the argument of what was clicked.
+
+use windows::core::{IInspectable, Interface, HSTRING};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::Foundation::TypedEventHandler;
+use windows::UI::Notifications::{
+    ToastActivatedEventArgs, ToastNotification, ToastNotificationManager,
+};
+use windows_sys::Win32::{
+    Foundation::ERROR_SUCCESS,
+    System::Registry::{RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ},
+};
+
+use crate::win_utils::{get_error_string, to_wide};
+
+/// The application user model ID toasts are shown with.
+const APP_ID: &str = "nickbeth.WslUsbManager";
+
+/// The registry key registering [`APP_ID`], needed for toasts of apps that are not packaged.
+const APP_ID_KEY: &str = r"Software\Classes\AppUserModelId\nickbeth.WslUsbManager";
+
+/// A button of a toast notification.
+pub struct ToastButton {
+    pub label: String,
+    /// Passed to the activation callback when the button is clicked
+    pub argument: String,
+}
+
+/// A toast notification that was shown. Its activation callback is only called
+/// as long as it is kept.
+pub struct Toast {
+    _notification: ToastNotification,
+}
+
+/// Shows a toast notification with a title, a text and buttons.
+///
+/// `on_activated` is called on a background thread with the argument of the clicked button,
+/// or with an empty string if the toast itself was clicked.
+pub fn show<F>(
+    title: &str,
+    text: &str,
+    buttons: &[ToastButton],
+    on_activated: F,
+) -> Result<Toast, String>
+where
+    F: Fn(String) + Send + 'static,
+{
+    register_app_id()?;
+
+    let show_toast = || -> windows::core::Result<ToastNotification> {
+        let xml = XmlDocument::new()?;
+        xml.LoadXml(&HSTRING::from(toast_xml(title, text, buttons)))?;
+
+        let notification = ToastNotification::CreateToastNotification(&xml)?;
+        notification.Activated(&TypedEventHandler::new(
+            move |_: &Option<ToastNotification>, args: &Option<IInspectable>| {
+                let argument = match args {
+                    Some(args) => args.cast::<ToastActivatedEventArgs>()?.Arguments()?,
+                    None => HSTRING::new(),
+                };
+                on_activated(argument.to_string());
+                Ok(())
+            },
+        ))?;
+
+        ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?
+            .Show(&notification)?;
+        Ok(notification)
+    };
+
+    match show_toast() {
+        Ok(notification) => Ok(Toast {
+            _notification: notification,
+        }),
+        Err(err) => Err(format!("Failed to show the notification: {err}")),
+    }
+}
+
+/// Registers [`APP_ID`] with the name of the app, shown as the source of its toasts.
+///
+/// Registering again is harmless, so this is done before showing each toast.
+fn register_app_id() -> Result<(), String> {
+    let key = to_wide(APP_ID_KEY);
+    let value_name = to_wide("DisplayName");
+    let display_name = to_wide("WSL USB Manager");
+
+    let result = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value_name.as_ptr(),
+            REG_SZ,
+            display_name.as_ptr() as *const _,
+            (display_name.len() * std::mem::size_of::<u16>()) as u32,
+        )
+    };
+
+    if result == ERROR_SUCCESS {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to register the app for notifications: {}",
+            get_error_string(result)
+        ))
+    }
+}
+
+/// Returns the XML describing a toast with a title, a text and buttons.
+fn toast_xml(title: &str, text: &str, buttons: &[ToastButton]) -> String {
+    let actions: String = buttons
+        .iter()
+        .map(|button| {
+            format!(
+                r#"<action content="{}" arguments="{}"/>"#,
+                escape_xml(&button.label),
+                escape_xml(&button.argument)
+            )
+        })
+        .collect();
+
+    format!(
+        concat!(
+            r#"<toast launch=""><visual><binding template="ToastGeneric">"#,
+            "<text>{}</text><text>{}</text>",
+            "</binding></visual><actions>{}</actions></toast>"
+        ),
+        escape_xml(title),
+        escape_xml(text),
+        actions
+    )
+}
+
+/// Escapes the characters of `s` that are not allowed in XML text and attribute values.
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_xml_special_characters() {
+        assert_eq!(
+            escape_xml(r#"<"Tom" & 'Jerry'>"#),
+            "&lt;&quot;Tom&quot; &amp; &apos;Jerry&apos;&gt;"
+        );
+        assert_eq!(escape_xml("Ubuntu-22.04"), "Ubuntu-22.04");
+    }
+
+    #[test]
+    fn toast_xml_lists_buttons_in_order() {
+        let buttons = [
+            ToastButton {
+                label: "Attach".to_owned(),
+                argument: r#"{"distribution":null}"#.to_owned(),
+            },
+            ToastButton {
+                label: "Attach to Ubuntu".to_owned(),
+                argument: r#"{"distribution":"Ubuntu"}"#.to_owned(),
+            },
+        ];
+
+        assert_eq!(
+            toast_xml("Device connected", "Keyboard & mouse", &buttons),
+            concat!(
+                r#"<toast launch=""><visual><binding template="ToastGeneric">"#,
+                "<text>Device connected</text><text>Keyboard &amp; mouse</text>",
+                "</binding></visual><actions>",
+                r#"<action content="Attach" arguments="{&quot;distribution&quot;:null}"/>"#,
+                r#"<action content="Attach to Ubuntu" "#,
+                r#"arguments="{&quot;distribution&quot;:&quot;Ubuntu&quot;}"/>"#,
+                "</actions></toast>"
+            )
+        );
+    }
+}
@@ -1,21 +1,71 @@
 //! This module provides objects and functions for interacting with the `usbipd`
 //! executable and the USB devices it manages.
 
+use std::cmp::Ordering;
 use std::fmt::Display;
+use std::net::{Ipv4Addr, UdpSocket};
 use std::os::windows::process::CommandExt;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, OnceLock, RwLock};
 use std::time::{Duration, Instant};
 
-use serde::Deserialize;
-use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
-use windows_sys::Win32::UI::Shell::{ShellExecuteExW, SHELLEXECUTEINFOW, SHELLEXECUTEINFOW_0};
+use serde::{Deserialize, Serialize};
+use windows_sys::Win32::Devices::Usb::{UsbFullSpeed, UsbHighSpeed, UsbLowSpeed, UsbSuperSpeed};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_CANCELLED, HANDLE, WAIT_OBJECT_0,
+};
+use windows_sys::Win32::System::Threading::{
+    GetExitCodeProcess, WaitForSingleObject, CREATE_NO_WINDOW,
+};
+use windows_sys::Win32::UI::Shell::{
+    ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW, SHELLEXECUTEINFOW_0,
+};
 use windows_sys::Win32::UI::WindowsAndMessaging::SW_HIDE;
 
-use crate::win_utils::get_last_error_string;
+use crate::logging;
+use crate::win_utils::{self, get_error_string};
 
-/// The `usbipd` executable name.
+mod fake;
+mod list;
+
+/// The `usbipd` executable name, used to look it up in the system PATH.
 const USBIPD_EXE: &str = "usbipd";
 
+/// The path of the `usbipd` executable in a default installation,
+/// relative to the `Program Files` directory.
+const USBIPD_DEFAULT_PATH: &str = r"usbipd-win\usbipd.exe";
+
+/// How long to wait for a command run as administrator to complete.
+const ADMIN_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many times a command failing with [`UsbipError::NotReady`] is retried.
+const NOT_READY_RETRIES: u32 = 2;
+
+/// The delay before retrying a command failing with [`UsbipError::NotReady`],
+/// doubled before each further retry.
+const NOT_READY_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The default maximum time [`UsbDevice::wait`] waits for a device to reach the desired state.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The default initial interval between device list polls in [`UsbDevice::wait`].
+pub const DEFAULT_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The interval between device list polls in [`UsbDevice::wait`] never backs off past this.
+const MAX_WAIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The resolved path of the `usbipd` executable, see [`set_path`].
+static USBIPD_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// The version of `usbipd` once retrieved by [`version`], along with the path of the
+/// executable it was retrieved from.
+static VERSION: RwLock<Option<(PathBuf, Version)>> = RwLock::new(None);
+
+/// The timeout and initial poll interval of [`UsbDevice::wait`], see [`set_wait_timing`].
+static WAIT_TIMING: RwLock<(Duration, Duration)> =
+    RwLock::new((DEFAULT_WAIT_TIMEOUT, DEFAULT_WAIT_POLL_INTERVAL));
+
 /// An enum representing the state of a USB device in `usbipd`.
 pub enum UsbipState {
     None,
@@ -46,8 +96,116 @@ impl Display for UsbipState {
     }
 }
 
+impl UsbipState {
+    /// Returns whether the device was bound with `--force`.
+    pub fn is_forced(&self) -> bool {
+        matches!(self, UsbipState::Shared(true) | UsbipState::Attached(true))
+    }
+}
+
+/// The speed a USB device is operating at, from the slowest to the fastest.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum UsbSpeed {
+    Low,
+    Full,
+    High,
+    Super,
+}
+
+impl Display for UsbSpeed {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            UsbSpeed::Low => write!(fmt, "Low speed (USB 1.0)"),
+            UsbSpeed::Full => write!(fmt, "Full speed (USB 1.1)"),
+            UsbSpeed::High => write!(fmt, "High speed (USB 2.0)"),
+            UsbSpeed::Super => write!(fmt, "SuperSpeed (USB 3.0)"),
+        }
+    }
+}
+
+/// An error returned by a `usbipd` operation.
+///
+/// Known failures are recognized from the error output of `usbipd`,
+/// anything else is kept as is in [`UsbipError::Other`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsbipError {
+    /// The operation requires administrator privileges.
+    NeedsAdmin,
+    /// The device is in use by Windows or another driver, binding it requires `--force`.
+    DeviceInUse,
+    /// The device was not found, e.g. because it was disconnected.
+    NotFound,
+    /// The device is already attached to a client.
+    Busy,
+    /// The device is not ready yet, e.g. while Windows is still setting it up after it was
+    /// plugged in. Unlike the other errors, retrying shortly after might succeed.
+    NotReady,
+    Other(String),
+}
+
+impl UsbipError {
+    /// Recognizes the failure described by the error output of `usbipd`.
+    fn parse(message: &str) -> Self {
+        let lowercase = message.to_lowercase();
+        if lowercase.contains("administrator") {
+            UsbipError::NeedsAdmin
+        } else if lowercase.contains("--force") {
+            UsbipError::DeviceInUse
+        } else if lowercase.contains("already attached") {
+            UsbipError::Busy
+        } else if lowercase.contains("no device") || lowercase.contains("no compatible device") {
+            UsbipError::NotFound
+        } else if ["not ready", "is busy", "try again", "temporarily"]
+            .iter()
+            .any(|pattern| lowercase.contains(pattern))
+        {
+            UsbipError::NotReady
+        } else {
+            UsbipError::Other(message.to_owned())
+        }
+    }
+}
+
+impl Display for UsbipError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            UsbipError::NeedsAdmin => {
+                write!(fmt, "This operation requires administrator privileges.")
+            }
+            UsbipError::DeviceInUse => write!(
+                fmt,
+                "The device is in use by Windows or another driver, binding it requires --force."
+            ),
+            UsbipError::NotFound => {
+                write!(
+                    fmt,
+                    "The device was not found, it might have been disconnected."
+                )
+            }
+            UsbipError::Busy => write!(fmt, "The device is already attached to a client."),
+            UsbipError::NotReady => write!(
+                fmt,
+                "The device is not ready yet, try again once Windows finished setting it up."
+            ),
+            UsbipError::Other(message) => write!(fmt, "{message}"),
+        }
+    }
+}
+
+impl From<String> for UsbipError {
+    fn from(message: String) -> Self {
+        UsbipError::Other(message)
+    }
+}
+
+impl From<UsbipError> for String {
+    fn from(err: UsbipError) -> Self {
+        err.to_string()
+    }
+}
+
 /// A struct representing a USB device as returned by `usbipd`.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UsbDevice {
     #[serde(rename = "BusId")]
     pub bus_id: Option<String>,
@@ -67,9 +225,18 @@ pub struct UsbDevice {
     #[serde(rename = "PersistedGuid")]
     pub persisted_guid: Option<String>,
 
-    #[allow(dead_code)]
+    /// Whether the device is bound even though it has no persisted GUID,
+    /// as `usbipd list` does not show the GUIDs of connected devices.
+    #[serde(skip)]
+    pub bound_without_guid: bool,
+
     #[serde(rename = "StubInstanceGuid")]
     pub stub_instance_id: Option<String>,
+
+    /// The speed the device is operating at, if known.
+    /// usbipd does not report it, see [`list_devices_with_speed`].
+    #[serde(skip)]
+    pub speed: Option<UsbSpeed>,
 }
 
 impl UsbDevice {
@@ -80,7 +247,7 @@ impl UsbDevice {
 
     /// Returns whether the device is shared by usbipd.
     pub fn is_bound(&self) -> bool {
-        self.is_connected() && self.persisted_guid.is_some()
+        self.is_connected() && (self.persisted_guid.is_some() || self.bound_without_guid)
     }
 
     /// Returns whether the device is attached to a usbip client.
@@ -88,6 +255,22 @@ impl UsbDevice {
         self.is_connected() && self.client_ip_address.is_some()
     }
 
+    /// Returns whether the sharing state reported by usbipd is inconsistent, e.g. after
+    /// usbipd was interrupted while attaching or detaching the device.
+    ///
+    /// This is the case for bound devices still held by the usbipd stub driver while neither
+    /// attached nor forced, and for devices attached or forced without being bound.
+    /// Unbinding and binding the device again fixes it.
+    pub fn needs_rebind(&self) -> bool {
+        if !self.is_connected() {
+            false
+        } else if self.is_bound() {
+            self.stub_instance_id.is_some() && !self.is_attached() && !self.is_forced
+        } else {
+            self.is_attached() || self.is_forced
+        }
+    }
+
     /// Returns the VID:PID of the device if available.
     pub fn vid_pid(&self) -> Option<String> {
         // USB\VID_XXXX&PID_XXXX\XXXX
@@ -118,6 +301,45 @@ impl UsbDevice {
         }
     }
 
+    /// Returns whether the device looks like a USB hub, judging by its instance ID or description.
+    pub fn is_hub(&self) -> bool {
+        let instance_id = self.instance_id.as_deref().unwrap_or_default();
+        let description = self.description.as_deref().unwrap_or_default();
+
+        instance_id.to_uppercase().starts_with(r"USB\ROOT_HUB")
+            || description
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|word| word.eq_ignore_ascii_case("hub"))
+    }
+
+    /// Queries the system for the speed the device is operating at.
+    /// Returns `None` if the device is not connected or its speed cannot be determined.
+    fn query_speed(&self) -> Option<UsbSpeed> {
+        if !self.is_connected() {
+            return None;
+        }
+
+        let speed = win_utils::get_usb_device_speed(self.instance_id.as_deref()?)?;
+        match speed {
+            UsbLowSpeed => Some(UsbSpeed::Low),
+            UsbFullSpeed => Some(UsbSpeed::Full),
+            UsbHighSpeed => Some(UsbSpeed::High),
+            UsbSuperSpeed => Some(UsbSpeed::Super),
+            _ => None,
+        }
+    }
+
+    /// Returns a key identifying the device across reconnections, see [`stable_key`].
+    ///
+    /// The key is used to store the nickname of the device and to match it to auto-attach profiles.
+    pub fn stable_key(&self) -> Option<String> {
+        stable_key(
+            &self.vid_pid()?,
+            self.serial().as_deref(),
+            self.bus_id.as_deref(),
+        )
+    }
+
     /// Returns the state of the USB device as a `UsbipState` enum.
     pub fn state(&self) -> UsbipState {
         if self.bus_id.is_none() {
@@ -132,103 +354,107 @@ impl UsbDevice {
     }
 
     /// Binds the device. Asks for admin privileges if necessary.
-    pub fn bind(&self, force: bool) -> Result<(), String> {
+    ///
+    /// Binding only shares the device, it is not attached to WSL. A shared device can be
+    /// attached by any usbip client that can reach this machine over the network.
+    pub fn bind(&self, force: bool) -> Result<(), UsbipError> {
         let bus_id = self
             .bus_id
             .as_deref()
             .ok_or("The device does not have a bus ID.".to_owned())?;
 
-        let args = if force {
-            ["bind", "--force", "--busid", bus_id].to_vec()
-        } else {
-            ["bind", "--busid", bus_id].to_vec()
-        };
-
-        usbipd(&args).or_else(|err| {
-            if err.contains("administrator") {
-                usbipd_admin(&args)
-            } else {
-                Err(err)
-            }
+        retry_not_ready(&format!("Binding device {bus_id}"), || {
+            backend().bind(bus_id, force)
         })
     }
 
     /// Unbinds the device. Asks for admin privileges if necessary.
-    pub fn unbind(&self) -> Result<(), String> {
-        let guid = self
-            .persisted_guid
-            .as_deref()
-            .ok_or("The device is already unbound.".to_owned())?;
+    pub fn unbind(&self) -> Result<(), UsbipError> {
+        match (self.persisted_guid.as_deref(), self.bus_id.as_deref()) {
+            (Some(guid), _) => backend().unbind(guid),
+            // The GUID is unknown if the device was listed without `usbipd state`
+            (None, Some(bus_id)) if self.bound_without_guid => backend().unbind_bus_id(bus_id),
+            _ => Err(UsbipError::Other(
+                "The device is already unbound.".to_owned(),
+            )),
+        }
+    }
 
-        let args = ["unbind", "--guid", guid].to_vec();
+    /// Binds the device if it is not bound yet, waiting for the binding to complete.
+    ///
+    /// This is the only place where attaching implies binding: [`UsbDevice::bind`]
+    /// itself never attaches the device.
+    fn ensure_bound(&self) -> Result<(), UsbipError> {
+        if self.is_bound() {
+            return Ok(());
+        }
 
-        usbipd(&args).or_else(|err| {
-            if err.contains("administrator") {
-                usbipd_admin(&args)
-            } else {
-                Err(err)
+        // A forced bind is only used when usbipd reports it as required,
+        // e.g. for devices claimed by incompatible filter drivers
+        match self.bind(false) {
+            Err(UsbipError::DeviceInUse) => {
+                logging::info(format_args!(
+                    "Binding {} requires --force, retrying with a forced bind",
+                    self.bus_id.as_deref().unwrap_or_default()
+                ));
+                self.bind(true)?;
             }
-        })
+            result => result?,
+        }
+        Ok(self.wait(|d| d.is_some_and(|d| d.is_bound()))?)
     }
 
-    /// Attaches the device. Binds the device if necessary.
-    pub fn attach(&self) -> Result<(), String> {
+    /// Attaches the device to the given WSL distribution, or to the default one if `None`.
+    /// Binds the device if necessary, forcing the binding only if usbipd requires it.
+    /// Forced bindings are shown in the device state.
+    pub fn attach(&self, distribution: Option<&str>) -> Result<(), UsbipError> {
         let bus_id = self
             .bus_id
             .as_deref()
             .ok_or("The device does not have a bus ID.".to_owned())?;
 
-        if !self.is_bound() {
-            self.bind(false)?;
-            self.wait(|d| d.is_some_and(|d| d.is_bound()))?;
-        }
+        self.ensure_bound()?;
 
-        let args = if version().major < 4 {
-            ["wsl", "attach", "--busid", bus_id].to_vec()
-        } else {
-            ["attach", "--wsl", "--busid", bus_id].to_vec()
-        };
-
-        usbipd(&args)
+        retry_not_ready(&format!("Attaching device {bus_id}"), || {
+            backend().attach(bus_id, distribution)
+        })
     }
 
     /// Detaches the device.
-    pub fn detach(&self) -> Result<(), String> {
+    pub fn detach(&self) -> Result<(), UsbipError> {
         let bus_id = self
             .bus_id
             .as_deref()
             .ok_or("The device does not have a bus ID.".to_owned())?;
 
-        let args = if version().major < 4 {
-            ["wsl", "detach", "--busid", bus_id].to_vec()
-        } else {
-            ["detach", "--busid", bus_id].to_vec()
-        };
-
-        usbipd(&args)
+        backend().detach(bus_id)
     }
 
     /// Spawns a process running the auto-attach loop for the device and
     /// returns its handle.
     ///
+    /// The device is attached to the given WSL distribution, or to the default one if `None`.
     /// The device **must** be bound before auto-attaching it.
-    pub fn auto_attach(&self) -> Result<std::process::Child, String> {
+    /// The error output of the process is piped, it must be read to keep the process running.
+    pub fn auto_attach(&self, distribution: Option<&str>) -> Result<std::process::Child, String> {
         let bus_id = self
             .bus_id
             .as_deref()
             .ok_or("The device does not have a bus ID.".to_owned())?;
 
-        let args = if version().major < 4 {
-            ["wsl", "attach", "--auto-attach", "--busid", bus_id].to_vec()
-        } else {
-            ["attach", "--wsl", "--auto-attach", "--busid", bus_id].to_vec()
-        };
+        backend().auto_attach(bus_id, distribution)
+    }
 
-        Command::new(USBIPD_EXE)
-            .args(args)
-            .creation_flags(CREATE_NO_WINDOW)
-            .spawn()
-            .map_err(|err| err.to_string())
+    /// Returns the command a usbip client on the network, e.g. a remote Linux host,
+    /// runs to attach the device. Returns `None` if the device is not shared.
+    ///
+    /// The client must be able to reach this machine on the usbip port (TCP 3240),
+    /// which might require a firewall rule.
+    pub fn remote_attach_command(&self) -> Option<String> {
+        match (&self.bus_id, self.is_bound()) {
+            (Some(bus_id), true) => Some(format!("usbip attach -r {} -b {bus_id}", host_address())),
+            _ => None,
+        }
     }
 
     /// Waits until `wait_cond` is satisfied for the device.
@@ -240,22 +466,37 @@ impl UsbDevice {
     /// `wait_cond` should return `true` when the device reaches the desired state
     /// and waiting should stop.
     ///
-    /// The maximum wait time is 5 seconds, which takes into account the worst-case
-    /// scenario of Windows remounting the USB device after a `usbipd` operation.
-    /// If the wait times out, the device is assumed to be lost.
+    /// The maximum wait time defaults to 5 seconds, which takes into account the worst-case
+    /// scenario of Windows remounting the USB device after a `usbipd` operation, and can be
+    /// changed with [`set_wait_timing`]. If the wait times out, the device is assumed to be lost.
+    ///
+    /// The poll interval doubles after each attempt, up to one second,
+    /// so that long operations do not spawn `usbipd` too often.
     pub fn wait(&self, wait_cond: fn(Option<&UsbDevice>) -> bool) -> Result<(), String> {
+        let (timeout, mut poll_interval) = *WAIT_TIMING.read().unwrap();
         let start = Instant::now();
+        let mut attempt = 0;
 
         // Wait for the device to be in the desired state with a timeout
-        while start.elapsed() < Duration::from_secs(5) {
-            let devices = list_devices();
-            let device = devices.iter().find(|d| d.instance_id == self.instance_id);
-            // Pass Option as we might want to check for the device being removed
-            if wait_cond(device) {
-                return Ok(());
+        while start.elapsed() < timeout {
+            attempt += 1;
+            logging::debug(format_args!(
+                "Waiting for {}, attempt {attempt} after {} ms",
+                self.bus_id.as_deref().unwrap_or("device"),
+                start.elapsed().as_millis()
+            ));
+
+            // Failures to list devices are retried until the timeout expires
+            if let Ok(devices) = list_devices() {
+                let device = devices.iter().find(|d| d.instance_id == self.instance_id);
+                // Pass Option as we might want to check for the device being removed
+                if wait_cond(device) {
+                    return Ok(());
+                }
             }
 
-            std::thread::sleep(std::time::Duration::from_millis(100));
+            std::thread::sleep(poll_interval.min(timeout.saturating_sub(start.elapsed())));
+            poll_interval = (poll_interval * 2).min(MAX_WAIT_POLL_INTERVAL);
         }
 
         // Assume the device was disconnected if the maximum wait time was reached
@@ -263,46 +504,581 @@ impl UsbDevice {
     }
 }
 
+/// Runs `command`, retrying it with an increasing delay while it fails with
+/// [`UsbipError::NotReady`]. `action` describes the command in the log.
+fn retry_not_ready(
+    action: &str,
+    command: impl Fn() -> Result<(), UsbipError>,
+) -> Result<(), UsbipError> {
+    let mut delay = NOT_READY_RETRY_DELAY;
+    for attempt in 1..=NOT_READY_RETRIES {
+        match command() {
+            Err(UsbipError::NotReady) => {
+                logging::info(format_args!(
+                    "{action}: device not ready, retry {attempt}/{NOT_READY_RETRIES} in {} ms",
+                    delay.as_millis()
+                ));
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    command()
+}
+
+/// Returns a key identifying a device across reconnections.
+///
+/// Devices are identified by VID:PID and serial number. Devices without a serial number
+/// cannot be told apart from identical devices, so they fall back to VID:PID and bus ID.
+/// Such keys are only as persistent as the port the device is plugged into: the same device
+/// on another port gets a different key, and an identical device on the same port gets the same key.
+/// Returns `None` if the device has neither a serial number nor a bus ID.
+pub fn stable_key(vid_pid: &str, serial: Option<&str>, bus_id: Option<&str>) -> Option<String> {
+    match (serial, bus_id) {
+        (Some(serial), _) => Some(format!("{vid_pid} {serial}")),
+        (None, Some(bus_id)) => Some(format!("{vid_pid} @{bus_id}")),
+        (None, None) => None,
+    }
+}
+
+/// Returns the address usbip clients on the network can reach this machine at.
+///
+/// This is the address of the network interface used for outgoing connections,
+/// found by connecting a UDP socket, which does not send any packets.
+/// Falls back to the computer name if there is no such interface.
+fn host_address() -> String {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(8, 8, 8, 8), 80))?;
+            socket.local_addr()
+        })
+        .ok()
+        .map(|address| address.ip())
+        .filter(|ip| !ip.is_unspecified() && !ip.is_loopback())
+        .map(|ip| ip.to_string())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_else(|| "localhost".to_owned())
+}
+
+/// Attaches all the bound devices that are not attached yet to the given WSL distribution,
+/// or the default one if `None`.
+///
+/// All devices are attempted even if some fail, errors are collected into a single message.
+pub fn attach_all(distribution: Option<&str>) -> Result<(), String> {
+    run_on_all(
+        |d| d.is_bound() && !d.is_attached(),
+        |d| {
+            d.attach(distribution)?;
+            d.wait(|d| d.is_some_and(|d| d.is_attached()))
+        },
+    )
+}
+
+/// Detaches all the attached devices.
+///
+/// All devices are attempted even if some fail, errors are collected into a single message.
+pub fn detach_all() -> Result<(), String> {
+    run_on_all(
+        |d| d.is_attached(),
+        |d| {
+            d.detach()?;
+            d.wait(|d| d.is_some_and(|d| !d.is_attached()))
+        },
+    )
+}
+
+/// Detaches all the attached devices, giving up once `timeout` has elapsed.
+///
+/// Devices that fail to detach are skipped, errors are only logged.
+/// This is meant to be used when exiting, where nothing waits for the detach to complete.
+pub fn detach_all_within(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let devices = match run_until(deadline, list_devices) {
+        Some(Ok(devices)) => devices,
+        Some(Err(err)) => {
+            logging::error(format_args!("Failed to detach devices: {err}"));
+            return;
+        }
+        None => {
+            logging::error(format_args!("Timed out listing devices to detach"));
+            return;
+        }
+    };
+
+    for device in devices
+        .into_iter()
+        .filter(|d| d.is_connected() && d.is_attached())
+    {
+        let description = device
+            .description
+            .clone()
+            .unwrap_or_else(|| "Unknown device".to_owned());
+
+        match run_until(deadline, move || device.detach()) {
+            Some(Ok(())) => {}
+            Some(Err(err)) => logging::error(format_args!("Failed to detach {description}: {err}")),
+            None => {
+                logging::error(format_args!("Timed out detaching {description}"));
+                return;
+            }
+        }
+    }
+}
+
+/// Runs `task` on a separate thread, waiting for its result until `deadline`.
+///
+/// Returns `None` if the deadline passed first, the task is then left running.
+fn run_until<T: Send + 'static>(
+    deadline: Instant,
+    task: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        // The receiver is gone if the deadline passed, the result is not needed anymore
+        let _ = sender.send(task());
+    });
+
+    receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// Runs `command` on all the connected devices matching `filter`,
+/// collecting any errors into a single message.
+fn run_on_all(
+    filter: impl Fn(&UsbDevice) -> bool,
+    command: impl Fn(&UsbDevice) -> Result<(), String>,
+) -> Result<(), String> {
+    let devices: Vec<UsbDevice> = list_devices()?
+        .into_iter()
+        .filter(|d| d.is_connected() && filter(d))
+        .collect();
+
+    run_on_devices(&devices, command)
+}
+
+/// Runs `command` on each of `devices`, collecting any errors into a single message.
+///
+/// All devices are attempted even if some fail.
+pub fn run_on_devices(
+    devices: &[UsbDevice],
+    command: impl Fn(&UsbDevice) -> Result<(), String>,
+) -> Result<(), String> {
+    device_errors(devices.iter().map(|d| (d, command(d))))
+}
+
+/// Collects the errors of the given results of devices into a single message,
+/// each prefixed with the description of its device.
+pub fn device_errors<'a>(
+    results: impl IntoIterator<Item = (&'a UsbDevice, Result<(), String>)>,
+) -> Result<(), String> {
+    let errors: Vec<String> = results
+        .into_iter()
+        .filter_map(|(d, result)| {
+            result.err().map(|err| {
+                let description = d.description.as_deref().unwrap_or("Unknown device");
+                format!("{description}: {}", err.trim())
+            })
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Compares two bus IDs in natural order, so that `1-2` comes before `1-10`.
+///
+/// Bus IDs are compared by bus number first, then by port number.
+pub fn compare_bus_ids(a: &str, b: &str) -> Ordering {
+    let parse = |bus_id: &str| -> Vec<u32> {
+        bus_id
+            .split('-')
+            .map(|part| part.trim().parse().unwrap_or(u32::MAX))
+            .collect()
+    };
+
+    parse(a).cmp(&parse(b)).then_with(|| a.cmp(b))
+}
+
 /// Retrieves the list of USB devices from `usbipd`.
-pub fn list_devices() -> Vec<UsbDevice> {
-    let state_str = {
-        let cmd = Command::new(USBIPD_EXE)
-            .arg("state")
+pub fn list_devices() -> Result<Vec<UsbDevice>, String> {
+    backend().list_devices()
+}
+
+/// Like [`list_devices`], also filling in the speed of the connected devices.
+///
+/// The speed of each device is queried from the system, which takes a while,
+/// so this is meant for device lists retrieved in the background.
+pub fn list_devices_with_speed() -> Result<Vec<UsbDevice>, String> {
+    let mut devices = list_devices()?;
+    for device in &mut devices {
+        device.speed = device.query_speed();
+    }
+    Ok(devices)
+}
+
+/// The operations the app performs through `usbipd`.
+///
+/// Besides the real `usbipd` backend, a fake one seeded with sample devices can be
+/// selected by setting the [`FAKE_BACKEND_ENV`] environment variable, which allows
+/// working on the UI without usbipd or any USB device.
+trait UsbipBackend: Send + Sync {
+    /// Retrieves the list of USB devices.
+    fn list_devices(&self) -> Result<Vec<UsbDevice>, String>;
+
+    /// Returns the version of the `usbipd` executable at `path`.
+    fn version(&self, path: &Path) -> Result<Version, VersionError>;
+
+    /// Binds the device with the given bus ID.
+    fn bind(&self, bus_id: &str, force: bool) -> Result<(), UsbipError>;
+
+    /// Binds all the devices with the given bus IDs, returning the result of each of them.
+    fn bind_all(&self, bus_ids: &[&str]) -> Vec<Result<(), UsbipError>> {
+        bus_ids
+            .iter()
+            .map(|bus_id| self.bind(bus_id, false))
+            .collect()
+    }
+
+    /// Unbinds the device with the given persisted GUID.
+    fn unbind(&self, guid: &str) -> Result<(), UsbipError>;
+
+    /// Unbinds the connected device with the given bus ID.
+    fn unbind_bus_id(&self, bus_id: &str) -> Result<(), UsbipError>;
+
+    /// Attaches the device with the given bus ID to a WSL distribution.
+    fn attach(&self, bus_id: &str, distribution: Option<&str>) -> Result<(), UsbipError>;
+
+    /// Detaches the device with the given bus ID.
+    fn detach(&self, bus_id: &str) -> Result<(), UsbipError>;
+
+    /// Spawns a process running the auto-attach loop for the device with the given bus ID.
+    fn auto_attach(&self, bus_id: &str, distribution: Option<&str>) -> Result<Child, String>;
+}
+
+/// The environment variable that selects the fake `usbipd` backend when set.
+const FAKE_BACKEND_ENV: &str = "WSL_USB_MANAGER_FAKE_USBIPD";
+
+static BACKEND: OnceLock<Box<dyn UsbipBackend>> = OnceLock::new();
+
+/// Returns the backend used for all `usbipd` operations.
+fn backend() -> &'static dyn UsbipBackend {
+    // Tests choose their backend per thread, as they run in parallel
+    #[cfg(test)]
+    if let Some(backend) = tests::TEST_BACKEND.with(|backend| backend.get()) {
+        return backend;
+    }
+
+    BACKEND
+        .get_or_init(|| {
+            if std::env::var_os(FAKE_BACKEND_ENV).is_some() {
+                logging::info(format_args!("Using the fake usbipd backend"));
+                Box::new(fake::FakeBackend::new())
+            } else {
+                Box::new(RealBackend)
+            }
+        })
+        .as_ref()
+}
+
+/// The backend running the `usbipd` executable.
+struct RealBackend;
+
+impl RealBackend {
+    /// Retrieves the devices from the human-readable output of `usbipd list`.
+    /// Some details are not available this way, see [`list::parse`].
+    fn list_devices_from_list() -> Result<Vec<UsbDevice>, String> {
+        let cmd = Command::new(path())
+            .arg("list")
             .creation_flags(CREATE_NO_WINDOW)
             .output()
-            .unwrap();
+            .map_err(|err| format!("Failed to run usbipd: {err}"))?;
+        if !cmd.status.success() {
+            return Err(error_message(&cmd));
+        }
 
-        String::from_utf8(cmd.stdout).unwrap()
-    };
+        let devices = list::parse(&String::from_utf8_lossy(&cmd.stdout));
+        logging::info(format_args!(
+            "Device list refreshed from usbipd list, {} devices",
+            devices.len()
+        ));
+        Ok(devices)
+    }
 
-    #[derive(Deserialize)]
-    struct StateResult {
-        #[serde(rename = "Devices")]
-        devices: Vec<UsbDevice>,
+    /// Executes `usbipd` with the given arguments, as administrator if required.
+    fn usbipd_maybe_admin(args: &[&str]) -> Result<(), UsbipError> {
+        match usbipd(args) {
+            Err(UsbipError::NeedsAdmin) => Ok(usbipd_admin(args)?),
+            result => result,
+        }
     }
+}
+
+impl UsbipBackend for RealBackend {
+    fn list_devices(&self) -> Result<Vec<UsbDevice>, String> {
+        if version().is_ok_and(|version| version.major < STATE_MIN_MAJOR) {
+            return Self::list_devices_from_list();
+        }
+
+        let state_str = match state_json() {
+            Ok(state_str) if !state_str.trim().is_empty() => state_str,
+            Ok(_) => {
+                logging::error(format_args!(
+                    "usbipd state returned nothing, using usbipd list"
+                ));
+                return Self::list_devices_from_list();
+            }
+            Err(err) => {
+                logging::error(format_args!("usbipd state failed, using usbipd list"));
+                return Self::list_devices_from_list().map_err(|_| err);
+            }
+        };
 
-    let state_res: StateResult = serde_json::from_str(&state_str).unwrap();
-    state_res.devices
+        #[derive(Deserialize)]
+        struct StateResult {
+            #[serde(rename = "Devices")]
+            devices: Vec<UsbDevice>,
+        }
+
+        let state_res: StateResult = serde_json::from_str(&state_str).map_err(|err| {
+            logging::error(format_args!("Failed to parse usbipd state: {err}"));
+            format!("Failed to parse the usbipd device list: {err}")
+        })?;
+
+        logging::info(format_args!(
+            "Device list refreshed, {} devices",
+            state_res.devices.len()
+        ));
+        Ok(state_res.devices)
+    }
+
+    fn version(&self, path: &Path) -> Result<Version, VersionError> {
+        let cmd = Command::new(path)
+            .arg("--version")
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|err| VersionError::NotFound(err.to_string()))?;
+        let version_string = String::from_utf8_lossy(&cmd.stdout);
+
+        parse_version(&version_string)
+            .ok_or_else(|| VersionError::Unrecognized(version_string.trim().to_owned()))
+    }
+
+    fn bind(&self, bus_id: &str, force: bool) -> Result<(), UsbipError> {
+        let args = if force {
+            ["bind", "--force", "--busid", bus_id].to_vec()
+        } else {
+            ["bind", "--busid", bus_id].to_vec()
+        };
+
+        Self::usbipd_maybe_admin(&args)
+    }
+
+    fn bind_all(&self, bus_ids: &[&str]) -> Vec<Result<(), UsbipError>> {
+        // Try without admin privileges first, as a single bind does
+        let mut results: Vec<Result<(), UsbipError>> = bus_ids
+            .iter()
+            .map(|bus_id| usbipd(&["bind", "--busid", *bus_id]))
+            .collect();
+        let needs_admin =
+            |result: &Result<(), UsbipError>| matches!(result, Err(UsbipError::NeedsAdmin));
+
+        let usbipd_path = path().to_string_lossy().into_owned();
+        let commands = bus_ids
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| needs_admin(result))
+            .map(|(bus_id, _)| {
+                format!(
+                    "{} {}",
+                    quote_args(&[usbipd_path.as_str()]),
+                    quote_args(&["bind", "--busid", *bus_id])
+                )
+            })
+            .collect::<Vec<_>>();
+        if commands.is_empty() {
+            return results;
+        }
+
+        // The whole command line is quoted, as `cmd` strips the first and last quotes
+        let elevated = run_as_admin("cmd.exe", &format!("/c \"{}\"", commands.join(" & ")));
+
+        // The exit code of `cmd` is the one of the last bind only, check each device instead
+        let devices = list_devices().ok();
+        for (bus_id, result) in bus_ids.iter().zip(&mut results) {
+            if !needs_admin(result) {
+                continue;
+            }
+
+            let bound = match &devices {
+                Some(devices) => devices
+                    .iter()
+                    .any(|d| d.bus_id.as_deref() == Some(*bus_id) && d.is_bound()),
+                None => elevated.is_ok(),
+            };
+            *result = if bound {
+                Ok(())
+            } else {
+                match &elevated {
+                    Err(err) => Err(UsbipError::Other(err.clone())),
+                    Ok(()) => Err(UsbipError::Other(
+                        "The device is still not bound after binding it as administrator."
+                            .to_owned(),
+                    )),
+                }
+            };
+        }
+
+        results
+    }
+
+    fn unbind(&self, guid: &str) -> Result<(), UsbipError> {
+        Self::usbipd_maybe_admin(&["unbind", "--guid", guid])
+    }
+
+    fn unbind_bus_id(&self, bus_id: &str) -> Result<(), UsbipError> {
+        Self::usbipd_maybe_admin(&["unbind", "--busid", bus_id])
+    }
+
+    fn attach(&self, bus_id: &str, distribution: Option<&str>) -> Result<(), UsbipError> {
+        let mut args = if uses_legacy_syntax() {
+            ["wsl", "attach", "--busid", bus_id].to_vec()
+        } else {
+            ["attach", "--wsl", "--busid", bus_id].to_vec()
+        };
+
+        if let Some(distribution) = distribution {
+            if uses_legacy_syntax() {
+                args.extend(["--distribution", distribution]);
+            } else {
+                // The distribution is an optional value of the `--wsl` option
+                args.insert(2, distribution);
+            }
+        }
+
+        usbipd(&args)
+    }
+
+    fn detach(&self, bus_id: &str) -> Result<(), UsbipError> {
+        let args = if uses_legacy_syntax() {
+            ["wsl", "detach", "--busid", bus_id].to_vec()
+        } else {
+            ["detach", "--busid", bus_id].to_vec()
+        };
+
+        usbipd(&args)
+    }
+
+    fn auto_attach(&self, bus_id: &str, distribution: Option<&str>) -> Result<Child, String> {
+        let mut args = if uses_legacy_syntax() {
+            ["wsl", "attach", "--auto-attach", "--busid", bus_id].to_vec()
+        } else {
+            ["attach", "--wsl", "--auto-attach", "--busid", bus_id].to_vec()
+        };
+
+        if let Some(distribution) = distribution {
+            if uses_legacy_syntax() {
+                args.extend(["--distribution", distribution]);
+            } else {
+                // The distribution is an optional value of the `--wsl` option
+                args.insert(2, distribution);
+            }
+        }
+
+        logging::info(format_args!("Spawning usbipd {}", args.join(" ")));
+        let child = Command::new(path())
+            .args(&args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .creation_flags(CREATE_NO_WINDOW)
+            .spawn()
+            .map_err(|err| err.to_string());
+
+        match &child {
+            Ok(child) => logging::info(format_args!("Auto-attach process {} started", child.id())),
+            Err(err) => logging::error(format_args!("Failed to spawn auto-attach process: {err}")),
+        }
+        child
+    }
+}
+
+/// Returns the raw JSON output of `usbipd state`, which describes all the devices.
+///
+/// This always runs the `usbipd` executable, even when the fake backend is selected.
+pub fn state_json() -> Result<String, String> {
+    let cmd = Command::new(path())
+        .arg("state")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|err| {
+            logging::error(format_args!("Failed to run usbipd state: {err}"));
+            format!("Failed to run usbipd: {err}")
+        })?;
+
+    String::from_utf8(cmd.stdout)
+        .map_err(|_| "The usbipd device list is not valid UTF-8.".to_owned())
 }
 
 /// Executes `usbipd` with the given arguments.
-fn usbipd<'a, I>(args: I) -> Result<(), String>
+///
+/// Known failures are recognized from the error output, see [`UsbipError`].
+fn usbipd<'a, I>(args: I) -> Result<(), UsbipError>
 where
     I: IntoIterator<Item = &'a &'a str>,
 {
-    match Command::new(USBIPD_EXE)
-        .args(args)
+    let args: Vec<&str> = args.into_iter().copied().collect();
+    logging::info(format_args!("Running usbipd {}", args.join(" ")));
+
+    match Command::new(path())
+        .args(&args)
         .creation_flags(CREATE_NO_WINDOW)
         .output()
     {
         Ok(output) => {
             if output.status.success() {
+                logging::info(format_args!("usbipd exited with {}", output.status));
                 Ok(())
             } else {
-                Err(String::from_utf8(output.stderr).unwrap())
+                let message = error_message(&output);
+                logging::error(format_args!(
+                    "usbipd exited with {}: {message}",
+                    output.status
+                ));
+                Err(UsbipError::parse(&message))
             }
         }
-        Err(err) => Err(err.to_string()),
+        Err(err) => {
+            logging::error(format_args!("Failed to run usbipd: {err}"));
+            Err(UsbipError::Other(err.to_string()))
+        }
+    }
+}
+
+/// Builds an error message from the output of a failed `usbipd` invocation.
+///
+/// The message is taken from stderr, or from stdout if stderr is empty,
+/// and includes the exit code for diagnostics.
+fn error_message(output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let message = match stderr.trim() {
+        "" => stdout.trim(),
+        stderr => stderr,
+    };
+    let message = if message.is_empty() {
+        "usbipd failed without an error message."
+    } else {
+        message
+    };
+
+    match output.status.code() {
+        Some(code) => format!("{message}\n\n(usbipd exit code: {code})"),
+        None => message.to_owned(),
     }
 }
 
@@ -311,30 +1087,67 @@ fn usbipd_admin<'a, I>(args: I) -> Result<(), String>
 where
     I: IntoIterator<Item = &'a &'a str>,
 {
-    // Build a space-separated string of arguments
-    let mut args_str: String = String::new();
-    for arg in args {
-        args_str.push_str(&format!("{arg} "));
+    run_as_admin(&path().to_string_lossy(), &quote_args(args))
+}
+
+/// Binds all the given devices, asking for admin privileges only once for all of them.
+///
+/// Returns the result of each device, in the same order as `devices`.
+pub fn bind_all(devices: &[&UsbDevice]) -> Vec<Result<(), UsbipError>> {
+    let bus_ids: Vec<&str> = devices.iter().filter_map(|d| d.bus_id.as_deref()).collect();
+    let mut results = if bus_ids.is_empty() {
+        Vec::new()
+    } else {
+        backend().bind_all(&bus_ids)
     }
-    // Remove the trailing comma
-    args_str.pop();
-    // Insert a null terminator
-    args_str.push('\0');
+    .into_iter();
+
+    devices
+        .iter()
+        .map(|device| match device.bus_id {
+            // The elevated batch might have stopped before reaching the device
+            Some(_) => results.next().unwrap_or_else(|| {
+                Err(UsbipError::Other(
+                    "usbipd did not report whether the device was bound.".to_owned(),
+                ))
+            }),
+            None => Err(UsbipError::Other(
+                "The device does not have a bus ID.".to_owned(),
+            )),
+        })
+        .collect()
+}
 
-    // Prepare u16 strings
+/// Binds all the given devices like [`bind_all`], then waits until they are bound.
+///
+/// All devices are attempted even if some fail, errors are collected into a single message.
+pub fn bind_all_and_wait(devices: &[&UsbDevice]) -> Result<(), String> {
+    let results = bind_all(devices);
+    device_errors(devices.iter().zip(results).map(|(device, result)| {
+        let result = result
+            .map_err(String::from)
+            .and_then(|()| device.wait(|d| d.is_some_and(|d| d.is_bound())));
+        (*device, result)
+    }))
+}
+
+/// Executes `file` as administrator with the given space-separated parameters.
+fn run_as_admin(file: &str, params: &str) -> Result<(), String> {
+    // Prepare null-terminated u16 strings
     let verb = "runas\0".encode_utf16().collect::<Vec<_>>();
-    let file = (USBIPD_EXE.to_owned() + "\0")
+    let file_w = (file.to_owned() + "\0").encode_utf16().collect::<Vec<_>>();
+    let params_w = (params.to_owned() + "\0")
         .encode_utf16()
         .collect::<Vec<_>>();
-    let params = args_str.encode_utf16().collect::<Vec<_>>();
 
     let mut shell_exec_info = SHELLEXECUTEINFOW {
         cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
-        fMask: 0,
+        // Keep the process handle to wait for the elevated command to finish
+        fMask: SEE_MASK_NOCLOSEPROCESS,
         hwnd: 0,
         lpVerb: verb.as_ptr(),
-        lpFile: file.as_ptr(),
-        lpParameters: params.as_ptr(),
+        lpFile: file_w.as_ptr(),
+        lpParameters: params_w.as_ptr(),
         lpDirectory: std::ptr::null(),
         nShow: SW_HIDE,
         hInstApp: 0,
@@ -346,52 +1159,539 @@ where
         hProcess: 0,
     };
 
+    logging::info(format_args!("Running `{file} {params}` as administrator"));
+
     if unsafe { ShellExecuteExW(&mut shell_exec_info as *mut _) } == 0 {
-        Err(get_last_error_string())
-    } else {
-        Ok(())
+        let error_code = unsafe { GetLastError() };
+        // The user dismissed the UAC prompt
+        if error_code == ERROR_CANCELLED {
+            logging::info(format_args!("Running as administrator was cancelled"));
+            return Err("Operation cancelled by user.".to_owned());
+        }
+
+        let message = format!(
+            "Failed to run `{} {}` as administrator: {}",
+            file,
+            params,
+            get_error_string(error_code)
+        );
+        logging::error(format_args!("{message}"));
+        return Err(message);
     }
+
+    // No handle is returned if the command was handed to an already running process
+    if shell_exec_info.hProcess == 0 {
+        return Ok(());
+    }
+
+    let result = wait_for_exit_code(shell_exec_info.hProcess);
+    unsafe { CloseHandle(shell_exec_info.hProcess) };
+
+    let message = match result {
+        Some(0) => return Ok(()),
+        // The output of elevated processes cannot be captured, only the exit code is known
+        Some(exit_code) => format!("`{file} {params}` failed with exit code {exit_code}."),
+        None => format!("Failed to wait for `{file} {params}` to complete."),
+    };
+    logging::error(format_args!("{message}"));
+    Err(message)
 }
 
+/// Waits for the process with the given handle to exit and returns its exit code.
+///
+/// Returns `None` if the process does not exit within [`ADMIN_COMMAND_TIMEOUT`]
+/// or its exit code cannot be retrieved.
+fn wait_for_exit_code(process: HANDLE) -> Option<u32> {
+    let timeout = ADMIN_COMMAND_TIMEOUT.as_millis() as u32;
+    if unsafe { WaitForSingleObject(process, timeout) } != WAIT_OBJECT_0 {
+        return None;
+    }
+
+    let mut exit_code = 0;
+    if unsafe { GetExitCodeProcess(process, &mut exit_code as *mut _) } == 0 {
+        return None;
+    }
+
+    Some(exit_code)
+}
+
+/// Builds a space-separated command line string from the given arguments.
+///
+/// Arguments containing whitespace or quotes are wrapped in double quotes, escaped so that
+/// `CommandLineToArgvW` splits them back into the original arguments.
+fn quote_args<'a, I>(args: I) -> String
+where
+    I: IntoIterator<Item = &'a &'a str>,
+{
+    args.into_iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes a single argument for [`quote_args`], if needed.
+///
+/// Backslashes are only special right before a quote: those before an embedded quote
+/// or the closing quote are doubled, and embedded quotes are escaped with a backslash.
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        return arg.to_owned();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+
+        let escapes = if c == '"' {
+            backslashes * 2 + 1
+        } else {
+            backslashes
+        };
+        quoted.extend(std::iter::repeat('\\').take(escapes));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.extend(std::iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+
+    quoted
+}
+
+/// The oldest version of `usbipd` that provides the `wsl attach` and `wsl detach`
+/// commands the app relies on.
+pub const MIN_SUPPORTED: Version = Version {
+    major: 2,
+    minor: 0,
+    patch: 0,
+};
+
+/// The oldest major version of `usbipd` that provides the `state` command.
+/// Older versions are listed with the `list` command, which reports fewer details.
+const STATE_MIN_MAJOR: u32 = 3;
+
+/// The newest major version of `usbipd` the app has been tested with.
+const LATEST_TESTED_MAJOR: u32 = 4;
+
 /// A `ubpidp` version struct with major, minor, and patch fields.
-#[allow(unused)]
+///
+/// Versions are ordered by major, then minor, then patch number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
     pub major: u32,
     pub minor: u32,
     pub patch: u32,
 }
 
+impl Display for Version {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Why the version of `usbipd` could not be retrieved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionError {
+    /// `usbipd` could not be run, most likely because it is not installed at its path.
+    NotFound(String),
+    /// `usbipd` ran, but its output does not contain a recognized version.
+    Unrecognized(String),
+}
+
+impl Display for VersionError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            VersionError::NotFound(err) => write!(fmt, "Failed to run usbipd: {err}"),
+            VersionError::Unrecognized(output) => {
+                write!(fmt, "Failed to parse the usbipd version: {output}")
+            }
+        }
+    }
+}
+
+impl From<VersionError> for String {
+    fn from(err: VersionError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Whether the app can run with a given version of `usbipd`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VersionSupport {
+    /// Older than [`MIN_SUPPORTED`], the app cannot run.
+    Unsupported,
+    /// Within the supported and tested range.
+    Supported,
+    /// Newer than the latest tested major version, some features may not work.
+    Untested,
+}
+
+/// Checks whether the app can run with `version` of `usbipd`.
+pub fn is_supported(version: &Version) -> VersionSupport {
+    if *version < MIN_SUPPORTED {
+        VersionSupport::Unsupported
+    } else if version.major > LATEST_TESTED_MAJOR {
+        VersionSupport::Untested
+    } else {
+        VersionSupport::Supported
+    }
+}
+
 /// Returns the version of `usbipd`, split into major, minor, and patch fields.
-pub fn version() -> Version {
-    let cmd = Command::new(USBIPD_EXE)
-        .arg("--version")
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()
-        .unwrap();
-    let version_string = String::from_utf8(cmd.stdout).unwrap();
+///
+/// The version is only retrieved once, until the path of `usbipd` changes, see [`set_path`].
+/// Fails if `usbipd` cannot be run or its version cannot be parsed, failures are not cached.
+pub fn version() -> Result<Version, VersionError> {
+    let path = path();
+    if let Some((version_path, version)) = &*VERSION.read().unwrap() {
+        if *version_path == path {
+            return Ok(*version);
+        }
+    }
 
-    let version_split: Vec<&str> = version_string.split('+').collect();
-    let version_parts: Vec<&str> = version_split.first().unwrap().split('.').collect();
+    // Cached along with its path, as the path might change while usbipd runs:
+    // the version of the previous executable is then not used for the new one
+    let version = backend().version(&path)?;
+    *VERSION.write().unwrap() = Some((path, version));
+    Ok(version)
+}
 
-    let parse = |i| -> u32 {
-        version_parts
-            .get(i)
-            .and_then(|part: &&str| part.parse().ok())
-            .unwrap_or(0)
-    };
+/// Returns whether `usbipd` is older than version 4, which uses a different
+/// command line syntax for WSL operations.
+///
+/// If the version cannot be determined, the current syntax is assumed.
+fn uses_legacy_syntax() -> bool {
+    version().is_ok_and(|version| version.major < 4)
+}
+
+/// Parses a version from the output of `usbipd --version`.
+///
+/// Leading non-numeric text (e.g. a `usbipd-win` banner line) is ignored, as are
+/// pre-release (`-rc.1`) and build metadata (`+abc`) suffixes.
+/// Missing minor and patch numbers default to 0.
+fn parse_version(output: &str) -> Option<Version> {
+    // Find the first word that looks like a version number, e.g. `4.3.0-rc.1+abc`
+    let version_str = output
+        .split_whitespace()
+        .map(|word| word.trim_start_matches(['v', 'V']))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))?;
 
-    Version {
-        major: parse(0),
-        minor: parse(1),
-        patch: parse(2),
+    // Strip pre-release and build metadata suffixes
+    let core = version_str.split(['-', '+']).next()?;
+
+    let mut parts = core.split('.').map(|part| part.parse::<u32>().ok());
+    let major = parts.next().flatten()?;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+
+    Some(Version {
+        major,
+        minor,
+        patch,
+    })
+}
+
+/// Sets the path of the `usbipd` executable used by all operations.
+///
+/// If `custom_path` is `None` or does not exist, the default installation path is used,
+/// falling back to looking up `usbipd` in the system PATH.
+pub fn set_path(custom_path: Option<&Path>) {
+    let default_path = std::env::var_os("ProgramFiles")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\Program Files"))
+        .join(USBIPD_DEFAULT_PATH);
+
+    let path = custom_path
+        .filter(|path| path.is_file())
+        .map(Path::to_path_buf)
+        .or_else(|| default_path.is_file().then_some(default_path))
+        .unwrap_or_else(|| PathBuf::from(USBIPD_EXE));
+
+    *USBIPD_PATH.write().unwrap() = Some(path);
+}
+
+/// Sets the maximum time and the initial poll interval used when waiting for devices
+/// to reach the desired state after an operation. See [`UsbDevice::wait`].
+pub fn set_wait_timing(timeout: Duration, poll_interval: Duration) {
+    *WAIT_TIMING.write().unwrap() = (timeout, poll_interval.max(Duration::from_millis(10)));
+}
+
+/// Returns the path of the `usbipd` executable.
+fn path() -> PathBuf {
+    // Tests choose their path per thread, as they run in parallel
+    #[cfg(test)]
+    if let Some(path) = tests::TEST_PATH.with(|path| path.borrow().clone()) {
+        return path;
     }
+
+    USBIPD_PATH
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(USBIPD_EXE))
 }
 
-/// Checks if `usbipd` is installed in the system.
+/// Checks if `usbipd` is installed in the system, i.e. whether it can be run.
+///
+/// An installed `usbipd` whose version is not recognized still counts as installed,
+/// [`version`] reports that error.
 pub fn check_installed() -> bool {
-    Command::new(USBIPD_EXE)
-        .arg("--version")
-        .creation_flags(CREATE_NO_WINDOW)
-        .status()
-        .is_ok()
+    !matches!(version(), Err(VersionError::NotFound(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    use super::*;
+
+    thread_local! {
+        /// The backend used by the current test instead of the global one, see [`backend`].
+        pub(super) static TEST_BACKEND: Cell<Option<&'static dyn UsbipBackend>> =
+            const { Cell::new(None) };
+
+        /// The `usbipd` path used by the current test instead of the global one, see [`path`].
+        pub(super) static TEST_PATH: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    }
+
+    /// Serializes the tests that rely on the cached version.
+    static GLOBALS: Mutex<()> = Mutex::new(());
+
+    fn lock_globals() -> MutexGuard<'static, ()> {
+        // A failed test must not fail the others
+        GLOBALS.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn use_path(path: &str) {
+        TEST_PATH.with(|test_path| *test_path.borrow_mut() = Some(PathBuf::from(path)));
+    }
+
+    fn use_real_backend() {
+        TEST_BACKEND.with(|backend| backend.set(Some(&RealBackend)));
+    }
+
+    /// Uses a new fake backend on the current thread, with its own sample devices.
+    fn use_fake_backend() {
+        let fake: &'static fake::FakeBackend = Box::leak(Box::new(fake::FakeBackend::new()));
+        TEST_BACKEND.with(|backend| backend.set(Some(fake)));
+    }
+
+    fn find_device(bus_id: &str) -> UsbDevice {
+        list_devices()
+            .unwrap()
+            .into_iter()
+            .find(|d| d.bus_id.as_deref() == Some(bus_id))
+            .unwrap()
+    }
+
+    #[test]
+    fn quote_args_leaves_plain_arguments() {
+        assert_eq!(quote_args(&["bind", "--busid", "1-2"]), "bind --busid 1-2");
+        assert_eq!(
+            quote_args(&[r"C:\usbipd\usbipd.exe"]),
+            r"C:\usbipd\usbipd.exe"
+        );
+    }
+
+    #[test]
+    fn quote_args_quotes_whitespace_and_empty_arguments() {
+        assert_eq!(
+            quote_args(&[r"C:\Program Files\usbipd-win\usbipd.exe", ""]),
+            r#""C:\Program Files\usbipd-win\usbipd.exe" """#
+        );
+    }
+
+    #[test]
+    fn quote_args_escapes_quotes_and_preceding_backslashes() {
+        assert_eq!(quote_args(&[r#"say "hi""#]), r#""say \"hi\"""#);
+        assert_eq!(quote_args(&[r#"a\"b"#]), r#""a\\\"b""#);
+    }
+
+    #[test]
+    fn quote_args_doubles_trailing_backslashes() {
+        assert_eq!(quote_args(&[r"C:\My Dir\"]), r#""C:\My Dir\\""#);
+        assert_eq!(quote_args(&[r"a b\\"]), r#""a b\\\\""#);
+    }
+
+    fn version(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    #[test]
+    fn parse_version_reads_plain_versions() {
+        assert_eq!(parse_version("4.2.0"), Some(version(4, 2, 0)));
+        assert_eq!(parse_version("4.2.0\r\n"), Some(version(4, 2, 0)));
+        assert_eq!(parse_version("v4.3.1"), Some(version(4, 3, 1)));
+    }
+
+    #[test]
+    fn parse_version_ignores_build_metadata_and_pre_release_suffixes() {
+        assert_eq!(parse_version("4.3.0+abc"), Some(version(4, 3, 0)));
+        assert_eq!(parse_version("4.3.0-rc.1"), Some(version(4, 3, 0)));
+        assert_eq!(parse_version("4.3.0-rc.1+1a2b3c4d"), Some(version(4, 3, 0)));
+    }
+
+    #[test]
+    fn parse_version_skips_banners() {
+        assert_eq!(parse_version("usbipd-win 4.2.0"), Some(version(4, 2, 0)));
+        assert_eq!(
+            parse_version("usbipd-win\r\nversion 4.3.0+152.Branch.master\r\n"),
+            Some(version(4, 3, 0))
+        );
+    }
+
+    #[test]
+    fn parse_version_defaults_missing_minor_and_patch() {
+        assert_eq!(parse_version("4"), Some(version(4, 0, 0)));
+        assert_eq!(parse_version("4.1"), Some(version(4, 1, 0)));
+    }
+
+    #[test]
+    fn parse_version_rejects_output_without_a_version() {
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("usbipd-win"), None);
+        assert_eq!(parse_version("'usbipd' is not recognized"), None);
+    }
+
+    #[test]
+    fn missing_usbipd_is_not_installed() {
+        use_real_backend();
+        use_path(r"C:\nonexistent\usbipd-win\usbipd.exe");
+
+        assert!(matches!(super::version(), Err(VersionError::NotFound(_))));
+        assert!(!check_installed());
+    }
+
+    fn device_described(description: &str) -> UsbDevice {
+        UsbDevice {
+            bus_id: Some("1-1".to_owned()),
+            client_ip_address: None,
+            description: Some(description.to_owned()),
+            instance_id: Some(r"USB\VID_1234&PID_5678\0001".to_owned()),
+            is_forced: false,
+            persisted_guid: None,
+            bound_without_guid: false,
+            stub_instance_id: None,
+            speed: None,
+        }
+    }
+
+    #[test]
+    fn is_hub_matches_hub_as_a_word() {
+        assert!(device_described("Generic USB Hub").is_hub());
+        assert!(device_described("USB 3.0 HUB").is_hub());
+        assert!(device_described("Hub (USB-C)").is_hub());
+        assert!(!device_described("GitHub Security Key").is_hub());
+        assert!(!device_described("Hubble Camera").is_hub());
+
+        let mut root_hub = device_described("USB Root Device");
+        root_hub.instance_id = Some(r"USB\ROOT_HUB30\4&1234".to_owned());
+        assert!(root_hub.is_hub());
+    }
+
+    #[test]
+    fn run_until_stops_waiting_at_the_deadline() {
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert_eq!(run_until(deadline, || 42), Some(42));
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+        assert_eq!(
+            run_until(deadline, || std::thread::sleep(Duration::from_secs(10))),
+            None
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn fake_backend_attaches_and_detaches() {
+        use_fake_backend();
+
+        let devices = list_devices().unwrap();
+        assert_eq!(devices.len(), 6);
+        assert_eq!(devices.iter().filter(|d| d.is_attached()).count(), 2);
+
+        let device = find_device("1-2");
+        assert!(device.is_bound() && !device.is_attached());
+        device.attach(None).unwrap();
+        assert!(find_device("1-2").is_attached());
+
+        device.detach().unwrap();
+        let device = find_device("1-2");
+        assert!(device.is_bound() && !device.is_attached());
+    }
+
+    #[test]
+    fn fake_backend_binds_when_attaching() {
+        use_fake_backend();
+
+        let device = find_device("1-1");
+        assert!(!device.is_bound());
+        device.attach(Some("Ubuntu")).unwrap();
+
+        let device = find_device("1-1");
+        assert!(device.is_bound() && !device.is_forced && device.is_attached());
+    }
+
+    #[test]
+    fn versions_are_ordered_by_major_minor_then_patch() {
+        assert!(version(1, 9, 9) < version(2, 0, 0));
+        assert!(version(2, 0, 0) < version(2, 0, 1));
+        assert!(version(2, 0, 9) < version(2, 1, 0));
+        assert!(version(3, 10, 0) > version(3, 9, 0));
+        assert_eq!(version(4, 2, 0), version(4, 2, 0));
+    }
+
+    #[test]
+    fn is_supported_boundaries() {
+        assert_eq!(is_supported(&version(1, 0, 0)), VersionSupport::Unsupported);
+        assert_eq!(
+            is_supported(&version(1, 99, 99)),
+            VersionSupport::Unsupported
+        );
+        assert_eq!(is_supported(&MIN_SUPPORTED), VersionSupport::Supported);
+        assert_eq!(is_supported(&version(2, 0, 0)), VersionSupport::Supported);
+        assert_eq!(is_supported(&version(3, 5, 1)), VersionSupport::Supported);
+        assert_eq!(
+            is_supported(&version(LATEST_TESTED_MAJOR, 0, 0)),
+            VersionSupport::Supported
+        );
+        assert_eq!(
+            is_supported(&version(LATEST_TESTED_MAJOR, 99, 99)),
+            VersionSupport::Supported
+        );
+        assert_eq!(
+            is_supported(&version(LATEST_TESTED_MAJOR + 1, 0, 0)),
+            VersionSupport::Untested
+        );
+    }
+
+    #[test]
+    fn version_is_cached_for_its_path() {
+        let _globals = lock_globals();
+        use_fake_backend();
+        use_path(r"C:\Program Files\usbipd-win\usbipd.exe");
+        *VERSION.write().unwrap() = None;
+
+        let first = super::version().unwrap();
+        assert_eq!(super::version().unwrap(), first);
+        assert_eq!(*VERSION.read().unwrap(), Some((path(), first)));
+
+        // The cached version is returned without asking the backend again
+        *VERSION.write().unwrap() = Some((path(), version(9, 9, 9)));
+        assert_eq!(super::version().unwrap(), version(9, 9, 9));
+
+        // A version retrieved for the previous path is not used for the new one
+        use_path(r"D:\Tools\usbipd.exe");
+        assert_eq!(super::version().unwrap(), first);
+        assert_eq!(*VERSION.read().unwrap(), Some((path(), first)));
+    }
 }
@@ -0,0 +1,207 @@
+//! A fake `usbipd` backend seeded with sample devices, for UI development.
+//!
+//! Operations only change the in-memory device list, nothing is run on the system.
+
+use std::path::Path;
+use std::process::Child;
+use std::sync::Mutex;
+
+use super::{UsbDevice, UsbipBackend, UsbipError, Version, VersionError};
+use crate::logging;
+
+/// The client IP address reported for attached devices.
+const FAKE_CLIENT_IP_ADDRESS: &str = "172.28.160.1";
+
+/// A backend keeping the devices in memory.
+pub(super) struct FakeBackend {
+    devices: Mutex<Vec<UsbDevice>>,
+}
+
+impl FakeBackend {
+    /// Creates a backend seeded with sample devices in all the possible states.
+    pub fn new() -> Self {
+        let devices = vec![
+            device(
+                Some("1-1"),
+                "USB\\VID_046D&PID_C52B\\5&2A3B1C4D&0&1",
+                "Logitech USB Input Device",
+                None,
+                false,
+                false,
+            ),
+            device(
+                Some("1-2"),
+                "USB\\VID_0483&PID_374B\\066DFF535155878281123456",
+                "ST-Link Debug, USB Mass Storage, ST-Link VCP Data",
+                Some(1),
+                false,
+                false,
+            ),
+            device(
+                Some("1-10"),
+                "USB\\VID_10C4&PID_EA60\\0001",
+                "Silicon Labs CP210x USB to UART Bridge (COM3)",
+                Some(2),
+                false,
+                true,
+            ),
+            device(
+                Some("2-3"),
+                "USB\\VID_1A86&PID_7523\\6&1B2C3D4E&0&3",
+                "USB-SERIAL CH340 (COM5)",
+                Some(3),
+                true,
+                true,
+            ),
+            device(
+                Some("2-4"),
+                "USB\\VID_2109&PID_2817\\000000000",
+                "Generic USB Hub",
+                None,
+                false,
+                false,
+            ),
+            device(
+                None,
+                "USB\\VID_0BDA&PID_8153\\000001000000",
+                "Realtek USB GbE Family Controller",
+                Some(4),
+                false,
+                false,
+            ),
+        ];
+
+        Self {
+            devices: Mutex::new(devices),
+        }
+    }
+
+    /// Runs `update` on the connected device with the given bus ID.
+    fn update(
+        &self,
+        bus_id: &str,
+        update: impl FnOnce(&mut UsbDevice) -> Result<(), UsbipError>,
+    ) -> Result<(), UsbipError> {
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices
+            .iter_mut()
+            .find(|d| d.bus_id.as_deref() == Some(bus_id))
+            .ok_or(UsbipError::NotFound)?;
+
+        update(device)
+    }
+}
+
+impl UsbipBackend for FakeBackend {
+    fn list_devices(&self) -> Result<Vec<UsbDevice>, String> {
+        Ok(self.devices.lock().unwrap().clone())
+    }
+
+    fn version(&self, _path: &Path) -> Result<Version, VersionError> {
+        Ok(Version {
+            major: 4,
+            minor: 3,
+            patch: 0,
+        })
+    }
+
+    fn bind(&self, bus_id: &str, force: bool) -> Result<(), UsbipError> {
+        logging::info(format_args!("Fake binding {bus_id}"));
+        self.update(bus_id, |device| {
+            if device.persisted_guid.is_none() {
+                // Derive the GUID from the bus ID, so that it is stable across binds
+                let seed = bus_id
+                    .bytes()
+                    .fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32));
+                device.persisted_guid = Some(fake_guid(seed));
+            }
+            device.is_forced = force;
+            Ok(())
+        })
+    }
+
+    fn unbind(&self, guid: &str) -> Result<(), UsbipError> {
+        logging::info(format_args!("Fake unbinding {guid}"));
+        let mut devices = self.devices.lock().unwrap();
+        let index = devices
+            .iter()
+            .position(|d| d.persisted_guid.as_deref() == Some(guid))
+            .ok_or(UsbipError::NotFound)?;
+
+        // Persisted devices that are not connected disappear once unbound
+        if devices[index].is_connected() {
+            let device = &mut devices[index];
+            device.persisted_guid = None;
+            device.client_ip_address = None;
+            device.is_forced = false;
+        } else {
+            devices.remove(index);
+        }
+        Ok(())
+    }
+
+    fn unbind_bus_id(&self, bus_id: &str) -> Result<(), UsbipError> {
+        let guid = self
+            .devices
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|d| d.bus_id.as_deref() == Some(bus_id))
+            .and_then(|d| d.persisted_guid.clone())
+            .ok_or(UsbipError::NotFound)?;
+
+        self.unbind(&guid)
+    }
+
+    fn attach(&self, bus_id: &str, _distribution: Option<&str>) -> Result<(), UsbipError> {
+        logging::info(format_args!("Fake attaching {bus_id}"));
+        self.update(bus_id, |device| {
+            if !device.is_bound() {
+                return Err(format!("Device with bus ID '{bus_id}' is not shared.").into());
+            }
+            device.client_ip_address = Some(FAKE_CLIENT_IP_ADDRESS.to_owned());
+            Ok(())
+        })
+    }
+
+    fn detach(&self, bus_id: &str) -> Result<(), UsbipError> {
+        logging::info(format_args!("Fake detaching {bus_id}"));
+        self.update(bus_id, |device| {
+            device.client_ip_address = None;
+            Ok(())
+        })
+    }
+
+    fn auto_attach(&self, _bus_id: &str, _distribution: Option<&str>) -> Result<Child, String> {
+        Err("Auto-attach is not available with the fake usbipd backend.".to_owned())
+    }
+}
+
+/// Builds a sample device.
+///
+/// Devices with a `guid_seed` are bound, using a GUID derived from it.
+fn device(
+    bus_id: Option<&str>,
+    instance_id: &str,
+    description: &str,
+    guid_seed: Option<u32>,
+    is_forced: bool,
+    is_attached: bool,
+) -> UsbDevice {
+    UsbDevice {
+        bus_id: bus_id.map(str::to_owned),
+        client_ip_address: is_attached.then(|| FAKE_CLIENT_IP_ADDRESS.to_owned()),
+        description: Some(description.to_owned()),
+        instance_id: Some(instance_id.to_owned()),
+        is_forced,
+        persisted_guid: guid_seed.map(fake_guid),
+        bound_without_guid: false,
+        stub_instance_id: None,
+        speed: None,
+    }
+}
+
+/// Returns a GUID derived from `seed`.
+fn fake_guid(seed: u32) -> String {
+    format!("{seed:08x}-0000-4000-8000-000000000000")
+}
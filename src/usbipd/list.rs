@@ -0,0 +1,263 @@
+//! A parser for the human-readable output of `usbipd list`, used with versions of usbipd
+//! that do not provide the `state` command.
+
+use super::UsbDevice;
+
+/// The client address shown for attached devices, which `usbipd list` does not report.
+const UNKNOWN_CLIENT: &str = "an unknown client";
+
+/// Parses the devices from the output of `usbipd list`.
+///
+/// The output does not include instance IDs, so placeholders are built from the VID:PID
+/// and the bus ID, which is how devices without a serial number are identified anyway.
+/// The persisted GUIDs of connected devices are not included either: bound devices are
+/// flagged with [`UsbDevice::bound_without_guid`], and [`UsbDevice::unbind`] unbinds them
+/// by bus ID instead.
+pub(super) fn parse(output: &str) -> Vec<UsbDevice> {
+    let mut devices = Vec::new();
+    let mut section = "";
+    // The start of each column, taken from the header line of the section
+    let mut columns: Vec<usize> = Vec::new();
+
+    for line in output.lines().map(str::trim_end) {
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') && line.ends_with(':') {
+            section = line.trim_end_matches(':');
+            columns.clear();
+            continue;
+        }
+        if columns.is_empty() {
+            columns = column_starts(line);
+            continue;
+        }
+
+        let fields = split_columns(line, &columns);
+        let device = match (section, fields.as_slice()) {
+            ("Connected", [bus_id, vid_pid, description, state]) => {
+                connected_device(bus_id, vid_pid, description, state)
+            }
+            ("Persisted", [guid, description]) => UsbDevice {
+                bus_id: None,
+                client_ip_address: None,
+                description: Some(description.clone()),
+                instance_id: None,
+                is_forced: false,
+                persisted_guid: Some(guid.clone()),
+                bound_without_guid: false,
+                stub_instance_id: None,
+                speed: None,
+            },
+            _ => continue,
+        };
+        devices.push(device);
+    }
+
+    devices
+}
+
+/// Builds a connected device from the columns of a `usbipd list` row.
+///
+/// States look like `Not shared`, `Shared (forced)` or `Attached`. Older versions append
+/// the WSL distribution to attached states, e.g. `Attached - Ubuntu`.
+fn connected_device(bus_id: &str, vid_pid: &str, description: &str, state: &str) -> UsbDevice {
+    let state = state.to_lowercase();
+    let attached = state.starts_with("attached");
+    let bound = attached || state.starts_with("shared");
+
+    let (vid, pid) = vid_pid.split_once(':').unwrap_or((vid_pid, ""));
+    let instance_id = format!(
+        r"USB\VID_{}&PID_{}\&BUSID_{bus_id}",
+        vid.to_uppercase(),
+        pid.to_uppercase()
+    );
+
+    UsbDevice {
+        bus_id: Some(bus_id.to_owned()),
+        client_ip_address: attached.then(|| UNKNOWN_CLIENT.to_owned()),
+        description: Some(description.to_owned()),
+        instance_id: Some(instance_id),
+        is_forced: state.contains("forced"),
+        persisted_guid: None,
+        bound_without_guid: bound,
+        stub_instance_id: None,
+        speed: None,
+    }
+}
+
+/// Returns the character offsets at which the columns of a header line start.
+///
+/// Columns are separated by at least two spaces, as titles like `VID:PID` contain none.
+fn column_starts(header: &str) -> Vec<usize> {
+    let chars: Vec<char> = header.chars().collect();
+    (0..chars.len())
+        .filter(|&i| chars[i] != ' ' && (i == 0 || (i >= 2 && chars[i - 2..i] == [' ', ' '])))
+        .collect()
+}
+
+/// Splits `line` at the given column offsets, trimming each field.
+fn split_columns(line: &str, columns: &[usize]) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = columns.get(i + 1).copied().unwrap_or(chars.len());
+            let start = start.min(chars.len());
+            let end = end.clamp(start, chars.len());
+            chars[start..end]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_owned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output of usbipd 2.x, which shows the distribution of attached devices.
+    const LIST_2X: &str = concat!(
+        "Connected:\n",
+        "BUSID  VID:PID    DEVICE                                                        STATE\n",
+        "1-1    046d:c52b  Logitech USB Input Device, USB Input Device                   Not shared\n",
+        "1-3    0bda:8153  Realtek USB GbE Family Controller                             Shared\n",
+        "2-4    1050:0407  USB Input Device, Microsoft Usbccid Smartcard Reader (WUDF)   Attached - Ubuntu\n",
+        "\n",
+        "Persisted:\n",
+        "GUID                                  DEVICE\n",
+        "6b0cf4b6-8d5b-4aa0-a1d5-ea23d1cb4bd0  USB Mass Storage Device\n",
+    );
+
+    /// The output of usbipd 3.x, with descriptions containing consecutive spaces.
+    const LIST_3X: &str = concat!(
+        "Connected:\n",
+        "BUSID  VID:PID    DEVICE                                                        STATE\n",
+        "1-2    10c4:ea60  Silicon Labs CP210x  USB to UART Bridge (COM4)                Not shared\n",
+        "1-5    0781:5581  USB Mass Storage Device                                       Shared (forced)\n",
+        "2-4    1050:0407  USB Input Device, Microsoft Usbccid Smartcard Reader (WUDF)   Attached\n",
+        "\n",
+        "Persisted:\n",
+        "GUID                                  DEVICE\n",
+        "3d3c5f8e-1a2b-4c5d-9e8f-0a1b2c3d4e5f  USB  Serial  Device  (COM7)\n",
+        "6b0cf4b6-8d5b-4aa0-a1d5-ea23d1cb4bd0  USB Mass Storage Device\n",
+    );
+
+    /// Returns the bus ID, or the persisted GUID if not connected, and the state of each device.
+    fn states(devices: &[UsbDevice]) -> Vec<(&str, String)> {
+        devices
+            .iter()
+            .map(|d| {
+                let key = d.bus_id.as_deref().or(d.persisted_guid.as_deref());
+                (key.unwrap_or_default(), d.state().to_string())
+            })
+            .collect()
+    }
+
+    fn descriptions(devices: &[UsbDevice]) -> Vec<&str> {
+        devices
+            .iter()
+            .map(|d| d.description.as_deref().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn parses_usbipd_2_list() {
+        let devices = parse(LIST_2X);
+        assert_eq!(
+            states(&devices),
+            [
+                ("1-1", "Not shared".to_owned()),
+                ("1-3", "Shared".to_owned()),
+                ("2-4", "Attached".to_owned()),
+                (
+                    "6b0cf4b6-8d5b-4aa0-a1d5-ea23d1cb4bd0",
+                    "Persisted".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            descriptions(&devices),
+            [
+                "Logitech USB Input Device, USB Input Device",
+                "Realtek USB GbE Family Controller",
+                "USB Input Device, Microsoft Usbccid Smartcard Reader (WUDF)",
+                "USB Mass Storage Device",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_usbipd_3_list() {
+        let devices = parse(LIST_3X);
+        assert_eq!(
+            states(&devices),
+            [
+                ("1-2", "Not shared".to_owned()),
+                ("1-5", "Shared (forced)".to_owned()),
+                ("2-4", "Attached".to_owned()),
+                (
+                    "3d3c5f8e-1a2b-4c5d-9e8f-0a1b2c3d4e5f",
+                    "Persisted".to_owned()
+                ),
+                (
+                    "6b0cf4b6-8d5b-4aa0-a1d5-ea23d1cb4bd0",
+                    "Persisted".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            descriptions(&devices),
+            [
+                "Silicon Labs CP210x  USB to UART Bridge (COM4)",
+                "USB Mass Storage Device",
+                "USB Input Device, Microsoft Usbccid Smartcard Reader (WUDF)",
+                "USB  Serial  Device  (COM7)",
+                "USB Mass Storage Device",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_persisted_devices_have_a_guid() {
+        for device in parse(LIST_3X) {
+            if device.is_connected() {
+                assert_eq!(device.persisted_guid, None);
+                assert_eq!(device.bound_without_guid, device.is_bound());
+            } else {
+                assert!(device.persisted_guid.is_some());
+                assert!(!device.bound_without_guid);
+            }
+        }
+    }
+
+    #[test]
+    fn connected_devices_get_placeholder_instance_ids() {
+        let devices = parse(LIST_3X);
+        assert_eq!(
+            devices[0].instance_id.as_deref(),
+            Some(r"USB\VID_10C4&PID_EA60\&BUSID_1-2")
+        );
+        assert_eq!(devices[0].vid_pid().as_deref(), Some("10C4:EA60"));
+        assert_eq!(
+            devices[2].client_ip_address.as_deref(),
+            Some(UNKNOWN_CLIENT)
+        );
+    }
+
+    #[test]
+    fn empty_sections_have_no_devices() {
+        let output = concat!(
+            "Connected:\n",
+            "BUSID  VID:PID    DEVICE                                                        STATE\n",
+            "\n",
+            "Persisted:\n",
+            "GUID                                  DEVICE\n",
+        );
+        assert!(parse(output).is_empty());
+        assert!(parse("").is_empty());
+    }
+}
@@ -1,30 +1,64 @@
 //! Various Windows utilities.
 
+use std::ffi::c_void;
+use std::path::Path;
 use std::ptr::null_mut;
+use std::time::Duration;
 
 use windows_sys::Win32::{
     Devices::{
         DeviceAndDriverInstallation::{
-            CM_Register_Notification, CM_Unregister_Notification, CM_NOTIFY_ACTION,
+            CM_Get_DevNode_Registry_PropertyW, CM_Get_Device_IDW, CM_Get_Device_Interface_ListW,
+            CM_Get_Device_Interface_List_SizeW, CM_Get_Parent, CM_Locate_DevNodeW,
+            CM_Register_Notification, CM_Unregister_Notification, CM_DRP_ADDRESS,
+            CM_GET_DEVICE_INTERFACE_LIST_PRESENT, CM_LOCATE_DEVNODE_NORMAL, CM_NOTIFY_ACTION,
             CM_NOTIFY_ACTION_DEVICEINTERFACEARRIVAL, CM_NOTIFY_ACTION_DEVICEINTERFACEREMOVAL,
             CM_NOTIFY_EVENT_DATA, CM_NOTIFY_FILTER, CM_NOTIFY_FILTER_0, CM_NOTIFY_FILTER_0_2,
-            CM_NOTIFY_FILTER_TYPE_DEVICEINTERFACE, CR_SUCCESS, HCMNOTIFICATION,
+            CM_NOTIFY_FILTER_TYPE_DEVICEINTERFACE, CR_SUCCESS, HCMNOTIFICATION, MAX_DEVICE_ID_LEN,
         },
-        Usb::GUID_DEVINTERFACE_USB_DEVICE,
+        Usb::{
+            GUID_DEVINTERFACE_USB_DEVICE, GUID_DEVINTERFACE_USB_HUB,
+            IOCTL_USB_GET_NODE_CONNECTION_INFORMATION_EX, USB_DEVICE_SPEED,
+            USB_NODE_CONNECTION_INFORMATION_EX,
+        },
+    },
+    Foundation::{
+        CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, ERROR_FILE_NOT_FOUND, ERROR_SUCCESS,
+        GENERIC_WRITE, HWND, INVALID_HANDLE_VALUE, WAIT_OBJECT_0,
     },
-    Foundation::{GetLastError, ERROR_ALREADY_EXISTS, ERROR_SUCCESS},
+    Storage::FileSystem::{CreateFileW, FILE_SHARE_WRITE, OPEN_EXISTING},
     System::{
+        Console::{AttachConsole, ATTACH_PARENT_PROCESS},
         Diagnostics::Debug::{FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM},
-        Threading::CreateMutexW,
+        Registry::{
+            RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ,
+            RRF_RT_REG_DWORD,
+        },
+        RemoteDesktop::ProcessIdToSessionId,
+        Threading::{
+            CreateEventW, CreateMutexW, GetCurrentProcessId, OpenEventW, SetEvent,
+            WaitForSingleObject, EVENT_MODIFY_STATE, INFINITE,
+        },
+        IO::DeviceIoControl,
+    },
+    UI::{
+        Input::KeyboardAndMouse::{
+            RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL,
+            MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, VK_F1,
+        },
+        Shell::ShellExecuteW,
+        WindowsAndMessaging::{AllowSetForegroundWindow, ASFW_ANY, SW_SHOWNORMAL},
     },
 };
 
+use crate::settings;
+
 /// Acquires a single instance lock for the application. Returns `true` if the lock was acquired.
+///
+/// The lock is scoped to the current session, so that users in different sessions
+/// (e.g. with fast user switching) can each run their own instance.
 pub fn acquire_single_instance_lock() -> bool {
-    // Convert to null-terminated UTF-16 string
-    let mutex_name: Vec<u16> = "WSL_USB_MANAGER_SINGLE_INSTANCE_LOCK\0"
-        .encode_utf16()
-        .collect();
+    let mutex_name = session_object_name("WSL_USB_MANAGER_SINGLE_INSTANCE_LOCK");
 
     let mutex_handle = unsafe { CreateMutexW(null_mut(), 1, mutex_name.as_ptr()) };
     if mutex_handle == 0 {
@@ -38,11 +72,90 @@ pub fn acquire_single_instance_lock() -> bool {
     true
 }
 
-/// Retrieves the last error message from the system.
-pub fn get_last_error_string() -> String {
+/// The name of the event other instances signal to ask the running instance to show its window.
+const SHOW_WINDOW_EVENT_NAME: &str = "WSL_USB_MANAGER_SHOW_WINDOW";
+
+/// How long another instance waits for the running instance to start listening for requests.
+const SHOW_WINDOW_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Returns the null-terminated UTF-16 name of a kernel object scoped to the current session.
+fn session_object_name(name: &str) -> Vec<u16> {
+    // Fall back to session 0 if the session ID cannot be retrieved
+    let mut session_id = 0;
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session_id as *mut _) };
+
+    to_wide(&format!("Local\\{name}_{session_id}"))
+}
+
+/// Calls `callback` on a background thread whenever another instance of the app
+/// asks for the window to be shown with [`request_show_window`].
+pub fn listen_for_show_window_requests<F>(callback: F) -> Result<(), String>
+where
+    F: Fn() + Send + 'static,
+{
+    let event_name = session_object_name(SHOW_WINDOW_EVENT_NAME);
+
+    // Auto-reset, so that each request wakes the listener once
+    let event = unsafe { CreateEventW(null_mut(), 0, 0, event_name.as_ptr()) };
+    if event == 0 {
+        let error_code = unsafe { GetLastError() };
+        return Err(format!(
+            "Failed to listen for other instances: {}",
+            get_error_string(error_code)
+        ));
+    }
+
+    // The event is kept open for the lifetime of the app
+    std::thread::spawn(move || {
+        while unsafe { WaitForSingleObject(event, INFINITE) } == WAIT_OBJECT_0 {
+            callback();
+        }
+    });
+
+    Ok(())
+}
+
+/// Asks the running instance of the app to show its window.
+///
+/// The running instance might still be starting up, so this waits for it to listen for
+/// requests for a short time. Returns `false` if the request could not be delivered.
+pub fn request_show_window() -> bool {
+    let event_name = session_object_name(SHOW_WINDOW_EVENT_NAME);
+    let poll_interval = Duration::from_millis(100);
+
+    let mut waited = Duration::ZERO;
+    let event = loop {
+        let event = unsafe { OpenEventW(EVENT_MODIFY_STATE, 0, event_name.as_ptr()) };
+        if event != 0 {
+            break event;
+        }
+        if waited >= SHOW_WINDOW_TIMEOUT {
+            return false;
+        }
+        std::thread::sleep(poll_interval);
+        waited += poll_interval;
+    };
+
+    // This process was started by the user, let the running instance take the foreground
+    unsafe { AllowSetForegroundWindow(ASFW_ANY) };
+
+    let signaled = unsafe { SetEvent(event) } != 0;
+    unsafe { CloseHandle(event) };
+    signaled
+}
+
+/// Attaches the process to the console of its parent process, if any.
+///
+/// The app is built for the Windows subsystem, so it does not have a console of its own.
+/// Attaching to the parent console makes output visible when run from a terminal.
+pub fn attach_parent_console() {
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+/// Returns the system message of the given Win32 error code.
+pub fn get_error_string(error_code: u32) -> String {
     let mut buffer = [0u16; 256];
 
-    let error_code = unsafe { GetLastError() };
     let msg_slice = unsafe {
         let len = FormatMessageW(
             FORMAT_MESSAGE_FROM_SYSTEM,
@@ -59,6 +172,137 @@ pub fn get_last_error_string() -> String {
     String::from_utf16_lossy(msg_slice).trim_end().to_owned()
 }
 
+/// The registry key listing the programs started when the user logs in.
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// The name of the app entry in the [`RUN_KEY`] registry key.
+const RUN_VALUE_NAME: &str = "WSL USB Manager";
+
+/// Registers the current executable to start minimized when the user logs in,
+/// or removes the registration if `enabled` is `false`.
+///
+/// Registering again updates the stored path, e.g. after the executable was moved.
+pub fn set_start_with_windows(enabled: bool) -> Result<(), String> {
+    let run_key = to_wide(RUN_KEY);
+    let value_name = to_wide(RUN_VALUE_NAME);
+
+    let result = if enabled {
+        let exe_path = std::env::current_exe().map_err(|err| err.to_string())?;
+        // Portable mode might have been enabled by the flag rather than a marker file
+        let portable_flag = if settings::is_portable() {
+            " --portable"
+        } else {
+            ""
+        };
+        let command = to_wide(&format!(
+            "\"{}\" --minimized{portable_flag}",
+            exe_path.display()
+        ));
+        unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                run_key.as_ptr(),
+                value_name.as_ptr(),
+                REG_SZ,
+                command.as_ptr() as *const _,
+                (command.len() * std::mem::size_of::<u16>()) as u32,
+            )
+        }
+    } else {
+        match unsafe {
+            RegDeleteKeyValueW(HKEY_CURRENT_USER, run_key.as_ptr(), value_name.as_ptr())
+        } {
+            // Nothing to remove
+            ERROR_FILE_NOT_FOUND => ERROR_SUCCESS,
+            result => result,
+        }
+    };
+
+    if result == ERROR_SUCCESS {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to update the startup registration: {}",
+            get_error_string(result)
+        ))
+    }
+}
+
+/// The registry key holding the theme chosen in the Windows personalization settings.
+const PERSONALIZE_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+
+/// Returns whether the user chose the dark theme for apps in the Windows settings.
+///
+/// Returns `false` on Windows versions without a dark theme.
+pub fn apps_use_dark_theme() -> bool {
+    let key = to_wide(PERSONALIZE_KEY);
+    let value_name = to_wide("AppsUseLightTheme");
+
+    let mut light_theme: u32 = 1;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value_name.as_ptr(),
+            RRF_RT_REG_DWORD,
+            null_mut(),
+            &mut light_theme as *mut _ as *mut c_void,
+            &mut size as *mut _,
+        )
+    };
+
+    result == ERROR_SUCCESS && light_theme == 0
+}
+
+/// Converts `s` to a null-terminated UTF-16 string.
+pub fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Opens `url` in the default web browser.
+pub fn open_url(url: &str) {
+    let operation = to_wide("open");
+    let url = to_wide(url);
+    unsafe {
+        ShellExecuteW(
+            0,
+            operation.as_ptr(),
+            url.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+}
+
+/// Opens the folder at `path` in Explorer.
+pub fn open_folder(path: &Path) -> Result<(), String> {
+    let operation = to_wide("open");
+    let path_w = to_wide(&path.to_string_lossy());
+    let result = unsafe {
+        ShellExecuteW(
+            0,
+            operation.as_ptr(),
+            path_w.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+
+    // Values greater than 32 indicate success, others are error codes
+    if result > 32 {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to open {}: {}",
+            path.display(),
+            get_error_string(result as u32)
+        ))
+    }
+}
+
 /// Registers a closure to be called when a USB device is connected or disconnected.
 pub fn register_usb_device_notifications(
     callback: impl Fn() + 'static,
@@ -144,3 +388,176 @@ impl Drop for DeviceNotification {
         }
     }
 }
+
+/// Registers a system-wide hotkey for `hwnd`, described by a string like `Ctrl+Alt+U`.
+///
+/// Supported modifiers are `Ctrl`, `Alt`, `Shift` and `Win`, and supported keys are
+/// letters, digits and `F1` to `F24`. At least one modifier is required.
+/// When the hotkey is pressed, `hwnd` receives a `WM_HOTKEY` message with `id` as `wParam`.
+/// The hotkey is unregistered when the returned `HotKey` is dropped.
+pub fn register_hotkey(hwnd: HWND, id: i32, hotkey: &str) -> Result<HotKey, String> {
+    let (modifiers, vk) =
+        parse_hotkey(hotkey).ok_or_else(|| format!("'{hotkey}' is not a valid hotkey."))?;
+
+    if unsafe { RegisterHotKey(hwnd, id, modifiers | MOD_NOREPEAT, vk) } == 0 {
+        let error_code = unsafe { GetLastError() };
+        return Err(format!(
+            "Failed to register the hotkey '{hotkey}': {}",
+            get_error_string(error_code)
+        ));
+    }
+
+    Ok(HotKey { hwnd, id })
+}
+
+/// Parses a hotkey like `Ctrl+Alt+U` into its modifiers and virtual key code.
+fn parse_hotkey(hotkey: &str) -> Option<(HOT_KEY_MODIFIERS, u32)> {
+    let mut modifiers = 0;
+    let mut vk = None;
+
+    for part in hotkey.split('+').map(|part| part.trim().to_uppercase()) {
+        match part.as_str() {
+            "CTRL" | "CONTROL" => modifiers |= MOD_CONTROL,
+            "ALT" => modifiers |= MOD_ALT,
+            "SHIFT" => modifiers |= MOD_SHIFT,
+            "WIN" => modifiers |= MOD_WIN,
+            // Only the last part can be the key
+            _ if vk.is_some() => return None,
+            // Virtual key codes of letters and digits match their ASCII uppercase codes
+            key if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                vk = Some(key.as_bytes()[0] as u32);
+            }
+            key => {
+                let number: u32 = key.strip_prefix('F')?.parse().ok()?;
+                if !(1..=24).contains(&number) {
+                    return None;
+                }
+                vk = Some(VK_F1 as u32 + number - 1);
+            }
+        }
+    }
+
+    if modifiers == 0 {
+        return None;
+    }
+    Some((modifiers, vk?))
+}
+
+/// A registered system-wide hotkey. It is unregistered when dropped.
+pub struct HotKey {
+    hwnd: HWND,
+    id: i32,
+}
+
+impl Drop for HotKey {
+    fn drop(&mut self) {
+        unsafe { UnregisterHotKey(self.hwnd, self.id) };
+    }
+}
+
+/// Returns the speed the USB device with the given instance ID is operating at.
+///
+/// The speed is queried from the hub port the device is connected to.
+/// Returns `None` if the device is not present or its hub cannot be queried.
+pub fn get_usb_device_speed(instance_id: &str) -> Option<USB_DEVICE_SPEED> {
+    let instance_id = to_wide(instance_id);
+    let mut devinst = 0;
+    let result =
+        unsafe { CM_Locate_DevNodeW(&mut devinst, instance_id.as_ptr(), CM_LOCATE_DEVNODE_NORMAL) };
+    if result != CR_SUCCESS {
+        return None;
+    }
+
+    // The address of a USB device is the number of the hub port it is connected to
+    let mut port = 0u32;
+    let mut len = std::mem::size_of::<u32>() as u32;
+    let result = unsafe {
+        CM_Get_DevNode_Registry_PropertyW(
+            devinst,
+            CM_DRP_ADDRESS,
+            null_mut(),
+            &mut port as *mut _ as *mut c_void,
+            &mut len,
+            0,
+        )
+    };
+    if result != CR_SUCCESS {
+        return None;
+    }
+
+    let mut hub = 0;
+    if unsafe { CM_Get_Parent(&mut hub, devinst, 0) } != CR_SUCCESS {
+        return None;
+    }
+    let mut hub_id = [0u16; MAX_DEVICE_ID_LEN as usize + 1];
+    let result = unsafe { CM_Get_Device_IDW(hub, hub_id.as_mut_ptr(), hub_id.len() as u32, 0) };
+    if result != CR_SUCCESS {
+        return None;
+    }
+
+    // Find the path of the hub interface, the list holds null-terminated strings
+    let mut list_len = 0;
+    let result = unsafe {
+        CM_Get_Device_Interface_List_SizeW(
+            &mut list_len,
+            &GUID_DEVINTERFACE_USB_HUB,
+            hub_id.as_ptr(),
+            CM_GET_DEVICE_INTERFACE_LIST_PRESENT,
+        )
+    };
+    if result != CR_SUCCESS || list_len <= 1 {
+        return None;
+    }
+    let mut hub_path = vec![0u16; list_len as usize];
+    let result = unsafe {
+        CM_Get_Device_Interface_ListW(
+            &GUID_DEVINTERFACE_USB_HUB,
+            hub_id.as_ptr(),
+            hub_path.as_mut_ptr(),
+            list_len,
+            CM_GET_DEVICE_INTERFACE_LIST_PRESENT,
+        )
+    };
+    if result != CR_SUCCESS {
+        return None;
+    }
+
+    let hub_handle = unsafe {
+        CreateFileW(
+            hub_path.as_ptr(),
+            GENERIC_WRITE,
+            FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            0,
+        )
+    };
+    if hub_handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let mut info: USB_NODE_CONNECTION_INFORMATION_EX = unsafe { std::mem::zeroed() };
+    info.ConnectionIndex = port;
+    let size = std::mem::size_of::<USB_NODE_CONNECTION_INFORMATION_EX>() as u32;
+    let mut bytes_returned = 0;
+    let result = unsafe {
+        let result = DeviceIoControl(
+            hub_handle,
+            IOCTL_USB_GET_NODE_CONNECTION_INFORMATION_EX,
+            &info as *const _ as *const c_void,
+            size,
+            &mut info as *mut _ as *mut c_void,
+            size,
+            &mut bytes_returned,
+            null_mut(),
+        );
+        CloseHandle(hub_handle);
+        result
+    };
+    if result == 0 {
+        return None;
+    }
+
+    Some(info.Speed as USB_DEVICE_SPEED)
+}
@@ -0,0 +1,181 @@
+//! This module provides functions for interacting with the `wsl` executable.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+
+/// The `wsl` executable name.
+const WSL_EXE: &str = "wsl";
+
+/// The running WSL distributions by the IP addresses assigned to them.
+pub type DistributionsByIp = HashMap<String, Vec<String>>;
+
+/// Returns the names of the installed WSL distributions.
+///
+/// An empty list is returned if WSL is not installed or the list cannot be retrieved.
+pub fn list_distributions() -> Vec<String> {
+    run_list(&["--list", "--quiet"])
+}
+
+/// Returns the running WSL distributions by the IP addresses assigned to them, i.e. the
+/// distributions a usbip client with one of these addresses might be running in.
+///
+/// WSL 2 distributions share the same virtual machine, so several of them can have the
+/// same address. Returns `None` if the distributions or their addresses cannot be retrieved.
+pub fn distributions_by_ip() -> Option<DistributionsByIp> {
+    let mut distributions = DistributionsByIp::new();
+    for distribution in run_lines(&["--list", "--running", "--quiet"])? {
+        let lines = run_lines(&["--distribution", &distribution, "--exec", "hostname", "-I"])?;
+        for address in lines.iter().flat_map(|line| line.split_whitespace()) {
+            distributions
+                .entry(address.to_owned())
+                .or_default()
+                .push(distribution.clone());
+        }
+    }
+    Some(distributions)
+}
+
+/// Runs `wsl` with the given arguments and returns the non-empty lines of its output.
+///
+/// An empty list is returned if `wsl` cannot be run or fails.
+fn run_list(args: &[&str]) -> Vec<String> {
+    run_lines(args).unwrap_or_default()
+}
+
+/// Like [`run_list`], returning `None` if `wsl` cannot be run or fails.
+fn run_lines(args: &[&str]) -> Option<Vec<String>> {
+    let output = Command::new(WSL_EXE)
+        .args(args)
+        .env("WSL_UTF8", "1")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    let lines = decode_output(&output.stdout)
+        .lines()
+        .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}'))
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect();
+    Some(lines)
+}
+
+/// Decodes the output of `wsl`.
+///
+/// Older versions of `wsl` ignore `WSL_UTF8` and always output UTF-16LE,
+/// which is detected by the presence of null bytes.
+fn decode_output(bytes: &[u8]) -> String {
+    if bytes.contains(&0) {
+        let utf16: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&utf16)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Checks whether the WSL kernel supports usbip, without which attached devices
+/// never show up in Linux, by loading the virtual host controller driver.
+///
+/// Returns `None` if no distribution is running, so that WSL is not started just for this,
+/// or if WSL cannot be run.
+pub fn kernel_supports_usbip() -> Option<bool> {
+    if list_running_distributions().is_empty() {
+        return None;
+    }
+
+    // The driver is built into recent WSL kernels, and is a module in custom ones
+    let status = Command::new(WSL_EXE)
+        .args(["--user", "root", "--exec", "sh", "-c"])
+        .arg("test -d /sys/bus/platform/drivers/vhci_hcd || modprobe vhci-hcd")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?
+        .status;
+
+    Some(status.success())
+}
+
+/// Runs `probe_command` inside the given WSL distribution, or the default one if `None`,
+/// to check that attached USB devices will be usable there.
+///
+/// The command is split on whitespace and run without a shell. It fails if WSL cannot be
+/// started or the command exits with an error, e.g. because the USB tools are not installed.
+///
+/// Starting WSL can take a while, the command is killed and `None` is returned
+/// if `cancelled` is set in the meantime.
+pub fn probe_usb_support(
+    distribution: Option<&str>,
+    probe_command: &str,
+    cancelled: &AtomicBool,
+) -> Option<Result<(), String>> {
+    let mut command = Command::new(WSL_EXE);
+    if let Some(distribution) = distribution {
+        command.args(["--distribution", distribution]);
+    }
+
+    let mut child = match command
+        .arg("--exec")
+        .args(probe_command.split_whitespace())
+        .env("WSL_UTF8", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => return Some(Err(format!("Failed to run wsl: {err}"))),
+    };
+
+    // Read on separate threads, so that the command never blocks on a full pipe
+    let stdout_reader = child.stdout.take().map(read_in_background);
+    let stderr_reader = child.stderr.take().map(read_in_background);
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if cancelled.load(Ordering::Relaxed) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(100)),
+            Err(err) => return Some(Err(format!("Failed to run wsl: {err}"))),
+        }
+    };
+    if status.success() {
+        return Some(Ok(()));
+    }
+
+    let output = |reader: Option<JoinHandle<Vec<u8>>>| {
+        let bytes = reader.and_then(|reader| reader.join().ok());
+        decode_output(&bytes.unwrap_or_default())
+    };
+    let stdout = output(stdout_reader);
+    let stderr = output(stderr_reader);
+    let message = match stderr.trim() {
+        "" => stdout.trim().to_owned(),
+        stderr => stderr.to_owned(),
+    };
+    Some(Err(format!("`{probe_command}` failed in WSL: {message}")))
+}
+
+/// Reads all of `pipe` on a separate thread.
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        output
+    })
+}